@@ -0,0 +1,39 @@
+//! The lobby server, holds the rooms online players meet in.
+//! Usage: `lobby [port]`
+
+use std::env;
+use std::net::TcpListener;
+use std::process;
+use tetris_engine::lobby::{Server, DEFAULT_PORT};
+
+fn main() {
+    let port = match env::args().nth(1) {
+        Some(port) => match port.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                eprintln!("Invalid port '{}'", port);
+                process::exit(1);
+            }
+        },
+        None => DEFAULT_PORT,
+    };
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Couldn't listen on port {}: {}", port, e);
+            process::exit(1);
+        }
+    };
+    // the port is looked up in case 0 was given
+    let port = listener.local_addr().map_or(port, |address| address.port());
+    match Server::new(listener) {
+        Ok(server) => {
+            println!("Lobby listening on port {}", port);
+            server.run();
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Plays a tournament between the ai-scripts in a directory and writes the
+//! leaderboard and the results of every pairing.
+//! Usage: `tournament <ai directory> [--bots] [--swiss ROUNDS] [--games N] [--seed N]
+//! [--level N] [--max-ticks N] [--threads N] [--out DIRECTORY]`, `--bots` enters
+//! the built-in bots too
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use tetris_engine::bots::Bot;
+use tetris_engine::game_data::{Rules, TICK_RATE};
+use tetris_engine::game_state::{find_ai_libraries, load_ai, Controller};
+use tetris_engine::tournament::{play_game, Pairing, Settings, Tournament};
+
+struct Options {
+    libraries: PathBuf,
+    bots: bool,
+    out: PathBuf,
+    settings: Settings,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut libraries = None;
+    let mut bots = false;
+    let mut out = PathBuf::from("./tournament");
+    let mut settings = Settings::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut number = |name: &str| -> Result<u64, String> {
+            let value = args.next().ok_or(format!("{} needs a value", name))?;
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value '{}' for {}", value, name))
+        };
+        match arg.as_str() {
+            "--bots" => bots = true,
+            "--swiss" => settings.pairing = Pairing::Swiss(number("--swiss")? as usize),
+            "--games" => settings.games = number("--games")?.max(1) as u32,
+            "--seed" => settings.seed = number("--seed")?,
+            "--level" => {
+                settings.init_level = number("--level")? as usize;
+                if settings.init_level >= Rules::default().levels() {
+                    return Err(format!("Invalid level {}", settings.init_level));
+                }
+            }
+            "--max-ticks" => settings.max_ticks = number("--max-ticks")? as u32,
+            "--threads" => settings.threads = number("--threads")?.max(1) as usize,
+            "--out" => out = PathBuf::from(args.next().ok_or("--out needs a value")?),
+            _ if libraries.is_none() => libraries = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(Options {
+        libraries: libraries.ok_or("Usage: tournament <ai directory> [options]")?,
+        bots,
+        out,
+        settings,
+    })
+}
+
+fn run(options: &Options) -> Result<Tournament, String> {
+    let paths = find_ai_libraries(&[options.libraries.as_path()]);
+    // broken ai-scripts are reported before any game is played
+    for path in &paths {
+        load_ai(path)?;
+    }
+    let mut names: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .map_or(String::new(), |name| name.to_string_lossy().to_string())
+        })
+        .collect();
+    let mut controllers: Vec<Controller> = paths.into_iter().map(Controller::AI).collect();
+    if options.bots {
+        names.extend(Bot::ALL.iter().map(|bot| bot.id().to_string()));
+        controllers.extend(Bot::ALL.iter().map(|bot| Controller::Bot(*bot)));
+    }
+    println!(
+        "Playing {} entrants, {} games a pairing on {} threads",
+        controllers.len(),
+        options.settings.games,
+        options.settings.threads
+    );
+    Tournament::run(names, &options.settings, |first, second, seed| {
+        play_game(
+            &controllers[first],
+            &controllers[second],
+            seed,
+            &options.settings,
+        )
+    })
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let tournament = run(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let ticks: u32 = tournament.games.iter().map(|game| game.ticks).sum();
+    println!(
+        "{} games played, {} minutes of game time",
+        tournament.games.len(),
+        ticks / TICK_RATE / 60
+    );
+    print!("{}", tournament.leaderboard_csv());
+    if let Err(e) = tournament.save(Path::new(&options.out)) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    println!("Saved to {}", options.out.display());
+}
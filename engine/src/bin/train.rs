@@ -0,0 +1,179 @@
+//! Evolves weights for the heuristic bot, saving a checkpoint every generation
+//! and the best weights so far. Running it again with the same `--out` carries
+//! on from the checkpoint, with its settings.
+//! Usage: `train [--versus] [--generations N] [--population N] [--elite N]
+//! [--games N] [--mutation X] [--seed N] [--level N] [--max-ticks N] [--threads N]
+//! [--out DIRECTORY]`
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use tetris_engine::game_data::Rules;
+use tetris_engine::training::{Fitness, Settings, Trainer};
+
+struct Options {
+    generations: usize,
+    /// The options given, to tell which ones a checkpoint overrides
+    given: Vec<String>,
+    out: PathBuf,
+    settings: Settings,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut generations = 20;
+    let mut out = PathBuf::from("./training");
+    let mut settings = Settings::default();
+    let mut given = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        given.push(arg.clone());
+        let mut value = |name: &str| -> Result<String, String> {
+            args.next().ok_or(format!("{} needs a value", name))
+        };
+        let mut number = |name: &str| -> Result<u64, String> {
+            let value = value(name)?;
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value '{}' for {}", value, name))
+        };
+        match arg.as_str() {
+            "--versus" => settings.fitness = Fitness::Versus,
+            "--generations" => generations = number("--generations")? as usize,
+            "--population" => settings.population = number("--population")?.max(2) as usize,
+            "--elite" => settings.elite = number("--elite")? as usize,
+            "--games" => settings.games = number("--games")?.max(1) as u32,
+            "--mutation" => {
+                let mutation = value("--mutation")?;
+                settings.mutation = match mutation.parse() {
+                    Ok(mutation) if mutation > 0.0 => mutation,
+                    _ => return Err(format!("Invalid value '{}' for --mutation", mutation)),
+                };
+            }
+            "--seed" => settings.seed = number("--seed")?,
+            "--level" => {
+                settings.init_level = number("--level")? as usize;
+                if settings.init_level >= Rules::default().levels() {
+                    return Err(format!("Invalid level {}", settings.init_level));
+                }
+            }
+            "--max-ticks" => settings.max_ticks = number("--max-ticks")? as u32,
+            "--threads" => settings.threads = number("--threads")?.max(1) as usize,
+            "--out" => out = PathBuf::from(value("--out")?),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    if settings.elite >= settings.population {
+        return Err("--elite must be below --population".to_string());
+    }
+    Ok(Options {
+        generations,
+        given,
+        out,
+        settings,
+    })
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    fs::create_dir_all(&options.out)
+        .map_err(|e| format!("Couldn't create {}: {}", options.out.display(), e))?;
+    let checkpoint = options.out.join("checkpoint.json");
+    let mut trainer = if checkpoint.exists() {
+        let mut trainer = Trainer::load(&checkpoint)?;
+        println!("Carrying on from generation {}", trainer.generation());
+        // the threads don't change the results, the rest of the run is kept as it was
+        if options.given.iter().any(|arg| arg == "--threads") {
+            trainer.settings.threads = options.settings.threads;
+        }
+        for (option, kept, given) in ignored(options, &trainer.settings) {
+            // --versus is the only option without a value
+            let given = if option == "--versus" {
+                option.to_string()
+            } else {
+                format!("{} {}", option, given)
+            };
+            eprintln!(
+                "Warning: {} is ignored, the checkpoint trains with {} {}",
+                given,
+                option.trim_start_matches('-'),
+                kept
+            );
+        }
+        trainer
+    } else {
+        Trainer::new(options.settings.clone())
+    };
+    println!(
+        "Training {} weights, {} games each on {} threads",
+        trainer.settings.population, trainer.settings.games, trainer.settings.threads
+    );
+    while trainer.generation() < options.generations {
+        let best = trainer.step()?;
+        println!(
+            "Generation {}: best fitness {:.2}",
+            trainer.generation(),
+            best.fitness
+        );
+        trainer.save(&checkpoint)?;
+        best.weights.save(&options.out.join("best.json"))?;
+    }
+    Ok(())
+}
+
+/// The options given that differ from the settings of a checkpoint, with the
+/// value kept and the value given
+fn ignored(options: &Options, kept: &Settings) -> Vec<(&'static str, String, String)> {
+    let given = &options.settings;
+    let fitness = |settings: &Settings| match settings.fitness {
+        Fitness::Solo => "off".to_string(),
+        Fitness::Versus => "on".to_string(),
+    };
+    let settings = [
+        ("--versus", fitness(kept), fitness(given)),
+        (
+            "--population",
+            kept.population.to_string(),
+            given.population.to_string(),
+        ),
+        ("--elite", kept.elite.to_string(), given.elite.to_string()),
+        ("--games", kept.games.to_string(), given.games.to_string()),
+        (
+            "--mutation",
+            kept.mutation.to_string(),
+            given.mutation.to_string(),
+        ),
+        ("--seed", kept.seed.to_string(), given.seed.to_string()),
+        (
+            "--level",
+            kept.init_level.to_string(),
+            given.init_level.to_string(),
+        ),
+        (
+            "--max-ticks",
+            kept.max_ticks.to_string(),
+            given.max_ticks.to_string(),
+        ),
+    ];
+    settings
+        .iter()
+        .filter(|(option, kept, given)| {
+            kept != given && options.given.iter().any(|arg| arg == option)
+        })
+        .cloned()
+        .collect()
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    if let Err(e) = run(&options) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    println!(
+        "The best weights are in {}, play them with heuristic-bot:{0}",
+        options.out.join("best.json").display()
+    );
+}
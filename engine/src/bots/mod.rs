@@ -0,0 +1,241 @@
+//! Bots built into the engine, controllers that play without an ai-script.
+//! They rate every placement of the falling piece by the features of the board
+//! it leaves, weighted like Pierre Dellacherie's bot as tuned for El-Tetris.
+
+use crate::features;
+use crate::game_data::{Action, Player, COLS, ROWS};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// Placements of the falling piece the beam bot looks ahead from
+pub const BEAM_WIDTH: usize = 8;
+
+/// How much each board feature is worth to a bot, higher is better, see
+/// `features` for how they are counted
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Weights {
+    /// How high the piece was placed
+    pub landing_height: f64,
+    /// Rows cleared by the piece times its blocks in those rows
+    pub eroded_cells: f64,
+    /// Filled and empty cells next to each other in a row, the walls count as filled
+    pub row_transitions: f64,
+    /// Filled and empty cells on top of each other in a column, the floor counts as filled
+    pub column_transitions: f64,
+    /// Empty cells with a filled cell above them
+    pub holes: f64,
+    /// Empty cells with filled cells on both sides, deeper ones counting more
+    pub wells: f64,
+}
+
+/// The weights found for El-Tetris
+pub const EL_TETRIS: Weights = Weights {
+    landing_height: -4.500158825082766,
+    eroded_cells: 3.4181268101392694,
+    row_transitions: -3.2178882868487753,
+    column_transitions: -9.348695305445199,
+    holes: -7.899265427351652,
+    wells: -3.3855972247263626,
+};
+
+impl Weights {
+    /// Loads weights saved as json, like the trainer exports them
+    pub fn load(path: &Path) -> Result<Weights, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("{} are not weights: {}", path.display(), e))
+    }
+
+    /// Saves the weights as json
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+}
+
+/// A built-in bot
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bot {
+    /// Places the falling piece where the weighted features are best
+    Heuristic(Weights),
+    /// Also places the next piece on each of the `BEAM_WIDTH` best boards and
+    /// picks the best pair of placements
+    Beam(Weights),
+}
+
+impl Bot {
+    /// Every bot with the default weights
+    pub const ALL: [Bot; 2] = [Bot::Heuristic(EL_TETRIS), Bot::Beam(EL_TETRIS)];
+
+    /// The name shown in menus
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bot::Heuristic(_) => "Heuristic bot",
+            Bot::Beam(_) => "Beam bot",
+        }
+    }
+
+    /// The name used for the bot in configs and on the command line
+    pub fn id(&self) -> &'static str {
+        match self {
+            Bot::Heuristic(_) => "heuristic-bot",
+            Bot::Beam(_) => "beam-bot",
+        }
+    }
+
+    /// The bot with the given id and the default weights
+    pub fn from_id(id: &str) -> Option<Bot> {
+        Bot::ALL.iter().find(|bot| bot.id() == id).copied()
+    }
+
+    /// The same bot with other weights
+    pub fn with_weights(&self, weights: Weights) -> Bot {
+        match self {
+            Bot::Heuristic(_) => Bot::Heuristic(weights),
+            Bot::Beam(_) => Bot::Beam(weights),
+        }
+    }
+}
+
+/// A bot playing a player. It plans the actions for a piece when it appears
+/// and then performs one a tick, like an ai-script.
+pub(crate) struct Pilot {
+    bot: Bot,
+    /// The actions left for the falling piece, the next one last
+    plan: Vec<Action>,
+}
+
+impl Pilot {
+    pub(crate) fn new(bot: Bot) -> Pilot {
+        Pilot {
+            bot,
+            plan: Vec::new(),
+        }
+    }
+
+    /// Forgets the plan, when the player was replaced
+    pub(crate) fn reset(&mut self) {
+        self.plan.clear();
+    }
+
+    /// The action to perform this tick, if any
+    pub(crate) fn next_action(&mut self, player: &Player) -> Option<Action> {
+        // a dropped piece is left alone until the next one appears
+        if self.plan.is_empty() && player.at_spawn() && !player.get_lost() {
+            self.plan = plan(self.bot, player);
+            self.plan.reverse();
+        }
+        self.plan.pop()
+    }
+}
+
+/// A way to place the falling piece
+struct Candidate {
+    /// The actions that place it, ending with a hard drop
+    actions: Vec<Action>,
+    /// The player with the piece dropped but not yet placed
+    dropped: Player,
+}
+
+/// The actions the bot performs for the falling piece
+fn plan(bot: Bot, player: &Player) -> Vec<Action> {
+    let mut rated: Vec<(f64, Candidate)> = candidates(player, true)
+        .into_iter()
+        .map(|candidate| {
+            let weights = match bot {
+                Bot::Heuristic(weights) | Bot::Beam(weights) => weights,
+            };
+            (rate(&candidate.dropped, &weights), candidate)
+        })
+        .collect();
+    rated.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let best = match bot {
+        Bot::Heuristic(_) => rated.into_iter().next(),
+        Bot::Beam(weights) => rated
+            .into_iter()
+            .take(BEAM_WIDTH)
+            .map(|(score, candidate)| {
+                // the next piece is placed without holding, holding could
+                // bring in a piece that isn't shown yet
+                let after = candidate.dropped.after_placing();
+                let next = candidates(&after, false)
+                    .iter()
+                    .map(|next| rate(&next.dropped, &weights))
+                    .fold(f64::NEG_INFINITY, f64::max);
+                (score + next, candidate)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0)),
+    };
+    best.map_or(vec![Action::HardDrop], |(_, candidate)| candidate.actions)
+}
+
+/// Every place the falling piece can be put, and the saved piece too if `hold`
+/// is set, found by the placement generator
+fn candidates(player: &Player, hold: bool) -> Vec<Candidate> {
+    let mut seen: Vec<[[i32; 2]; 4]> = Vec::new();
+    player
+        .placements()
+        .into_iter()
+        .filter(|placement| hold || !placement.hold)
+        .filter_map(|placement| {
+            // a spin into a spot leaves the same board as sliding into it
+            if seen.contains(&placement.blocks) {
+                return None;
+            }
+            seen.push(placement.blocks);
+            let mut dropped = player.clone();
+            for action in &placement.actions {
+                dropped.perform(*action);
+            }
+            Some(Candidate {
+                actions: placement.actions,
+                dropped,
+            })
+        })
+        .collect()
+}
+
+/// How good it is to place the dropped piece where it is
+fn rate(dropped: &Player, weights: &Weights) -> f64 {
+    let blocks = dropped.get_current_shape();
+    // pieces reaching into the top rows end the game
+    if blocks.iter().any(|[_, y]| *y >= ROWS as i32 - 4) {
+        return f64::NEG_INFINITY;
+    }
+    let mut board = dropped.get_board();
+    for [x, y] in &blocks {
+        board[*y as usize][*x as usize] = 1;
+    }
+    let full: Vec<usize> = (0..ROWS)
+        .filter(|y| board[*y].iter().all(|cell| *cell != 0))
+        .collect();
+    let eroded = full.len()
+        * blocks
+            .iter()
+            .filter(|[_, y]| full.contains(&(*y as usize)))
+            .count();
+    // the board after the full rows are cleared
+    let mut cleared = [[0; COLS]; ROWS];
+    for (row, cleared) in board
+        .iter()
+        .enumerate()
+        .filter(|(y, _)| !full.contains(y))
+        .map(|(_, row)| row)
+        .zip(cleared.iter_mut())
+    {
+        *cleared = *row;
+    }
+    let low = blocks.iter().map(|[_, y]| *y).min().unwrap_or(0);
+    let high = blocks.iter().map(|[_, y]| *y).max().unwrap_or(0);
+    weights.landing_height * (low + high) as f64 / 2.0
+        + weights.eroded_cells * eroded as f64
+        + weights.row_transitions * features::row_transitions(&cleared) as f64
+        + weights.column_transitions * features::column_transitions(&cleared) as f64
+        + weights.holes * features::holes(&cleared) as f64
+        + weights.wells * features::wells(&cleared) as f64
+}
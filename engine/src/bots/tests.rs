@@ -0,0 +1,59 @@
+use super::{Bot, Weights, EL_TETRIS};
+use crate::game_data::Rules;
+use crate::game_state::{Controller, Game, GameMode};
+use std::env;
+
+#[test]
+fn ids() {
+    for bot in &Bot::ALL {
+        assert_eq!(Some(*bot), Bot::from_id(bot.id()));
+    }
+    assert_eq!(None, Bot::from_id("keyboard"));
+    assert_eq!(
+        Some(Bot::Heuristic(EL_TETRIS)),
+        Bot::from_id("heuristic-bot")
+    );
+}
+
+#[test]
+fn weights_file() {
+    let weights = Weights {
+        holes: -1.0,
+        ..EL_TETRIS
+    };
+    let path = env::temp_dir().join("tetris-bot-weights.json");
+    weights.save(&path).unwrap();
+    assert_eq!(weights, Weights::load(&path).unwrap());
+    let arg = format!("beam-bot:{}", path.display());
+    assert!(Controller::from_arg(&arg).unwrap() == Controller::Bot(Bot::Beam(weights)));
+    assert!(Controller::from_arg("heuristic-bot:missing.json").is_err());
+    assert!(Controller::from_arg("ai.so").unwrap() == Controller::AI("ai.so".into()));
+}
+
+/// Plays a marathon game with the bot for a while, the bots clear lines and
+/// don't top out on an empty board
+fn clears_lines(bot: Bot) {
+    let mut game = Game::new(
+        &Rules::default(),
+        0,
+        GameMode::Marathon,
+        vec![Controller::Bot(bot), Controller::Bot(bot)],
+        7,
+    )
+    .unwrap();
+    for _ in 0..3000 {
+        game.update();
+    }
+    assert!(!game.get_losts()[0], "{} topped out", bot.name());
+    assert!(game.get_scores()[0] > 0, "{} cleared nothing", bot.name());
+}
+
+#[test]
+fn heuristic_bot() {
+    clears_lines(Bot::Heuristic(EL_TETRIS));
+}
+
+#[test]
+fn beam_bot() {
+    clears_lines(Bot::Beam(EL_TETRIS));
+}
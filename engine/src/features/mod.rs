@@ -0,0 +1,193 @@
+//! Features of a board used to rate it, by the bots and by ai-scripts through
+//! `board_features`. They work on boards like those of `Player::get_board`,
+//! `ROWS` rows of `COLS` cells from the bottom up where any value but 0 is a
+//! block. The walls and the floor count as filled and the rows above the board
+//! as empty.
+
+use crate::game_data::{COLS, ROWS};
+
+#[cfg(test)]
+mod tests;
+
+/// A board, the bottom row first
+pub type Board = [[u32; COLS]; ROWS];
+
+/// The signature of `board_features`, what ai-scripts get handed with `ai_features`
+pub type FeaturesFn = unsafe extern "C" fn(*const Board) -> Features;
+
+/// Every feature of a board, laid out like a C struct for ai-scripts
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+    /// The heights of all columns added up
+    pub aggregate_height: u32,
+    /// The height differences of neighbouring columns added up
+    pub bumpiness: u32,
+    /// Empty cells with a filled cell above them
+    pub holes: u32,
+    /// Empty cells with filled cells on both sides, deeper ones counting more
+    pub wells: u32,
+    /// Filled and empty cells next to each other in a row
+    pub row_transitions: u32,
+    /// Filled and empty cells on top of each other in a column
+    pub column_transitions: u32,
+    /// Filled cells with a hole below them
+    pub covered_cells: u32,
+    /// Places a t piece pointing down fits with at least three of its corners filled
+    pub t_slots: u32,
+}
+
+impl Features {
+    /// Computes every feature of a board
+    pub fn new(board: &Board) -> Features {
+        Features {
+            aggregate_height: aggregate_height(board),
+            bumpiness: bumpiness(board),
+            holes: holes(board),
+            wells: wells(board),
+            row_transitions: row_transitions(board),
+            column_transitions: column_transitions(board),
+            covered_cells: covered_cells(board),
+            t_slots: t_slots(board),
+        }
+    }
+}
+
+/// The features of a board for ai-scripts, all 0 for a null pointer
+///
+/// # Safety
+/// `board` has to be null or point to a board
+pub unsafe extern "C" fn board_features(board: *const Board) -> Features {
+    match board.as_ref() {
+        Some(board) => Features::new(board),
+        None => Features::default(),
+    }
+}
+
+/// Whether a cell is filled, the walls and the floor are
+fn filled(board: &Board, x: i32, y: i32) -> bool {
+    x < 0 || x >= COLS as i32 || y < 0 || (y < ROWS as i32 && board[y as usize][x as usize] != 0)
+}
+
+/// How high each column is filled, the row above its highest block
+pub fn column_heights(board: &Board) -> [u32; COLS] {
+    let mut heights = [0; COLS];
+    for (x, height) in heights.iter_mut().enumerate() {
+        *height = (0..ROWS)
+            .rev()
+            .find(|y| board[*y][x] != 0)
+            .map_or(0, |y| y as u32 + 1);
+    }
+    heights
+}
+
+/// The heights of all columns added up
+pub fn aggregate_height(board: &Board) -> u32 {
+    column_heights(board).iter().sum()
+}
+
+/// The height differences of neighbouring columns added up
+pub fn bumpiness(board: &Board) -> u32 {
+    column_heights(board)
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum()
+}
+
+/// Empty cells with a filled cell above them
+pub fn holes(board: &Board) -> u32 {
+    let mut holes = 0;
+    for x in 0..COLS as i32 {
+        let mut covered = false;
+        for y in (0..ROWS as i32).rev() {
+            let cell = filled(board, x, y);
+            if !cell && covered {
+                holes += 1;
+            }
+            covered |= cell;
+        }
+    }
+    holes
+}
+
+/// Filled cells with a hole below them
+pub fn covered_cells(board: &Board) -> u32 {
+    let mut covered = 0;
+    for x in 0..COLS as i32 {
+        let mut above = 0;
+        for y in (0..ROWS as i32).rev() {
+            if filled(board, x, y) {
+                above += 1;
+            } else if above > 0 {
+                // the cells counted so far are all above this hole
+                covered += above;
+                above = 0;
+            }
+        }
+    }
+    covered
+}
+
+/// Empty cells with filled cells on both sides, covered or not. A cell at the
+/// bottom of a well counts as deep as the well is there, so a well three deep
+/// counts 1 + 2 + 3.
+pub fn wells(board: &Board) -> u32 {
+    let mut wells = 0;
+    for x in 0..COLS as i32 {
+        let mut depth = 0;
+        for y in (0..ROWS as i32).rev() {
+            let well = !filled(board, x, y) && filled(board, x - 1, y) && filled(board, x + 1, y);
+            depth = if well { depth + 1 } else { 0 };
+            wells += depth;
+        }
+    }
+    wells
+}
+
+/// Filled and empty cells next to each other in a row, an empty row counts 2
+pub fn row_transitions(board: &Board) -> u32 {
+    let mut transitions = 0;
+    for y in 0..ROWS as i32 {
+        for x in 0..=COLS as i32 {
+            if filled(board, x - 1, y) != filled(board, x, y) {
+                transitions += 1;
+            }
+        }
+    }
+    transitions
+}
+
+/// Filled and empty cells on top of each other in a column, an empty column counts 1
+pub fn column_transitions(board: &Board) -> u32 {
+    let mut transitions = 0;
+    for x in 0..COLS as i32 {
+        for y in 0..ROWS as i32 {
+            if filled(board, x, y - 1) != filled(board, x, y) {
+                transitions += 1;
+            }
+        }
+    }
+    transitions
+}
+
+/// Places a t piece pointing down fits, its three cells in a row and the one
+/// below them empty, with at least three of the corners around the middle
+/// filled. Those are the places a t-spin can be done.
+pub fn t_slots(board: &Board) -> u32 {
+    let mut slots = 0;
+    for y in 1..ROWS as i32 - 1 {
+        for x in 1..COLS as i32 - 1 {
+            let fits = [(x - 1, y), (x, y), (x + 1, y), (x, y - 1)]
+                .iter()
+                .all(|&(x, y)| !filled(board, x, y));
+            let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+                .iter()
+                .filter(|&&(dx, dy)| filled(board, x + dx, y + dy))
+                .count();
+            if fits && corners >= 3 {
+                slots += 1;
+            }
+        }
+    }
+    slots
+}
@@ -0,0 +1,73 @@
+use super::{
+    aggregate_height, board_features, bumpiness, column_heights, column_transitions, covered_cells,
+    holes, row_transitions, t_slots, wells, Board, Features,
+};
+use crate::game_data::{COLS, ROWS};
+use std::ptr;
+
+#[test]
+fn empty_board() {
+    let board: Board = [[0; COLS]; ROWS];
+    assert_eq!(
+        Features {
+            row_transitions: 2 * ROWS as u32,
+            column_transitions: COLS as u32,
+            ..Features::default()
+        },
+        Features::new(&board)
+    );
+    assert_eq!(Features::default(), unsafe { board_features(ptr::null()) });
+}
+
+#[test]
+fn heights_and_holes() {
+    let mut board: Board = [[0; COLS]; ROWS];
+    // a bottom row with one gap in the middle and a block covering it
+    board[0] = [1; COLS];
+    board[0][4] = 0;
+    board[1][4] = 1;
+    assert_eq!([1, 1, 1, 1, 2, 1, 1, 1, 1, 1], column_heights(&board));
+    assert_eq!(11, aggregate_height(&board));
+    assert_eq!(2, bumpiness(&board));
+    assert_eq!(1, holes(&board));
+    assert_eq!(1, covered_cells(&board));
+    // two around the gap, four around the block and two in every empty row
+    assert_eq!(2 + 4 + (ROWS as u32 - 2) * 2, row_transitions(&board));
+    // the top of every column and both sides of the hole
+    assert_eq!(COLS as u32 + 2, column_transitions(&board));
+    // the gap has filled cells on both sides, covered or not
+    assert_eq!(1, wells(&board));
+
+    // two blocks on top of two holes
+    board[2][4] = 1;
+    board[0][7] = 0;
+    board[1][7] = 1;
+    assert_eq!(2, holes(&board));
+    assert_eq!(3, covered_cells(&board));
+}
+
+#[test]
+fn wells_and_slots() {
+    let mut board: Board = [[0; COLS]; ROWS];
+    for row in board.iter_mut().take(3) {
+        *row = [1; COLS];
+        row[0] = 0;
+    }
+    // a well three deep at the wall
+    assert_eq!(1 + 2 + 3, wells(&board));
+    assert_eq!(0, t_slots(&board));
+
+    // a t-slot with an overhang on the left, the one the placement tests spin into
+    let mut board: Board = [[0; COLS]; ROWS];
+    for row in board.iter_mut().take(3) {
+        *row = [1; COLS];
+    }
+    for [x, y] in &[[4, 0], [3, 1], [4, 1], [5, 1], [4, 2], [5, 2]] {
+        board[*y][*x] = 0;
+    }
+    assert_eq!(1, t_slots(&board));
+    assert_eq!(Features::new(&board), unsafe { board_features(&board) });
+    // without the overhang two corners are left
+    board[2][3] = 0;
+    assert_eq!(0, t_slots(&board));
+}
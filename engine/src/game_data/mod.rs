@@ -0,0 +1,995 @@
+//! The rules of a single player: the board, the falling pieces, scoring and garbage.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+
+#[cfg(test)]
+mod tests;
+
+/// What a cell of a board holds, the boards returned by the engine use these values
+#[derive(Copy, Clone, Hash, Serialize, Deserialize)]
+pub enum Color {
+    /// An empty cell
+    Void = 0,
+    /// The I tetromino
+    Color1 = 1,
+    /// The O tetromino
+    Color2 = 2,
+    /// The T tetromino
+    Color3 = 3,
+    /// The S tetromino
+    Color4 = 4,
+    /// The Z tetromino
+    Color5 = 5,
+    /// The J tetromino
+    Color6 = 6,
+    /// The L tetromino
+    Color7 = 7,
+    /// Garbage sent by the opponent
+    Fixed = 8,
+    /// The ghost of the I tetromino
+    Shadow1 = 9,
+    /// The ghost of the O tetromino
+    Shadow2 = 10,
+    /// The ghost of the T tetromino
+    Shadow3 = 11,
+    /// The ghost of the S tetromino
+    Shadow4 = 12,
+    /// The ghost of the Z tetromino
+    Shadow5 = 13,
+    /// The ghost of the J tetromino
+    Shadow6 = 14,
+    /// The ghost of the L tetromino
+    Shadow7 = 15,
+}
+
+/// Everything a player can do with a piece
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    /// Moves the piece one column to the left
+    MoveLeft,
+    /// Moves the piece one column to the right
+    MoveRight,
+    /// Rotates the piece clockwise, kicking it away from walls
+    RotateClockwise,
+    /// Rotates the piece counter-clockwise, kicking it away from walls
+    RotateCounterClockwise,
+    /// Moves the piece one row down
+    SoftDrop,
+    /// Drops the piece to the bottom and places it
+    HardDrop,
+    /// Swaps the piece with the saved one, once per piece
+    Hold,
+}
+
+impl Action {
+    /// Every action, in the order of the ai-script outputs
+    pub const ALL: [Action; 7] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Hold,
+    ];
+
+    /// The name shown in menus
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::RotateClockwise => "Rotate clockwise",
+            Action::RotateCounterClockwise => "Rotate counter-clockwise",
+            Action::SoftDrop => "Move down",
+            Action::HardDrop => "Instant drop",
+            Action::Hold => "Save piece",
+        }
+    }
+
+    /// Translates the output of an ai-script, see the table in the README
+    pub fn from_ai_output(output: u32) -> Option<Action> {
+        match output {
+            1..=7 => Some(Action::ALL[output as usize - 1]),
+            _ => None,
+        }
+    }
+}
+
+type Point = [i32; 2];
+type Shape = [Point; 4];
+
+/// How many lines a piece cleared at once
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClearKind {
+    /// One line
+    Single,
+    /// Two lines
+    Double,
+    /// Three lines
+    Triple,
+    /// Four lines
+    Tetris,
+}
+
+/// Something that happened to a player, see `Game::take_events`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameEvent {
+    /// The falling piece was placed on the board
+    PieceLocked {
+        /// The x and y of its blocks, rows are counted from the bottom
+        blocks: [[i32; 2]; 4],
+    },
+    /// The placed piece cleared lines
+    LinesCleared {
+        /// How many lines were cleared
+        kind: ClearKind,
+        /// Bit `i` is set if row `i` was cleared, counted from the bottom
+        rows: u32,
+    },
+    /// Garbage was sent to the opponent, it arrives after `Rules::attack_delay` gravity ticks
+    GarbageSent {
+        /// Lines of garbage
+        lines: u8,
+    },
+    /// Garbage rose from the bottom of the board
+    GarbageReceived {
+        /// Lines of garbage
+        lines: u8,
+    },
+    /// The falling piece was swapped with the saved one
+    Hold,
+    /// The score reached a new level, the pieces fall faster
+    LevelUp {
+        /// The new level
+        level: usize,
+    },
+    /// The stack reached the hidden rows, the player is out
+    TopOut,
+}
+
+/// The default of `Rules::attack_delay`
+pub const ATTACK_DELAY: u8 = 6; //Osäker på denna. nu processeras även attacks med move_tick.
+/// The default of `Rules::grace_delay`
+pub const GRACE_DELAY: u8 = 4;
+
+/// Game ticks per second, the engine only advances in whole ticks so it is deterministic
+pub const TICK_RATE: u32 = 60;
+
+/// Rows of a board, the top four are hidden and a piece placed in them tops out
+pub const ROWS: usize = 24;
+/// Columns of a board
+pub const COLS: usize = 10;
+
+pub(crate) const SHAPES: [Shape; 7] = [
+    //I
+    [[-2, 0], [-1, 0], [0, 0], [1, 0]],
+    //O
+    [[-1, 0], [-1, -1], [0, -1], [0, 0]],
+    //T
+    [[-1, 0], [0, 0], [0, -1], [1, 0]],
+    //S
+    [[-1, 0], [0, 0], [0, -1], [1, -1]],
+    //Z
+    [[-1, -1], [0, -1], [0, 0], [1, 0]],
+    //J
+    [[-1, -1], [-1, 0], [0, 0], [1, 0]],
+    //L
+    [[-1, 0], [0, 0], [1, 0], [1, -1]],
+];
+
+/// The default of `Rules::gravity`
+pub const TIME_LEVELS: [f64; 20] = [
+    1.0, 0.79300, 0.61780, 0.47273, 0.35520, 0.26200, 0.18968, 0.13473, 0.09388, 0.06415, 0.04298,
+    0.02822, 0.01815, 0.01144, 0.00706, 0.00426, 0.00252, 0.00146, 0.00082, 0.00046,
+];
+
+/// The tunable rules of the game, the defaults are the constants above
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    /// Gravity ticks before an incoming attack is added to the board
+    pub attack_delay: u8,
+    /// Gravity ticks a piece can rest on the stack before it is placed
+    pub grace_delay: u8,
+    /// Attack sent for clearing 1, 2, 3 and 4 lines
+    pub attack_table: [u8; 4],
+    /// Score for clearing 1, 2, 3 and 4 lines
+    pub score_table: [usize; 4],
+    /// Score needed to advance one level
+    pub score_per_level: usize,
+    /// Seconds between gravity ticks for each level
+    pub gravity: Vec<f64>,
+    /// Ticks the cleared rows stay on the board before they are removed and the
+    /// next piece appears, gives the frontends time to animate the clear
+    pub clear_delay: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            attack_delay: ATTACK_DELAY,
+            grace_delay: GRACE_DELAY,
+            attack_table: [0, 1, 2, 4],
+            score_table: [1, 3, 5, 8],
+            score_per_level: 5,
+            gravity: TIME_LEVELS.to_vec(),
+            clear_delay: 0,
+        }
+    }
+}
+
+impl Rules {
+    /// Checks that the rules can be played with
+    pub fn validate(&self) -> Result<(), String> {
+        if self.gravity.is_empty() {
+            return Err("gravity must have at least one level".to_string());
+        }
+        if let Some(level) = self
+            .gravity
+            .iter()
+            .position(|time| time.is_nan() || *time <= 0.0)
+        {
+            return Err(format!(
+                "gravity of level {} must be more than 0 seconds, found {}",
+                level, self.gravity[level]
+            ));
+        }
+        if self.score_per_level == 0 {
+            return Err("score_per_level must be at least 1".to_string());
+        }
+        if self.attack_delay == 0 {
+            return Err("attack_delay must be at least 1".to_string());
+        }
+        if let Some(attack) = self
+            .attack_table
+            .iter()
+            .find(|attack| **attack as usize > ROWS - 4)
+        {
+            return Err(format!(
+                "attack_table can't send more than {} rows, found {}",
+                ROWS - 4,
+                attack
+            ));
+        }
+        Ok(())
+    }
+
+    /// Seconds between gravity ticks, levels past the end of the curve use the last value
+    pub fn gravity(&self, level: usize) -> f64 {
+        self.gravity[level.min(self.gravity.len() - 1)]
+    }
+
+    /// Levels with their own gravity
+    pub fn levels(&self) -> usize {
+        self.gravity.len()
+    }
+}
+
+/// A place the falling piece can be put, see `Game::placements`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    /// The blocks of the placed piece on the board, sorted
+    pub blocks: [[i32; 2]; 4],
+    /// The fewest inputs that place the piece there, ending with a hard drop
+    pub actions: Vec<Action>,
+    /// Set if the saved piece is placed, the inputs then start with a hold
+    pub hold: bool,
+    /// Set if the last input turned the piece into a spot it can't move out of
+    /// sideways or up
+    pub spin: bool,
+}
+
+/// Random numbers that can be saved with the rest of the state, every draw
+/// seeds a new generator from the seed and the amount of draws so far
+#[derive(Clone, Hash, Serialize, Deserialize)]
+struct SeedStream {
+    seed: u64,
+    draws: u64,
+}
+
+impl SeedStream {
+    fn next(&mut self) -> StdRng {
+        let rng = StdRng::seed_from_u64(self.seed.wrapping_add(self.draws));
+        self.draws += 1;
+        rng
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Player {
+    rules: Rules,
+    rng: SeedStream,
+    board: [[u32; COLS]; ROWS],
+    incoming: Vec<(u8, u8)>,
+    outgoing: Option<(u8, u8)>,
+    current_piece: Piece,
+    piece_shadow: Option<Piece>,
+    saved_piece: Option<Piece>,
+    has_saved: bool,
+    next_piece: Piece,
+    score: usize,
+    level: usize,
+    lost: bool,
+    gravity: f64,
+    /// Ticks since the last gravity tick
+    update_timer: u32,
+    grace_count: u8,
+    /// Ticks left until the cleared rows are removed, the player can't move meanwhile
+    clearing: u32,
+    /// What happened since the events were last taken, not part of the state
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+}
+
+impl Player {
+    /// Players created with the same seed get the same pieces
+    pub fn new(level: usize, rules: &Rules, seed: u64) -> Player {
+        let mut rng = SeedStream { seed, draws: 0 };
+        let current_piece = Piece::random_piece(&mut rng.next());
+        let next_piece = Piece::random_piece(&mut rng.next());
+        Player {
+            rules: rules.clone(),
+            rng,
+            board: [[0; COLS]; ROWS],
+            incoming: Vec::new(),
+            outgoing: None,
+            current_piece,
+            piece_shadow: None,
+            saved_piece: None,
+            has_saved: false,
+            next_piece,
+            score: 0,
+            level,
+            lost: false,
+            gravity: rules.gravity(level),
+            update_timer: 0,
+            grace_count: 0,
+            clearing: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Advances the player one game tick
+    pub fn update(&mut self) {
+        self.shadow_piece();
+        if self.clearing > 0 && !self.lost {
+            self.clearing -= 1;
+            if self.clearing == 0 {
+                self.remove_full_rows();
+                self.next_piece();
+            }
+        } else if !self.lost {
+            self.update_timer += 1;
+            if self.update_timer as f64 >= self.gravity * TICK_RATE as f64 {
+                self.process_attacks();
+                self.move_tick();
+                self.update_timer = 0;
+            }
+        }
+    }
+
+    pub fn move_tick(&mut self) {
+        if !self.lost {
+            self.current_piece.mov(0, -1);
+            if !self.valid_pos(&self.current_piece) {
+                self.current_piece.mov(0, 1);
+                if self.grace_count >= self.rules.grace_delay {
+                    self.place_piece(None);
+                    self.process_lines();
+                    if self.clearing == 0 {
+                        self.next_piece();
+                    }
+                    self.grace_count = 0;
+                } else {
+                    self.grace_count += 1;
+                }
+            }
+        }
+    }
+
+    fn process_lines(&mut self) {
+        let full_rows = self.full_rows();
+        if !full_rows.is_empty() {
+            let kind = [
+                ClearKind::Single,
+                ClearKind::Double,
+                ClearKind::Triple,
+                ClearKind::Tetris,
+            ][full_rows.len().min(4) - 1];
+            let rows = full_rows.iter().fold(0, |rows, row| rows | 1 << row);
+            self.events.push(GameEvent::LinesCleared { kind, rows });
+            self.process_score(full_rows.len());
+            if self.rules.clear_delay > 0 {
+                self.clearing = self.rules.clear_delay;
+            } else {
+                self.remove_full_rows();
+            }
+        }
+    }
+
+    fn full_rows(&self) -> Vec<usize> {
+        let mut full_rows: Vec<usize> = Vec::new();
+        for i in 0..self.board.len() {
+            if !self.board[i].contains(&0) {
+                full_rows.push(i);
+            }
+        }
+        full_rows
+    }
+
+    fn remove_full_rows(&mut self) {
+        let full_rows = self.full_rows();
+        if !full_rows.is_empty() {
+            let mut board = [[0; COLS]; ROWS];
+            let mut r = 0;
+            for row in &mut board {
+                while full_rows.contains(&r) {
+                    r += 1;
+                }
+                if r >= ROWS {
+                    break;
+                }
+                *row = self.board[r];
+                r += 1;
+            }
+            self.board = board;
+        }
+    }
+
+    fn process_attacks(&mut self) {
+        let mut rows = 0;
+        for (attack, count) in &mut self.incoming {
+            if *count == 1 {
+                rows = u8::saturating_add(rows, *attack);
+            }
+            *count = count.saturating_sub(1);
+        }
+        // more garbage than the board holds tops out the same
+        let mut rows = rows.min((ROWS - 4) as u8);
+        if rows > 0 {
+            self.events.push(GameEvent::GarbageReceived { lines: rows });
+            let mut i = 0;
+            let mut lost = false;
+            for point in &self.board[ROWS - 4 - (rows as usize)] {
+                if *point != 0 {
+                    lost = true;
+                }
+            }
+            let mut board = [[0; COLS]; ROWS];
+            let rng = self.rng.next().gen_range(0, COLS);
+            for row in &mut board {
+                if rows > 0 {
+                    *row = [Color::Fixed as u32; COLS];
+                    row[rng] = 0;
+                    rows -= 1;
+                } else {
+                    *row = self.board[i];
+                    i += 1;
+                }
+            }
+            self.board = board;
+
+            if lost {
+                self.lose_game();
+            }
+        }
+        self.incoming.retain(|(_, time)| time > &0);
+    }
+
+    pub fn save_piece(&mut self) {
+        if !self.has_saved {
+            if let Some(piece) = &mut self.saved_piece {
+                let p = piece.clone();
+                let mut pc = self.current_piece.clone();
+                pc.set_position([COLS as i32 / 2, ROWS as i32 - 1]);
+                *piece = pc;
+                self.current_piece = p;
+            } else {
+                let mut pc = self.current_piece.clone();
+                pc.set_position([COLS as i32 / 2, ROWS as i32 - 1]);
+                self.saved_piece = Some(pc);
+                self.next_piece();
+            }
+            self.has_saved = true;
+            self.events.push(GameEvent::Hold);
+        }
+    }
+
+    fn next_piece(&mut self) {
+        self.current_piece = self.next_piece.clone();
+        self.next_piece = Piece::random_piece(&mut self.rng.next());
+    }
+
+    fn process_score(&mut self, lines_cleared: usize) {
+        let index = lines_cleared.min(4) - 1;
+        let attack = self.rules.attack_table[index];
+        self.score += self.rules.score_table[index];
+        let level = self.score / self.rules.score_per_level;
+        if level > self.level {
+            self.level = level;
+            self.events.push(GameEvent::LevelUp { level });
+        }
+
+        let gravity = self.rules.gravity(level);
+        if gravity < self.gravity {
+            self.gravity = gravity;
+        }
+        if attack > 0 {
+            self.outgoing = Some((attack, self.rules.attack_delay));
+        }
+    }
+
+    /// Set if the falling piece is where new pieces appear, bots plan their
+    /// moves when it is
+    pub(crate) fn at_spawn(&self) -> bool {
+        self.current_piece.position == [COLS as i32 / 2, ROWS as i32 - 1]
+    }
+
+    /// Set if the falling piece can still be swapped with the saved one
+    pub(crate) fn can_hold(&self) -> bool {
+        !self.has_saved
+    }
+
+    /// A copy of the player with the falling piece placed where it is, the
+    /// rows it filled removed right away and the next piece falling. Used by
+    /// bots to look ahead.
+    pub(crate) fn after_placing(&self) -> Player {
+        let mut player = self.clone();
+        if player.place_piece(None).is_ok() {
+            player.remove_full_rows();
+            player.clearing = 0;
+            player.next_piece();
+        }
+        player.events.clear();
+        player
+    }
+
+    /// Every place the falling piece and the saved one can be put, with the
+    /// fewest inputs that get them there. Every input is tried from every
+    /// position the piece can reach, so tucks under overhangs and spins are
+    /// found too. Empty while the player can't move.
+    pub(crate) fn placements(&self) -> Vec<Placement> {
+        if self.lost || self.clearing > 0 {
+            return Vec::new();
+        }
+        let mut placements = self.piece_placements(false);
+        if self.can_hold() {
+            let mut held = self.clone();
+            held.save_piece();
+            placements.extend(held.piece_placements(true));
+        }
+        placements
+    }
+
+    /// The placements of the falling piece, a breadth-first search so the
+    /// first inputs found for a placement are the fewest
+    fn piece_placements(&self, hold: bool) -> Vec<Placement> {
+        let start = if hold { vec![Action::Hold] } else { Vec::new() };
+        let mut queue = VecDeque::new();
+        let mut seen = HashSet::new();
+        seen.insert((self.current_piece.position, self.current_piece.shape));
+        queue.push_back((self.current_piece.clone(), start));
+        let mut probe = self.clone();
+        let mut placements: Vec<Placement> = Vec::new();
+        while let Some((piece, actions)) = queue.pop_front() {
+            let dropped = self.fast_drop(piece.clone());
+            let turned = matches!(
+                actions.last(),
+                Some(Action::RotateClockwise) | Some(Action::RotateCounterClockwise)
+            );
+            let resting = dropped.position == piece.position;
+            let spin = turned && resting && self.immobile(&piece);
+            let mut blocks = dropped.pos_on_board();
+            blocks.sort_unstable();
+            if !placements
+                .iter()
+                .any(|placement| placement.blocks == blocks && placement.spin == spin)
+            {
+                let mut actions = actions.clone();
+                actions.push(Action::HardDrop);
+                placements.push(Placement {
+                    blocks,
+                    actions,
+                    hold,
+                    spin,
+                });
+            }
+            for &action in &[
+                Action::MoveLeft,
+                Action::MoveRight,
+                Action::RotateClockwise,
+                Action::RotateCounterClockwise,
+                Action::SoftDrop,
+            ] {
+                probe.current_piece = piece.clone();
+                probe.perform(action);
+                let moved = &probe.current_piece;
+                if self.valid_pos(moved) && seen.insert((moved.position, moved.shape)) {
+                    let mut actions = actions.clone();
+                    actions.push(action);
+                    queue.push_back((moved.clone(), actions));
+                }
+            }
+        }
+        placements
+    }
+
+    /// Set if a piece can't move left, right or up
+    fn immobile(&self, piece: &Piece) -> bool {
+        [(-1, 0), (1, 0), (0, 1)].iter().all(|&(x, y)| {
+            let mut moved = piece.clone();
+            moved.mov(x, y);
+            !self.valid_pos(&moved)
+        })
+    }
+
+    pub(crate) fn lose_game(&mut self) {
+        if !self.lost {
+            self.events.push(GameEvent::TopOut);
+        }
+        self.lost = true;
+    }
+
+    pub fn get_board(&self) -> [[u32; COLS]; ROWS] {
+        self.board
+    }
+
+    pub fn get_current_shape(&self) -> [[i32; 2]; 4] {
+        self.current_piece.pos_on_board()
+    }
+
+    pub fn get_saved_shape(&self) -> [[i32; 2]; 4] {
+        if let Some(piece) = &self.saved_piece {
+            piece.get_shape()
+        } else {
+            [[0; 2]; 4]
+        }
+    }
+
+    pub fn get_board_visual(&self) -> [[u32; COLS]; ROWS] {
+        let mut board = self.board;
+        // there is no falling piece while the cleared rows are shown
+        if self.clearing > 0 {
+            return board;
+        }
+        if let Some(shadow) = &self.piece_shadow {
+            for [x, y] in &shadow.pos_on_board() {
+                let (x, y) = (*x as usize, *y as usize);
+                if x < COLS && y < ROWS {
+                    board[y][x] = shadow.color as u32;
+                }
+            }
+        }
+        for [x, y] in &self.current_piece.pos_on_board() {
+            let (x, y) = (*x as usize, *y as usize);
+            if x < COLS && y < ROWS {
+                board[y][x] = self.current_piece.color as u32;
+            }
+        }
+        board
+    }
+
+    pub fn get_incoming(&self) -> &Vec<(u8, u8)> {
+        &self.incoming
+    }
+
+    pub fn get_next_piece(&self) -> &Piece {
+        &self.next_piece
+    }
+
+    pub fn get_saved_piece(&self) -> &Option<Piece> {
+        &self.saved_piece
+    }
+
+    pub fn get_score(&self) -> usize {
+        self.score
+    }
+
+    pub fn get_level(&self) -> usize {
+        self.level
+    }
+
+    pub fn get_lost(&self) -> bool {
+        self.lost
+    }
+
+    pub fn add_incoming(&mut self, attack: (u8, u8)) {
+        self.incoming.push(attack);
+    }
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn take_outgoing(&mut self) -> Option<(u8, u8)> {
+        let outgoing = self.outgoing;
+        self.outgoing = None;
+        outgoing
+    }
+
+    fn place_piece(&mut self, alt_piece: Option<&Piece>) -> Result<(), String> {
+        let piece = if let Some(piece) = alt_piece {
+            piece
+        } else {
+            &self.current_piece
+        };
+        if self.valid_pos(&piece) {
+            let mut lost = false;
+            for [x, y] in &piece.pos_on_board() {
+                self.board[*y as usize][*x as usize] = piece.color as u32;
+                if *y >= (ROWS as i32 - 4) {
+                    lost = true;
+                }
+            }
+            self.has_saved = false;
+            self.events.push(GameEvent::PieceLocked {
+                blocks: piece.pos_on_board(),
+            });
+            if lost {
+                self.lose_game();
+            }
+            return Ok(());
+        }
+        Err("Error placing piece on board!".to_string())
+    }
+
+    pub fn perform(&mut self, action: Action) {
+        if self.clearing > 0 {
+            return;
+        }
+        match action {
+            Action::MoveLeft => self.move_current(-1, 0),
+            Action::MoveRight => self.move_current(1, 0),
+            Action::RotateClockwise => self.rotate_current(true),
+            Action::RotateCounterClockwise => self.rotate_current(false),
+            Action::SoftDrop => self.move_current(0, -1),
+            Action::HardDrop => self.drop_current(),
+            Action::Hold => self.save_piece(),
+        }
+    }
+
+    pub fn move_current(&mut self, x: i32, y: i32) {
+        self.current_piece.mov(x, y);
+        if !self.valid_pos(&self.current_piece) {
+            self.current_piece.mov(-x, -y);
+        }
+    }
+
+    pub fn drop_current(&mut self) {
+        self.current_piece = self.fast_drop(self.current_piece.clone());
+        self.grace_count = self.rules.grace_delay;
+    }
+
+    fn fast_drop(&self, mut piece: Piece) -> Piece {
+        loop {
+            piece.mov(0, -1);
+            if !self.valid_pos(&piece) {
+                piece.mov(0, 1);
+                break;
+            }
+        }
+        piece
+    }
+
+    fn shadow_piece(&mut self) {
+        let mut shadow = self.current_piece.clone();
+        shadow.color = match shadow.color as u32 {
+            1 => Color::Shadow1,
+            2 => Color::Shadow2,
+            3 => Color::Shadow3,
+            4 => Color::Shadow4,
+            5 => Color::Shadow5,
+            6 => Color::Shadow6,
+            _ => Color::Shadow7,
+        };
+        self.piece_shadow = Some(self.fast_drop(shadow));
+    }
+
+    pub fn rotate_current(&mut self, clockwise: bool) {
+        let old_shape = self.current_piece.shape;
+        self.current_piece.rotate(clockwise);
+        if !self.valid_pos(&self.current_piece) {
+            self.adjust_current();
+            if !self.valid_pos(&self.current_piece) {
+                self.current_piece.shape = old_shape;
+            }
+        }
+    }
+
+    fn valid_pos(&self, piece: &Piece) -> bool {
+        for [x, y] in &piece.pos_on_board() {
+            if *x < 0 || *y < 0 {
+                return false;
+            }
+            let x = *x as usize;
+            let y = *y as usize;
+            if y >= ROWS || x >= COLS || self.board[y][x] != 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn adjust_current(&mut self) {
+        let mut y_adj = 0;
+        let mut x_adj = 0;
+        for [x, y] in &self.current_piece.pos_on_board() {
+            if *y < -y_adj && *y < 0 {
+                y_adj = -*y;
+            } else if (ROWS as i32 - 1 - *y) < y_adj {
+                y_adj = ROWS as i32 - 1 - *y;
+            }
+            if *x < -x_adj && *x < 0 {
+                x_adj = -*x;
+            } else if (COLS as i32 - 1 - *x) < x_adj {
+                x_adj = COLS as i32 - 1 - *x;
+            }
+            loop {
+                if (*y + y_adj) < ROWS as i32                                           //UNSURE IF THIS IS A GOOD BUGFIX!
+                    && self.board[(*y + y_adj) as usize][(*x + x_adj) as usize] != 0
+                {
+                    y_adj += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.current_piece.position[0] += x_adj;
+        self.current_piece.position[1] += y_adj as i32;
+    }
+}
+
+/// Everything that affects how the game continues, used to detect desyncs
+impl Hash for Player {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rng.hash(state);
+        hash_each(self.board.iter().flatten(), state);
+        self.incoming.hash(state);
+        self.outgoing.hash(state);
+        self.current_piece.hash(state);
+        self.saved_piece.hash(state);
+        self.has_saved.hash(state);
+        self.next_piece.hash(state);
+        // usize is hashed as u64 so 32 and 64 bit machines agree
+        (self.score as u64).hash(state);
+        (self.level as u64).hash(state);
+        self.lost.hash(state);
+        self.gravity.to_bits().hash(state);
+        self.update_timer.hash(state);
+        self.grace_count.hash(state);
+        self.clearing.hash(state);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Piece {
+    shape: Shape,
+    display_shape: [[u32; 4]; 4],
+    color: Color,
+    position: Point,
+}
+
+impl Hash for Piece {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_each(self.shape.iter().flatten(), state);
+        hash_each(self.display_shape.iter().flatten(), state);
+        (self.color as u32).hash(state);
+        hash_each(&self.position, state);
+    }
+}
+
+/// Hashes numbers one at a time. Arrays of them are hashed as their bytes in
+/// memory, which differ between little and big endian machines.
+fn hash_each<'a, T: Hash + 'a, H: Hasher>(values: impl IntoIterator<Item = &'a T>, state: &mut H) {
+    for value in values {
+        value.hash(state);
+    }
+}
+
+impl Piece {
+    pub fn new(shape: Shape, color: Color, position: Point) -> Piece {
+        let mut display_shape = [[0; 4]; 4];
+        for [x, y] in &shape {
+            display_shape[(y + 2) as usize][(x + 2) as usize] = color as u32;
+        }
+        Piece {
+            shape,
+            display_shape,
+            color,
+            position,
+        }
+    }
+
+    pub fn random_piece<R: Rng>(rng: &mut R) -> Piece {
+        let rng = rng.gen_range(0, SHAPES.len());
+        let shape = SHAPES[rng];
+        let color = match rng {
+            0 => Color::Color1,
+            1 => Color::Color2,
+            2 => Color::Color3,
+            3 => Color::Color4,
+            4 => Color::Color5,
+            5 => Color::Color6,
+            _ => Color::Color7,
+        };
+        Piece::new(shape, color, [COLS as i32 / 2, ROWS as i32 - 1])
+    }
+
+    pub fn get_shape(&self) -> Shape {
+        self.shape
+    }
+
+    pub fn get_display_shape(&self) -> [[u32; 4]; 4] {
+        self.display_shape
+    }
+
+    fn mov(&mut self, x: i32, y: i32) {
+        self.position[0] += x;
+        self.position[1] += y;
+    }
+
+    fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    fn rotate(&mut self, clockwise: bool) {
+        if self.shape != SHAPES[1] {
+            for block in &mut self.shape {
+                block.swap(0, 1);
+                if clockwise {
+                    block[1] *= -1
+                } else {
+                    block[0] *= -1
+                }
+            }
+        }
+    }
+
+    pub fn pos_on_board(&self) -> Shape {
+        let mut board_pos = [[0; 2]; 4];
+        for i in 0..4 {
+            board_pos[i][0] = self.position[0] + self.shape[i][0];
+            board_pos[i][1] = self.position[1] + self.shape[i][1];
+        }
+        board_pos
+    }
+}
+
+/// The fewest moves and rotations that take a new piece to where a piece was
+/// placed, on an empty board. Holding a move to slide to a wall counts as one
+/// input, like with auto-repeat. `None` if the blocks aren't a piece.
+pub fn finesse_inputs(blocks: &[[i32; 2]; 4]) -> Option<u32> {
+    let mut placed = *blocks;
+    placed.sort_unstable();
+    let mut fewest = None;
+    for &shape in &SHAPES {
+        let mut piece = Piece::new(shape, Color::Void, [COLS as i32 / 2, ROWS as i32 - 1]);
+        // turning twice either way takes two rotations, once either way takes one
+        for &rotations in &[0, 1, 2, 1] {
+            let mut spawned = piece.pos_on_board();
+            spawned.sort_unstable();
+            let dx = placed[0][0] - spawned[0][0];
+            let dy = placed[0][1] - spawned[0][1];
+            let same = (0..4)
+                .all(|i| placed[i][0] - spawned[i][0] == dx && placed[i][1] - spawned[i][1] == dy);
+            if same {
+                let left = spawned.iter().map(|[x, _]| x).min().unwrap_or(&0) + dx;
+                let right = spawned.iter().map(|[x, _]| x).max().unwrap_or(&0) + dx;
+                // tapping, or sliding to a wall and tapping back
+                let moves = if dx == 0 {
+                    0
+                } else {
+                    dx.abs().min(1 + left).min(COLS as i32 - right) as u32
+                };
+                let inputs = rotations + moves;
+                fewest = Some(fewest.map_or(inputs, |fewest: u32| fewest.min(inputs)));
+            }
+            piece.rotate(true);
+        }
+    }
+    fewest
+}
@@ -0,0 +1,362 @@
+use super::{
+    finesse_inputs, Action, ClearKind, Color, GameEvent, Piece, Player, Rules, COLS, ROWS, SHAPES,
+    TICK_RATE,
+};
+use std::thread;
+
+#[test]
+fn rotation() {
+    let mut player = Player::new(0, &Rules::default(), 0);
+    player.current_piece = Piece::new(SHAPES[2], Color::Color1, [2, 2]);
+    assert_eq!(
+        [[1, 2], [2, 2], [2, 1], [3, 2]],
+        player.current_piece.pos_on_board()
+    );
+    player.current_piece.rotate(true);
+    assert_eq!(
+        [[2, 3], [2, 2], [1, 2], [2, 1]],
+        player.current_piece.pos_on_board()
+    );
+    player.current_piece.rotate(true);
+    assert_eq!(
+        [[3, 2], [2, 2], [2, 3], [1, 2]],
+        player.current_piece.pos_on_board()
+    );
+    player.current_piece.rotate(true);
+    assert_eq!(
+        [[2, 1], [2, 2], [3, 2], [2, 3]],
+        player.current_piece.pos_on_board()
+    );
+}
+
+#[test]
+fn line_clear() {
+    let mut player = Player::new(0, &Rules::default(), 0);
+    for i in 0..COLS {
+        player.current_piece = Piece::new(SHAPES[0], Color::Color1, [i as i32, 1]);
+        player.rotate_current(true);
+        let mut loop_var = 0;
+        println!("-------------------------------------------");
+        for line in &player.get_board_visual() {
+            loop_var += 1;
+            if loop_var > (ROWS - 4) {
+                break;
+            }
+            print!("|");
+            for point in line {
+                print!(" ");
+                if *point == 0 {
+                    print!(" ");
+                } else {
+                    print!("*");
+                }
+            }
+            println!("|");
+        }
+        println!("-------------------------------------------");
+        player.move_tick();
+    }
+    let mut is_cleared = true;
+    for line in &player.board {
+        for block in line {
+            if block != &0 {
+                is_cleared = false;
+            }
+        }
+    }
+    let mut loop_var = 0;
+    println!("-------------------------------------------");
+    for line in &player.board {
+        loop_var += 1;
+        if loop_var > (ROWS - 4) {
+            break;
+        }
+        print!("|");
+        for point in line {
+            print!(" ");
+            if *point == 0 {
+                print!(" ");
+            } else {
+                print!("*");
+            }
+        }
+        println!("|");
+    }
+    println!("-------------------------------------------");
+    assert!(is_cleared);
+    //print!("{}[2J", 27 as char);
+}
+
+#[test] //not real test!
+fn console_debug() {
+    let mut player = Player::new(0, &Rules::default(), 0);
+    loop {
+        print!("{}[2J", 27 as char);
+        let mut loop_var = 0;
+        println!("-------------------------------------------");
+        for line in &player.get_board_visual() {
+            loop_var += 1;
+            if loop_var > (ROWS - 4) {
+                break;
+            }
+            print!("|");
+            for point in line {
+                print!(" ");
+                if *point == 0 {
+                    print!(" ");
+                } else {
+                    print!("*");
+                }
+            }
+            println!("|");
+        }
+        println!("-------------------------------------------");
+        if player.lost {
+            break;
+        }
+        thread::sleep_ms(100);
+        player.rotate_current(true);
+        player.move_tick();
+    }
+    println!("game lost!");
+    assert_eq!(true, player.lost);
+}
+
+#[test]
+fn rules_validation() {
+    assert!(Rules::default().validate().is_ok());
+    let mut rules = Rules {
+        gravity: Vec::new(),
+        ..Rules::default()
+    };
+    assert!(rules.validate().is_err());
+    rules.gravity = vec![1.0, 0.0];
+    assert!(rules.validate().is_err());
+    rules.gravity = vec![1.0, 0.5];
+    assert!(rules.validate().is_ok());
+    assert_eq!(0.5, rules.gravity(100));
+    // garbage that never arrives or doesn't fit on the board
+    rules.attack_delay = 0;
+    assert!(rules.validate().is_err());
+    rules.attack_delay = 1;
+    rules.attack_table = [0, 1, 2, ROWS as u8 - 3];
+    assert!(rules.validate().is_err());
+    rules.attack_table = [0, 1, 2, ROWS as u8 - 4];
+    assert!(rules.validate().is_ok());
+}
+
+#[test]
+fn garbage_overflow() {
+    let mut player = Player::new(0, &Rules::default(), 0);
+    // garbage from a peer that skipped validation is capped, not a panic
+    player.add_incoming((u8::MAX, 1));
+    player.add_incoming((u8::MAX, 1));
+    player.add_incoming((1, 0));
+    player.process_attacks();
+    assert_eq!(
+        Color::Fixed as u32,
+        player.board[ROWS - 5].iter().max().copied().unwrap()
+    );
+    assert_eq!([0; COLS], player.board[ROWS - 4]);
+    assert!(player.incoming.is_empty());
+}
+
+#[test]
+fn gravity_ticks() {
+    let mut player = Player::new(0, &Rules::default(), 0);
+    let position = player.current_piece.position;
+    // level 0 falls one row per second
+    for _ in 1..TICK_RATE {
+        player.update();
+    }
+    assert_eq!(position, player.current_piece.position);
+    player.update();
+    assert_eq!(position[1] - 1, player.current_piece.position[1]);
+}
+
+#[test]
+fn events() {
+    let rules = Rules {
+        score_per_level: 1,
+        ..Rules::default()
+    };
+    let mut player = Player::new(0, &rules, 0);
+    for row in &mut player.board[..2] {
+        *row = [Color::Fixed as u32; COLS];
+        row[0] = 0;
+    }
+    // a standing I piece fills the gaps
+    player.current_piece = Piece::new([[0, -2], [0, -1], [0, 0], [0, 1]], Color::Color1, [0, 5]);
+    player.drop_current();
+    player.move_tick();
+    assert_eq!(
+        vec![
+            GameEvent::PieceLocked {
+                blocks: [[0, 0], [0, 1], [0, 2], [0, 3]]
+            },
+            GameEvent::LinesCleared {
+                kind: ClearKind::Double,
+                rows: 0b11,
+            },
+            GameEvent::LevelUp { level: 3 },
+        ],
+        player.take_events()
+    );
+    player.save_piece();
+    player.save_piece();
+    player.add_incoming((2, 1));
+    player.process_attacks();
+    player.lose_game();
+    player.lose_game();
+    assert_eq!(
+        vec![
+            GameEvent::Hold,
+            GameEvent::GarbageReceived { lines: 2 },
+            GameEvent::TopOut,
+        ],
+        player.take_events()
+    );
+}
+
+#[test]
+fn clear_delay() {
+    let rules = Rules {
+        clear_delay: 10,
+        gravity: vec![1.0 / TICK_RATE as f64],
+        ..Rules::default()
+    };
+    let mut player = Player::new(0, &rules, 0);
+    player.board[0] = [Color::Fixed as u32; COLS];
+    player.board[0][0] = 0;
+    player.current_piece = Piece::new([[0, -2], [0, -1], [0, 0], [0, 1]], Color::Color1, [0, 5]);
+    player.drop_current();
+    player.update();
+    // the cleared row stays until the delay is over, without a falling piece
+    for _ in 0..10 {
+        assert!(player.board[0].iter().all(|block| *block != 0));
+        assert_eq!(player.board, player.get_board_visual());
+        player.update();
+    }
+    assert_eq!([0, 0, 0], player.board[3][..3]);
+    assert_eq!(Color::Color1 as u32, player.board[2][0]);
+    assert_ne!(player.board, player.get_board_visual());
+}
+
+#[test]
+fn finesse() {
+    let placed = |shape, position: [i32; 2], rotations| {
+        let mut piece = Piece::new(shape, Color::Color1, position);
+        for _ in 0..rotations {
+            piece.rotate(true);
+        }
+        piece.pos_on_board()
+    };
+    let spawn = [COLS as i32 / 2, ROWS as i32 - 1];
+    // dropped where it spawned, at any height
+    assert_eq!(Some(0), finesse_inputs(&placed(SHAPES[2], [5, 3], 0)));
+    assert_eq!(Some(1), finesse_inputs(&placed(SHAPES[2], [6, 0], 0)));
+    assert_eq!(Some(2), finesse_inputs(&placed(SHAPES[2], [3, 0], 0)));
+    // sliding to the wall is one input
+    assert_eq!(Some(1), finesse_inputs(&placed(SHAPES[0], [2, 0], 0)));
+    assert_eq!(Some(2), finesse_inputs(&placed(SHAPES[0], [0, 0], 1)));
+    // three turns one way are one turn the other way
+    assert_eq!(Some(1), finesse_inputs(&placed(SHAPES[5], spawn, 3)));
+    assert_eq!(Some(2), finesse_inputs(&placed(SHAPES[5], spawn, 2)));
+    // the o piece doesn't turn
+    assert_eq!(Some(1), finesse_inputs(&placed(SHAPES[1], [1, 5], 0)));
+    assert_eq!(None, finesse_inputs(&[[0, 0], [1, 0], [5, 0], [6, 0]]));
+}
+
+#[test]
+fn placements() {
+    let spawn = [COLS as i32 / 2, ROWS as i32 - 1];
+    let mut player = Player::new(0, &Rules::default(), 0);
+    // every rotation of a piece at every column, the same ones turned
+    // a different way counted once
+    player.has_saved = true;
+    for (shape, expected) in SHAPES.iter().zip(&[17, 9, 34, 17, 17, 34, 34]) {
+        player.current_piece = Piece::new(*shape, Color::Color1, spawn);
+        let placements = player.placements();
+        assert_eq!(*expected, placements.len());
+        assert!(placements
+            .iter()
+            .all(|placement| placement.actions.last() == Some(&Action::HardDrop)));
+    }
+    // the saved piece is placed too, or the next one when none is saved
+    player.has_saved = false;
+    player.current_piece = Piece::new(SHAPES[2], Color::Color1, spawn);
+    player.next_piece = Piece::new(SHAPES[1], Color::Color1, spawn);
+    let placements = player.placements();
+    assert_eq!(43, placements.len());
+    let held: Vec<_> = placements
+        .iter()
+        .filter(|placement| placement.hold)
+        .collect();
+    assert_eq!(9, held.len());
+    assert!(held
+        .iter()
+        .all(|placement| placement.actions[0] == Action::Hold));
+    // the fewest inputs, sliding to the wall is one input at a time
+    assert!(placements.iter().any(|placement| !placement.hold
+        && placement.blocks == [[0, 1], [1, 0], [1, 1], [2, 1]]
+        && placement.actions
+            == [
+                Action::MoveLeft,
+                Action::MoveLeft,
+                Action::MoveLeft,
+                Action::MoveLeft,
+                Action::HardDrop
+            ]));
+}
+
+#[test]
+fn tucks_and_spins() {
+    let spawn = [COLS as i32 / 2, ROWS as i32 - 1];
+    let mut player = Player::new(0, &Rules::default(), 0);
+    player.has_saved = true;
+    // a roof over the left half, the o piece goes on it, next to it or under it
+    for x in 0..=4 {
+        player.board[2][x] = Color::Fixed as u32;
+    }
+    player.current_piece = Piece::new(SHAPES[1], Color::Color1, spawn);
+    let placements = player.placements();
+    assert_eq!(14, placements.len());
+    let tuck = placements
+        .iter()
+        .find(|placement| placement.blocks == [[0, 0], [0, 1], [1, 0], [1, 1]])
+        .unwrap();
+    assert!(tuck.actions.contains(&Action::SoftDrop));
+    assert!(!tuck.spin);
+
+    // a t-slot that only a turn at the bottom gets into, clearing two rows
+    player.board = [[0; COLS]; ROWS];
+    for y in 0..3 {
+        player.board[y] = [Color::Fixed as u32; COLS];
+    }
+    for [x, y] in &[[4, 0], [3, 1], [4, 1], [5, 1], [4, 2], [5, 2]] {
+        player.board[*y][*x] = 0;
+    }
+    player.current_piece = Piece::new(SHAPES[2], Color::Color1, spawn);
+    let spin = player
+        .placements()
+        .into_iter()
+        .find(|placement| placement.blocks == [[3, 1], [4, 0], [4, 1], [5, 1]])
+        .unwrap();
+    assert!(spin.spin);
+    assert!(matches!(
+        spin.actions[spin.actions.len() - 2],
+        Action::RotateClockwise | Action::RotateCounterClockwise
+    ));
+    let mut spun = player.clone();
+    for action in &spin.actions {
+        spun.perform(*action);
+    }
+    spun.place_piece(None).unwrap();
+    assert_eq!(
+        2,
+        spun.board
+            .iter()
+            .filter(|row| row.iter().all(|cell| *cell != 0))
+            .count()
+    );
+}
@@ -0,0 +1,559 @@
+//! A game of two to eight players, their controllers and the game modes.
+
+use crate::bots::{Bot, Pilot, Weights};
+use crate::features::{self, FeaturesFn};
+use crate::game_data::{Action, GameEvent, Placement, Player, Rules, COLS, ROWS};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use libloading::{Library, Symbol};
+
+/// Players in a default game, online games and terminal games always have this many
+pub const PLAYER_AMOUNT: usize = 2;
+/// The most players a game can have
+pub const MAX_PLAYERS: usize = 8;
+/// File ending of ai-scripts on this platform
+#[cfg(windows)]
+pub const AI_EXTENSION: &str = "dll";
+/// File ending of ai-scripts on this platform
+#[cfg(not(windows))]
+pub const AI_EXTENSION: &str = "so";
+/// Function signature for the ai-script
+type AIFunc = unsafe fn(*const [[u32; 10]; 24], *const [[i32; 2]; 4], *const [[i32; 2]; 4]) -> u32;
+/// Function signature for the optional function of ai-scripts that is handed
+/// `features::board_features` when they are loaded
+type FeaturesHook = unsafe extern "C" fn(FeaturesFn);
+
+#[cfg(test)]
+mod tests;
+
+/// The available game modes
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Cleared lines are sent as garbage to the next player still in the game,
+    /// last one standing wins
+    Versus,
+    /// No garbage, every player plays until they top out and the highest score wins
+    Marathon,
+}
+
+impl GameMode {
+    /// The name shown in menus
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Versus => "Versus",
+            GameMode::Marathon => "Marathon",
+        }
+    }
+}
+
+/// Who is controlling a player
+#[derive(Clone, PartialEq)]
+pub enum Controller {
+    /// Actions come from `Game::perform`, whatever the input is
+    Keyboard,
+    /// Path to an ai-script shared library
+    AI(PathBuf),
+    /// A bot built into the engine
+    Bot(Bot),
+}
+
+impl Controller {
+    /// The controller given on the command line or in a config, a bot's id
+    /// or the path to an ai-script. A bot's id can be followed by `:` and a
+    /// file with the weights to play with, e.g. `heuristic-bot:best.json`.
+    pub fn from_arg(arg: &str) -> Result<Controller, String> {
+        let (id, weights) = match arg.split_once(':') {
+            Some((id, weights)) if Bot::from_id(id).is_some() => (id, Some(weights)),
+            _ => (arg, None),
+        };
+        match (Bot::from_id(id), weights) {
+            (Some(bot), Some(weights)) => Ok(Controller::Bot(
+                bot.with_weights(Weights::load(Path::new(weights))?),
+            )),
+            (Some(bot), None) => Ok(Controller::Bot(bot)),
+            (None, _) => Ok(Controller::AI(PathBuf::from(arg))),
+        }
+    }
+}
+
+/// What plays a player that isn't controlled with `perform`
+enum Ai {
+    Script(Library),
+    Bot(Pilot),
+}
+
+/// The state of every player at one tick, restoring it rewinds the game
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    tick: u32,
+    players: Vec<Player>,
+}
+
+impl Snapshot {
+    /// The tick the snapshot was taken on
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// How many players the game has
+    pub fn player_count(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Hash of the state of every player, equal on two machines running the same game
+    pub fn state_hash(&self) -> u64 {
+        hash_players(&self.players)
+    }
+}
+
+/// A game of two to `MAX_PLAYERS` players, advanced one tick at a time by `update`
+pub struct Game {
+    rules: Rules,
+    players: Vec<Player>,
+    ai: Vec<Option<Ai>>,
+    mode: GameMode,
+    /// Updates since the game started
+    tick: u32,
+    /// Every action performed, with the tick whose update it happened before
+    performed: Vec<(u32, usize, Action)>,
+    /// What happened to the players, with the tick and the player
+    events: Vec<(u32, usize, GameEvent)>,
+}
+
+impl Game {
+    /// Creates a game with a player for every controller. Games created with the same
+    /// seed and given the same actions on the same ticks play out identically.
+    pub fn new(
+        rules: &Rules,
+        init_level: usize,
+        mode: GameMode,
+        controllers: Vec<Controller>,
+        seed: u64,
+    ) -> Result<Game, String> {
+        if !(2..=MAX_PLAYERS).contains(&controllers.len()) {
+            return Err(format!(
+                "A game needs 2 to {} players, found {}",
+                MAX_PLAYERS,
+                controllers.len()
+            ));
+        }
+        let ai = controllers
+            .into_iter()
+            .map(load_controller)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Game {
+            rules: rules.clone(),
+            players: (0..ai.len())
+                .map(|_| Player::new(init_level, rules, seed))
+                .collect(),
+            ai,
+            mode,
+            tick: 0,
+            performed: Vec::new(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Creates a game from a snapshot, every player is given its actions with `perform`
+    pub fn from_snapshot(
+        rules: &Rules,
+        mode: GameMode,
+        snapshot: &Snapshot,
+    ) -> Result<Game, String> {
+        let controllers = vec![Controller::Keyboard; snapshot.player_count()];
+        let mut game = Game::new(rules, 0, mode, controllers, 0)?;
+        game.restore(snapshot);
+        Ok(game)
+    }
+
+    /// The game-tick update function, called `TICK_RATE` times per second
+    pub fn update(&mut self) {
+        // update game tick for players
+        for p in 0..self.players.len() {
+            self.players[p].update();
+            self.collect_events(p);
+            //attack handling
+            if let Some(attack) = self.players[p].take_outgoing() {
+                if let Some(target) = self.target(p) {
+                    self.players[target].add_incoming(attack);
+                    let event = GameEvent::GarbageSent { lines: attack.0 };
+                    self.events.push((self.tick, p, event));
+                }
+            }
+        }
+        self.tick += 1;
+        for i in 0..self.ai.len() {
+            let action = match &mut self.ai[i] {
+                Some(Ai::Script(_)) => Action::from_ai_output(self.call_ai_script(i)),
+                Some(Ai::Bot(pilot)) => pilot.next_action(&self.players[i]),
+                None => None,
+            };
+            if let Some(action) = action {
+                self.perform_ai_action(i, action);
+            }
+        }
+    }
+    /// How many players the game has
+    pub fn player_count(&self) -> usize {
+        self.players.len()
+    }
+
+    /// The player the garbage of a player is sent to in versus: the next player
+    /// in order that is still in the game. `None` for players that have topped out.
+    pub fn target(&self, player: usize) -> Option<usize> {
+        if self.mode != GameMode::Versus || self.players[player].get_lost() {
+            return None;
+        }
+        let count = self.players.len();
+        (1..count)
+            .map(|step| (player + step) % count)
+            .find(|other| !self.players[*other].get_lost())
+    }
+
+    /// Gets and returns the graphical boardstate of the players
+    pub fn get_boards(&self) -> Vec<[[u32; COLS]; ROWS]> {
+        self.players.iter().map(Player::get_board_visual).collect()
+    }
+    /// Gets and returns the next pieces of the players
+    pub fn get_next_pieces(&self) -> Vec<[[u32; 4]; 4]> {
+        self.players
+            .iter()
+            .map(|player| player.get_next_piece().get_display_shape())
+            .collect()
+    }
+    /// Gets and returns the saved pieces of the players
+    pub fn get_saved_pieces(&self) -> Vec<[[u32; 4]; 4]> {
+        self.players
+            .iter()
+            .map(|player| {
+                player
+                    .get_saved_piece()
+                    .as_ref()
+                    .map_or([[0; 4]; 4], |piece| piece.get_display_shape())
+            })
+            .collect()
+    }
+    /// Gets the incoming attacks from players and returns formatted data
+    pub fn get_attackbars(&self) -> Vec<u32> {
+        self.players
+            .iter()
+            .map(|player| {
+                player
+                    .get_incoming()
+                    .iter()
+                    .map(|(attack, _)| *attack as u32)
+                    .sum()
+            })
+            .collect()
+    }
+    /// Returns formatted data for the ai-script, without block-projection.
+    pub fn get_player_data(
+        &self,
+        index: usize,
+    ) -> ([[u32; COLS]; ROWS], [[i32; 2]; 4], [[i32; 2]; 4]) {
+        let mut data = ([[0; COLS]; ROWS], [[0; 2]; 4], [[0; 2]; 4]);
+        if index < self.players.len() {
+            let p = &self.players[index];
+            data = (p.get_board(), p.get_current_shape(), p.get_saved_shape());
+        }
+        data
+    }
+
+    /// Gets the scores of the players
+    pub fn get_scores(&self) -> Vec<u32> {
+        self.players
+            .iter()
+            .map(|player| player.get_score() as u32)
+            .collect()
+    }
+
+    /// Gets the levels of the players
+    pub fn get_levels(&self) -> Vec<usize> {
+        self.players.iter().map(Player::get_level).collect()
+    }
+
+    /// Gets which players have topped out
+    pub fn get_losts(&self) -> Vec<bool> {
+        self.players.iter().map(Player::get_lost).collect()
+    }
+
+    /// Versus ends when at most one player is left, marathon when everyone has topped out
+    pub fn is_over(&self) -> bool {
+        let left = self.get_losts().iter().filter(|lost| !**lost).count();
+        match self.mode {
+            GameMode::Versus => left <= 1,
+            GameMode::Marathon => left == 0,
+        }
+    }
+
+    /// Every place a player can put the falling piece, or the saved one, with the
+    /// inputs to get it there. Empty while the player can't move.
+    pub fn placements(&self, player: usize) -> Vec<Placement> {
+        self.players
+            .get(player)
+            .map_or(Vec::new(), Player::placements)
+    }
+
+    /// Returns the index of the winning player, `None` if the game is still running or tied
+    pub fn get_winner(&self) -> Option<usize> {
+        if !self.is_over() {
+            return None;
+        }
+        match self.mode {
+            GameMode::Versus => self.get_losts().iter().position(|lost| !*lost),
+            GameMode::Marathon => {
+                let scores = self.get_scores();
+                let best = scores.iter().max()?;
+                match scores.iter().filter(|score| *score == best).count() {
+                    1 => scores.iter().position(|score| score == best),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Performs an action for a player, ignored if the player is controlled by an ai-script
+    pub fn perform(&mut self, player: usize, action: Action) {
+        if player < self.players.len() && self.ai[player].is_none() {
+            self.players[player].perform(action);
+            self.performed.push((self.tick, player, action));
+            self.collect_events(player);
+        }
+    }
+
+    /// Starts over with new players, the controllers, rules and mode are kept
+    pub fn restart(&mut self, init_level: usize, seed: u64) {
+        self.players = (0..self.players.len())
+            .map(|_| Player::new(init_level, &self.rules, seed))
+            .collect();
+        self.reset_bots();
+        self.tick = 0;
+        self.performed.clear();
+        self.events.clear();
+    }
+
+    /// Updates since the game started
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The game mode played
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// The rules every player plays by
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Removes and returns the actions performed before the update of tick `before`,
+    /// including the ones of ai-scripts. Performing them on the same ticks of a game
+    /// without the ai-scripts plays it out the same way.
+    pub fn take_performed(&mut self, before: u32) -> Vec<(u32, usize, Action)> {
+        let count = self
+            .performed
+            .iter()
+            .take_while(|(tick, _, _)| *tick < before)
+            .count();
+        self.performed.drain(..count).collect()
+    }
+
+    /// Removes and returns what happened to the players on the ticks before `before`,
+    /// with the tick and the player. Like actions, events between two updates belong
+    /// to the tick of the next one. Events of ticks that are rolled back are dropped,
+    /// so online games should only take the confirmed ticks.
+    pub fn take_events(&mut self, before: u32) -> Vec<(u32, usize, GameEvent)> {
+        let count = self
+            .events
+            .iter()
+            .take_while(|(tick, _, _)| *tick < before)
+            .count();
+        self.events.drain(..count).collect()
+    }
+
+    /// Hash of the state of every player, equal on two machines running the same game
+    pub fn state_hash(&self) -> u64 {
+        hash_players(&self.players)
+    }
+
+    /// Copies the state of the players
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            players: self.players.clone(),
+        }
+    }
+
+    /// Rewinds the players to a snapshot, the controllers and rules are kept
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.players = snapshot.players.clone();
+        self.reset_bots();
+        self.tick = snapshot.tick;
+        self.performed.retain(|(tick, _, _)| *tick < snapshot.tick);
+        self.events.retain(|(tick, _, _)| *tick < snapshot.tick);
+    }
+
+    fn collect_events(&mut self, player: usize) {
+        for event in self.players[player].take_events() {
+            self.events.push((self.tick, player, event));
+        }
+    }
+
+    fn call_ai_script(&self, player_index: usize) -> u32 {
+        let mut output = 0;
+
+        unsafe {
+            if let Some(Ai::Script(lib)) = &self.ai[player_index] {
+                let func: Symbol<AIFunc> = lib.get(b"ai").expect("Couldn't find ai function");
+                let (board, current_piece, saved_piece) = self.get_player_data(player_index);
+                output = func(&board, &current_piece, &saved_piece);
+            }
+        }
+        output
+    }
+
+    fn perform_ai_action(&mut self, player_index: usize, action: Action) {
+        self.players[player_index].perform(action);
+        self.performed.push((self.tick, player_index, action));
+        self.collect_events(player_index);
+    }
+
+    /// Makes the bots plan again, after the players were replaced
+    fn reset_bots(&mut self) {
+        for ai in &mut self.ai {
+            if let Some(Ai::Bot(pilot)) = ai {
+                pilot.reset();
+            }
+        }
+    }
+}
+
+fn hash_players(players: &[Player]) -> u64 {
+    let mut hasher = StateHasher::new();
+    players.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 64 bit FNV-1a over little endian bytes, unlike the hasher of the standard
+/// library it hashes the same with every Rust version on every machine, so
+/// peers can compare their hashes
+struct StateHasher(u64);
+
+impl StateHasher {
+    fn new() -> StateHasher {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // lengths and enum discriminants are hashed as 64 bits on every machine
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+/// Loads the ai-script library or starts the bot of a controller, keyboard
+/// controllers don't have either
+fn load_controller(controller: Controller) -> Result<Option<Ai>, String> {
+    match controller {
+        Controller::Keyboard => Ok(None),
+        Controller::AI(path) => load_ai(&path).map(|lib| Some(Ai::Script(lib))),
+        Controller::Bot(bot) => Ok(Some(Ai::Bot(Pilot::new(bot)))),
+    }
+}
+
+/// Loads an ai-script and makes sure it has an `ai` function. Scripts with an
+/// `ai_features` function get `features::board_features` passed to it.
+pub fn load_ai(path: &Path) -> Result<Library, String> {
+    if !is_ai_library(path) {
+        return Err(format!(
+            "{} is not a .{} library",
+            path.display(),
+            AI_EXTENSION
+        ));
+    }
+    let lib = Library::new(path).map_err(|e| format!("Couldn't load {}: {}", path.display(), e))?;
+    unsafe {
+        if lib.get::<AIFunc>(b"ai").is_err() {
+            return Err(format!("{} has no ai function", path.display()));
+        }
+        if let Ok(hook) = lib.get::<FeaturesHook>(b"ai_features") {
+            hook(features::board_features);
+        }
+    }
+    Ok(lib)
+}
+
+/// Whether a path has the file ending of ai-scripts on this platform
+pub fn is_ai_library(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(AI_EXTENSION))
+}
+
+/// Lists the ai-scripts found directly inside the given directories
+pub fn find_ai_libraries(dirs: &[&Path]) -> Vec<PathBuf> {
+    let mut libraries = Vec::new();
+    for dir in dirs {
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file() && is_ai_library(&path) {
+                    libraries.push(path);
+                }
+            }
+        }
+    }
+    libraries.sort();
+    libraries
+}
@@ -0,0 +1,263 @@
+use super::{find_ai_libraries, load_ai, Controller, Game, GameMode, StateHasher, MAX_PLAYERS};
+use crate::game_data::{Action, GameEvent, Rules};
+use std::hash::Hasher;
+use std::path::Path;
+#[cfg(unix)]
+use std::{env, fs, path::PathBuf, process, process::Command};
+
+#[test]
+fn load_dynamic_ai_lib() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        0,
+    )
+    .expect("keyboard controllers can't fail");
+    assert_eq!(0, game.call_ai_script(0));
+    assert!(load_ai(Path::new("../ai-example/ai.rs")).is_err());
+    assert!(load_ai(Path::new("./does-not-exist.so")).is_err());
+}
+
+/// Builds the example ai-script into its own directory, the built one isn't
+/// checked in
+#[cfg(unix)]
+fn build_example_ai() -> PathBuf {
+    let dir = env::temp_dir().join(format!("tetris-example-ai-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let status = Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .args([
+            "--edition",
+            "2018",
+            "--crate-type",
+            "cdylib",
+            "--cap-lints",
+            "allow",
+        ])
+        .arg("-o")
+        .arg(dir.join("libai.so"))
+        .arg("../ai-example/ai.rs")
+        .status()
+        .expect("couldn't run rustc");
+    assert!(status.success(), "the example ai-script doesn't build");
+    dir
+}
+
+#[cfg(unix)]
+#[test]
+fn find_example_ai() {
+    let dir = build_example_ai();
+    let libraries = find_ai_libraries(&[dir.as_path()]);
+    assert_eq!(vec![dir.join("libai.so")], libraries);
+    assert!(Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::AI(libraries[0].clone())],
+        0
+    )
+    .is_ok());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn winner() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        0,
+    )
+    .expect("keyboard controllers can't fail");
+    assert!(!game.is_over());
+    assert_eq!(None, game.get_winner());
+    game.players[1].lose_game();
+    assert!(game.is_over());
+    assert_eq!(Some(0), game.get_winner());
+
+    game.mode = GameMode::Marathon;
+    assert!(!game.is_over());
+    game.players[0].lose_game();
+    assert!(game.is_over());
+    assert_eq!(None, game.get_winner());
+}
+
+#[test]
+fn more_players() {
+    let new_game = |players| {
+        Game::new(
+            &Rules::default(),
+            5,
+            GameMode::Versus,
+            vec![Controller::Keyboard; players],
+            0,
+        )
+    };
+    assert!(new_game(1).is_err());
+    assert!(new_game(MAX_PLAYERS + 1).is_err());
+
+    let mut game = new_game(3).expect("keyboard controllers can't fail");
+    assert_eq!(3, game.player_count());
+    assert_eq!(
+        vec![Some(1), Some(2), Some(0)],
+        (0..3).map(|p| game.target(p)).collect::<Vec<_>>()
+    );
+    game.players[1].lose_game();
+    assert!(!game.is_over());
+    // the one targeting a lost player moves on to the next one still in the game
+    assert_eq!(
+        vec![Some(2), None, Some(0)],
+        (0..3).map(|p| game.target(p)).collect::<Vec<_>>()
+    );
+    game.players[0].lose_game();
+    assert!(game.is_over());
+    assert_eq!(Some(2), game.get_winner());
+    assert_eq!(None, game.target(2));
+}
+
+#[test]
+fn deterministic() {
+    let new_game = |seed| {
+        Game::new(
+            &Rules::default(),
+            5,
+            GameMode::Versus,
+            vec![Controller::Keyboard, Controller::Keyboard],
+            seed,
+        )
+        .expect("keyboard controllers can't fail")
+    };
+    let mut games = [new_game(7), new_game(7)];
+    for tick in 0..2000 {
+        for game in &mut games {
+            if tick % 3 == 0 {
+                game.perform(0, Action::ALL[tick / 3 % Action::ALL.len()]);
+            }
+            if tick % 40 == 0 {
+                game.perform(1, Action::HardDrop);
+            }
+            game.update();
+        }
+        assert_eq!(games[0].state_hash(), games[1].state_hash());
+    }
+    assert_ne!(games[0].state_hash(), new_game(7).state_hash());
+    assert_ne!(new_game(7).state_hash(), new_game(8).state_hash());
+}
+
+#[test]
+fn snapshots() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        3,
+    )
+    .expect("keyboard controllers can't fail");
+    let snapshot = game.snapshot();
+    assert_eq!(game.state_hash(), snapshot.state_hash());
+    let play = |game: &mut Game| {
+        for tick in 0..300 {
+            if tick % 20 == 0 {
+                game.perform(0, Action::HardDrop);
+                game.perform(1, Action::MoveLeft);
+            }
+            game.update();
+        }
+        game.state_hash()
+    };
+    let first = play(&mut game);
+    assert_ne!(first, snapshot.state_hash());
+    // replaying from the snapshot gives the same game, pieces included
+    game.restore(&snapshot);
+    assert_eq!(snapshot.state_hash(), game.state_hash());
+    assert_eq!(first, play(&mut game));
+}
+
+#[test]
+fn performed_actions() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        3,
+    )
+    .expect("keyboard controllers can't fail");
+    game.perform(0, Action::MoveLeft);
+    game.update();
+    let snapshot = game.snapshot();
+    game.perform(1, Action::Hold);
+    game.update();
+    game.perform(0, Action::HardDrop);
+    assert_eq!(2, game.tick());
+    assert_eq!(
+        vec![(0, 0, Action::MoveLeft), (1, 1, Action::Hold)],
+        game.take_performed(2)
+    );
+    assert_eq!(vec![(2, 0, Action::HardDrop)], game.take_performed(3));
+
+    // rewinding forgets the actions after the snapshot
+    game.perform(1, Action::MoveRight);
+    game.restore(&snapshot);
+    assert_eq!(1, game.tick());
+    assert!(game.take_performed(10).is_empty());
+}
+
+#[test]
+fn events() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        3,
+    )
+    .expect("keyboard controllers can't fail");
+    game.perform(1, Action::Hold);
+    game.update();
+    let snapshot = game.snapshot();
+    game.perform(0, Action::HardDrop);
+    for _ in 0..60 {
+        game.update();
+    }
+    assert_eq!(vec![(0, 1, GameEvent::Hold)], game.take_events(1));
+    // the hard dropped piece locks on the next gravity tick
+    let events = game.take_events(game.tick());
+    assert!(events[0].0 > 1);
+    assert_eq!(0, events[0].1);
+    assert!(matches!(events[0].2, GameEvent::PieceLocked { .. }));
+
+    // rewinding forgets the events after the snapshot
+    game.restore(&snapshot);
+    game.perform(1, Action::Hold);
+    game.restore(&snapshot);
+    assert!(game.take_events(100).is_empty());
+}
+
+#[test]
+fn state_hash() {
+    // the 64 bit FNV-1a test vector
+    let mut hasher = StateHasher::new();
+    hasher.write(b"a");
+    assert_eq!(0xaf63_dc4c_8601_ec8c, hasher.finish());
+    // sizes hash like u64 whatever the size of usize is
+    let mut sizes = StateHasher::new();
+    sizes.write_usize(7);
+    let mut longs = StateHasher::new();
+    longs.write_u64(7);
+    assert_eq!(longs.finish(), sizes.finish());
+
+    // the same game hashes the same with every build
+    let game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        0,
+    )
+    .unwrap();
+    assert_eq!(13869927390943060041, game.state_hash());
+}
@@ -0,0 +1,23 @@
+//! The engine of the game: the rules, the ai-scripts and bots, the stats, the tournaments,
+//! the training of bots and the networking.
+//! Nothing in here depends on a graphics library, the ggez game, the terminal
+//! frontend and the lobby server are all built on top of it.
+//!
+//! A `game_state::Game` is advanced one tick at a time with `update`, `TICK_RATE`
+//! times per second, and the players are controlled with `perform`. Games created
+//! from the same seed play out the same given the same actions on the same ticks.
+
+#![warn(missing_docs)]
+
+pub mod bots;
+pub mod features;
+pub mod game_data;
+pub mod game_state;
+pub mod lobby;
+pub mod netplay;
+pub mod replay;
+pub mod series;
+pub mod spectate;
+pub mod stats;
+pub mod tournament;
+pub mod training;
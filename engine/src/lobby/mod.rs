@@ -0,0 +1,593 @@
+//! The lobby server online players meet in, and the client the frontends connect to it with.
+
+use crate::game_data::Rules;
+use crate::game_state::GameMode;
+use crate::netplay::{Connection, MatchSetup, Message, Netcode, Transport, PROTOCOL_VERSION};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// The port the lobby server listens on by default
+pub const DEFAULT_PORT: u16 = 7780;
+
+/// Room codes are this many letters long
+pub const CODE_LENGTH: usize = 4;
+
+/// Letters used in room codes, the ones that are easily confused are left out
+const CODE_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Players in a room
+const SEATS: usize = 2;
+
+/// The settings of the matches played in a room
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// The game mode played
+    pub mode: GameMode,
+    /// The level both players start at
+    pub init_level: usize,
+    /// How the inputs are synchronized
+    pub netcode: Netcode,
+    /// The rules both players play by
+    pub rules: Rules,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), String> {
+        self.rules.validate()?;
+        if self.init_level >= self.rules.levels() {
+            return Err(format!(
+                "The start level must be below {}",
+                self.rules.levels()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What a client asks the lobby server for
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    /// Creates a room and joins it as its owner
+    Create(Settings),
+    /// Joins the room with a code, case doesn't matter
+    Join {
+        /// The code of the room
+        code: String,
+    },
+    /// Changes the settings of the room, only the owner can
+    Configure(Settings),
+    /// The match starts once both players are ready
+    Ready(bool),
+    /// Reports how the match ended, `None` for a draw. The result counts once
+    /// both players report the same one.
+    Finished {
+        /// The seat of the winner
+        winner: Option<usize>,
+    },
+    /// Leaves the room, which ends the series
+    Leave,
+}
+
+/// The room as the server sends it to its players
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomState {
+    /// The code others join the room with
+    pub code: String,
+    /// The settings of the next match
+    pub settings: Settings,
+    /// Whether each player in the room is ready, the owner first
+    pub ready: Vec<bool>,
+    /// Matches won by each player in the current series
+    pub wins: [u32; SEATS],
+    /// Matches drawn in the current series
+    pub draws: u32,
+    /// Set while a match is played
+    pub playing: bool,
+    /// The player the room was sent to, the owner is 0
+    pub seat: usize,
+}
+
+/// What the lobby server tells a client
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// The room the client is in has changed
+    Room(RoomState),
+    /// The client is no longer in a room
+    Left,
+    /// A request was refused
+    Error(String),
+    /// Both players are ready, the game messages are relayed until the match has finished
+    Start {
+        /// The player the client controls
+        player: usize,
+        /// The match both clients start
+        setup: MatchSetup,
+    },
+    /// The opponent left during the match
+    OpponentLeft,
+}
+
+fn encode<T: Serialize>(value: &T) -> Message {
+    Message::Lobby(serde_json::to_string(value).expect("lobby messages can always be serialized"))
+}
+
+fn decode<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|e| format!("Received an invalid lobby message: {}", e))
+}
+
+struct Peer {
+    connection: Connection,
+    /// Set once the peer has sent a compatible `Join`
+    joined: bool,
+    room: Option<String>,
+}
+
+struct Room {
+    settings: Settings,
+    /// The peer and ready flag of every player, the owner first
+    players: Vec<(usize, bool)>,
+    wins: [u32; SEATS],
+    draws: u32,
+    playing: bool,
+    /// The result reported by each player of the running match
+    reported: [Option<Option<usize>>; SEATS],
+}
+
+/// Holds the rooms and pairs the players in them. Every connection is polled
+/// from one thread, nothing blocks.
+pub struct Server {
+    listener: TcpListener,
+    peers: BTreeMap<usize, Peer>,
+    next_peer: usize,
+    rooms: BTreeMap<String, Room>,
+}
+
+impl Server {
+    /// Serves the rooms to the clients connecting to the listener
+    pub fn new(listener: TcpListener) -> Result<Server, String> {
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Couldn't set up the server: {}", e))?;
+        Ok(Server {
+            listener,
+            peers: BTreeMap::new(),
+            next_peer: 0,
+            rooms: BTreeMap::new(),
+        })
+    }
+
+    /// Serves the clients until the process is stopped
+    pub fn run(mut self) {
+        loop {
+            self.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Accepts new clients and handles every message that has arrived
+    pub fn poll(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let connection = stream
+                        .set_nonblocking(false)
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| Connection::new(stream)?.start());
+                    if let Ok(connection) = connection {
+                        let peer = Peer {
+                            connection,
+                            joined: false,
+                            room: None,
+                        };
+                        self.peers.insert(self.next_peer, peer);
+                        self.next_peer += 1;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // one failed client doesn't stop the server
+                Err(_) => break,
+            }
+        }
+        let ids: Vec<usize> = self.peers.keys().copied().collect();
+        for id in ids {
+            if let Err(e) = self.receive(id) {
+                self.send(id, &Event::Error(e));
+                self.disconnect(id);
+            }
+        }
+        for peer in self.peers.values_mut() {
+            // a peer that can't be written to is noticed when reading from it
+            let _ = peer.connection.flush();
+        }
+    }
+
+    fn receive(&mut self, id: usize) -> Result<(), String> {
+        loop {
+            let peer = match self.peers.get_mut(&id) {
+                Some(peer) => peer,
+                None => return Ok(()),
+            };
+            let message = match peer.connection.receive()? {
+                Some(message) => message,
+                None if peer.connection.closed() => {
+                    self.disconnect(id);
+                    return Ok(());
+                }
+                None => return Ok(()),
+            };
+            match message {
+                Message::Join { version } if version == PROTOCOL_VERSION => peer.joined = true,
+                Message::Join { version } => {
+                    return Err(format!(
+                        "The server runs version {}, found {}",
+                        PROTOCOL_VERSION, version
+                    ))
+                }
+                _ if !peer.joined => return Err("Expected a join request".to_string()),
+                Message::Lobby(json) => {
+                    let request = decode(&json)?;
+                    if let Err(e) = self.handle(id, request) {
+                        self.send(id, &Event::Error(e));
+                    }
+                }
+                Message::Input { .. }
+                | Message::Inputs { .. }
+                | Message::Hash { .. }
+                | Message::Quit => self.relay(id, &message),
+                _ => return Err("Received an unexpected message".to_string()),
+            }
+        }
+    }
+
+    fn handle(&mut self, id: usize, request: Request) -> Result<(), String> {
+        let code = self.peers[&id].room.clone();
+        match (request, code) {
+            (Request::Create(settings), None) => {
+                settings.validate()?;
+                let code = self.new_code();
+                self.rooms.insert(
+                    code.clone(),
+                    Room {
+                        settings,
+                        players: vec![(id, false)],
+                        wins: [0; SEATS],
+                        draws: 0,
+                        playing: false,
+                        reported: [None; SEATS],
+                    },
+                );
+                self.enter(id, &code);
+            }
+            (Request::Join { code }, None) => {
+                let code = code.to_uppercase();
+                let room = self
+                    .rooms
+                    .get_mut(&code)
+                    .ok_or_else(|| format!("There is no room {}", code))?;
+                if room.players.len() >= SEATS {
+                    return Err(format!("Room {} is full", code));
+                }
+                room.players.push((id, false));
+                // a new opponent starts a new series
+                room.wins = [0; SEATS];
+                room.draws = 0;
+                self.enter(id, &code);
+            }
+            (Request::Create(_), Some(_)) | (Request::Join { .. }, Some(_)) => {
+                return Err("Leave the room first".to_string())
+            }
+            (_, None) => return Err("Not in a room".to_string()),
+            (Request::Configure(settings), Some(code)) => {
+                let room = self.rooms.get_mut(&code).expect("rooms of peers exist");
+                if room.players[0].0 != id {
+                    return Err("Only the owner of the room can change it".to_string());
+                }
+                if room.playing {
+                    return Err("The match has already started".to_string());
+                }
+                settings.validate()?;
+                room.settings = settings;
+                for player in &mut room.players {
+                    player.1 = false;
+                }
+                self.update_room(&code);
+            }
+            (Request::Ready(ready), Some(code)) => {
+                let room = self.rooms.get_mut(&code).expect("rooms of peers exist");
+                if room.playing {
+                    return Err("The match has already started".to_string());
+                }
+                for player in &mut room.players {
+                    if player.0 == id {
+                        player.1 = ready;
+                    }
+                }
+                if room.players.len() == SEATS && room.players.iter().all(|(_, ready)| *ready) {
+                    self.start_match(&code);
+                }
+                self.update_room(&code);
+            }
+            (Request::Finished { winner }, Some(code)) => {
+                let room = self.rooms.get_mut(&code).expect("rooms of peers exist");
+                let seat = room.seat(id);
+                if !room.playing {
+                    return Err("No match is played".to_string());
+                }
+                room.reported[seat] = Some(winner);
+                if let [Some(first), Some(second)] = room.reported {
+                    // players that disagree don't change the series
+                    if first == second {
+                        match first {
+                            Some(winner) if winner < SEATS => room.wins[winner] += 1,
+                            Some(_) => (),
+                            None => room.draws += 1,
+                        }
+                    }
+                    room.playing = false;
+                    self.update_room(&code);
+                }
+            }
+            (Request::Leave, Some(_)) => {
+                self.leave(id);
+                self.send(id, &Event::Left);
+            }
+        }
+        Ok(())
+    }
+
+    /// A room code that isn't used
+    fn new_code(&self) -> String {
+        let mut rng = rand::thread_rng();
+        loop {
+            let code: String = (0..CODE_LENGTH)
+                .map(|_| CODE_LETTERS[rng.gen_range(0, CODE_LETTERS.len())] as char)
+                .collect();
+            if !self.rooms.contains_key(&code) {
+                return code;
+            }
+        }
+    }
+
+    fn enter(&mut self, id: usize, code: &str) {
+        if let Some(peer) = self.peers.get_mut(&id) {
+            peer.room = Some(code.to_string());
+        }
+        self.update_room(code);
+    }
+
+    fn start_match(&mut self, code: &str) {
+        let room = self.rooms.get_mut(code).expect("the room exists");
+        room.playing = true;
+        room.reported = [None; SEATS];
+        let setup = MatchSetup {
+            seed: rand::random(),
+            mode: room.settings.mode,
+            init_level: room.settings.init_level,
+            netcode: room.settings.netcode,
+            rules: room.settings.rules.clone(),
+        };
+        let players: Vec<usize> = room.players.iter().map(|(id, _)| *id).collect();
+        for player in &mut room.players {
+            player.1 = false;
+        }
+        for (seat, id) in players.into_iter().enumerate() {
+            let start = Event::Start {
+                player: seat,
+                setup: setup.clone(),
+            };
+            self.send(id, &start);
+        }
+    }
+
+    /// Forwards a game message to the opponent while a match is played
+    fn relay(&mut self, id: usize, message: &Message) {
+        let room = match self.peers[&id]
+            .room
+            .as_ref()
+            .and_then(|code| self.rooms.get(code))
+        {
+            Some(room) => room,
+            None => return,
+        };
+        if !room.playing {
+            return;
+        }
+        let opponent = room
+            .players
+            .iter()
+            .map(|(other, _)| *other)
+            .find(|other| *other != id);
+        if let Some(peer) = opponent.and_then(|other| self.peers.get_mut(&other)) {
+            let _ = peer.connection.send(message);
+        }
+    }
+
+    fn leave(&mut self, id: usize) {
+        let code = match self.peers.get_mut(&id).and_then(|peer| peer.room.take()) {
+            Some(code) => code,
+            None => return,
+        };
+        let room = self.rooms.get_mut(&code).expect("rooms of peers exist");
+        let was_playing = room.playing;
+        room.players.retain(|(other, _)| *other != id);
+        room.playing = false;
+        room.wins = [0; SEATS];
+        room.draws = 0;
+        for player in &mut room.players {
+            player.1 = false;
+        }
+        if room.players.is_empty() {
+            self.rooms.remove(&code);
+            return;
+        }
+        if was_playing {
+            let opponent = room.players[0].0;
+            self.send(opponent, &Event::OpponentLeft);
+        }
+        self.update_room(&code);
+    }
+
+    fn disconnect(&mut self, id: usize) {
+        self.leave(id);
+        if let Some(mut peer) = self.peers.remove(&id) {
+            let _ = peer.connection.flush();
+        }
+    }
+
+    /// Sends the room to everyone in it
+    fn update_room(&mut self, code: &str) {
+        let room = match self.rooms.get(code) {
+            Some(room) => room,
+            None => return,
+        };
+        let state = RoomState {
+            code: code.to_string(),
+            settings: room.settings.clone(),
+            ready: room.players.iter().map(|(_, ready)| *ready).collect(),
+            wins: room.wins,
+            draws: room.draws,
+            playing: room.playing,
+            seat: 0,
+        };
+        let players: Vec<usize> = room.players.iter().map(|(id, _)| *id).collect();
+        for (seat, id) in players.into_iter().enumerate() {
+            self.send(
+                id,
+                &Event::Room(RoomState {
+                    seat,
+                    ..state.clone()
+                }),
+            );
+        }
+    }
+
+    fn send(&mut self, id: usize, event: &Event) {
+        if let Some(peer) = self.peers.get_mut(&id) {
+            let _ = peer.connection.send(&encode(event));
+        }
+    }
+}
+
+impl Room {
+    fn seat(&self, id: usize) -> usize {
+        self.players
+            .iter()
+            .position(|(other, _)| *other == id)
+            .expect("the peer is in the room")
+    }
+}
+
+/// A connection to the lobby server
+pub struct Client {
+    connection: Connection,
+    events: VecDeque<Event>,
+    /// Game messages of the running match
+    game: VecDeque<Message>,
+    playing: bool,
+    opponent_left: bool,
+}
+
+impl Client {
+    /// Greets the lobby server on a connected stream
+    pub fn new(stream: TcpStream) -> Result<Client, String> {
+        let mut connection = Connection::new(stream)?.start()?;
+        connection.send(&Message::Join {
+            version: PROTOCOL_VERSION,
+        })?;
+        Ok(Client {
+            connection,
+            events: VecDeque::new(),
+            game: VecDeque::new(),
+            playing: false,
+            opponent_left: false,
+        })
+    }
+
+    /// Sends a request, the answer arrives as an event
+    pub fn request(&mut self, request: &Request) -> Result<(), String> {
+        if let Request::Finished { .. } | Request::Leave = request {
+            self.playing = false;
+        }
+        self.connection.send(&encode(request))
+    }
+
+    /// Returns the next event from the server, if any has arrived
+    pub fn poll(&mut self) -> Result<Option<Event>, String> {
+        self.receive()?;
+        if self.events.is_empty() && self.connection.closed() {
+            return Err("The lobby server closed the connection".to_string());
+        }
+        Ok(self.events.pop_front())
+    }
+
+    /// Sorts the arrived messages into lobby events and game messages
+    fn receive(&mut self) -> Result<(), String> {
+        while let Some(message) = self.connection.receive()? {
+            match message {
+                Message::Lobby(json) => {
+                    let event = decode(&json)?;
+                    match &event {
+                        Event::Start { .. } => {
+                            self.playing = true;
+                            self.opponent_left = false;
+                            self.game.clear();
+                        }
+                        Event::OpponentLeft => self.opponent_left = true,
+                        _ => (),
+                    }
+                    self.events.push_back(event);
+                }
+                // messages of a match that has already ended are dropped
+                message if self.playing => self.game.push_back(message),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The transport of a match played through the lobby server. The client is
+/// shared with the frontend, which keeps using it for the room.
+pub struct Relay {
+    client: Rc<RefCell<Client>>,
+}
+
+impl Relay {
+    /// Plays a match through the client, after it has received `Event::Start`
+    pub fn new(client: Rc<RefCell<Client>>) -> Relay {
+        Relay { client }
+    }
+}
+
+impl Transport for Relay {
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        self.client.borrow_mut().connection.send(message)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.client.borrow_mut().connection.flush()
+    }
+
+    fn receive(&mut self) -> Result<Option<Message>, String> {
+        let mut client = self.client.borrow_mut();
+        client.receive()?;
+        Ok(client.game.pop_front())
+    }
+
+    fn closed(&self) -> bool {
+        let client = self.client.borrow();
+        client.opponent_left || client.connection.closed()
+    }
+
+    /// Tells the opponent we are leaving, the connection to the lobby stays open
+    fn close(&mut self) {
+        let _ = self.send(&Message::Quit);
+    }
+}
@@ -0,0 +1,854 @@
+//! Online games between two peers: the messages, the connection and the netcodes.
+
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Controller, Game, GameMode, Snapshot, PLAYER_AMOUNT};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// Bumped whenever the messages or the engine change in an incompatible way
+pub const PROTOCOL_VERSION: u32 = 7;
+
+/// The longest message a peer may send, longer frames close the connection
+/// instead of being buffered
+pub const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Ticks between a local input and the tick it is performed on in lockstep, hides the latency
+pub const INPUT_DELAY: u32 = 3;
+
+/// Ticks between a local input and the tick it is performed on with rollback
+pub const ROLLBACK_INPUT_DELAY: u32 = 1;
+
+/// How many ticks rollback may run ahead of the other peer's inputs
+pub const MAX_ROLLBACK: u32 = 8;
+
+/// Ticks between the state hashes that are compared to detect desyncs
+pub const HASH_INTERVAL: u32 = 60;
+
+/// How long the handshake may take
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the other peer to close the connection after leaving
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How the two games are kept in sync
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Netcode {
+    /// Wait for the other player's inputs before every tick
+    Lockstep,
+    /// Guess the other player's inputs and replay the game when the guess was wrong
+    Rollback,
+}
+
+/// Everything both peers need to create the same game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchSetup {
+    /// The seed both games are created with
+    pub seed: u64,
+    /// The game mode played
+    pub mode: GameMode,
+    /// The level both players start at
+    pub init_level: usize,
+    /// How the inputs are synchronized
+    pub netcode: Netcode,
+    /// The rules both players play by
+    pub rules: Rules,
+}
+
+impl MatchSetup {
+    /// Creates the game both peers play
+    pub fn create_game(&self) -> Game {
+        Game::new(
+            &self.rules,
+            self.init_level,
+            self.mode,
+            vec![Controller::Keyboard; PLAYER_AMOUNT],
+            self.seed,
+        )
+        .expect("keyboard controllers can't fail")
+    }
+}
+
+/// The messages sent between two peers, each framed with its length
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// First message from the joining peer
+    Join {
+        /// The `PROTOCOL_VERSION` of the peer
+        version: u32,
+    },
+    /// The host's answer to `Join`, the game starts when it arrives
+    Start(MatchSetup),
+    /// The actions of the sending peer's player on a tick
+    Input {
+        /// The tick the actions are performed before
+        tick: u32,
+        /// The actions, in the order they were performed
+        actions: Vec<Action>,
+    },
+    /// The state hash after a tick
+    Hash {
+        /// The tick the state was hashed after
+        tick: u32,
+        /// The state hash
+        hash: u64,
+    },
+    /// The actions of the sending peer's player on every tick from `first`, resent
+    /// until acknowledged. `ack` is the first tick the sender is missing inputs for.
+    Inputs {
+        /// The tick of the first actions
+        first: u32,
+        /// The first tick the sender is missing inputs for
+        ack: u32,
+        /// The actions of every tick
+        inputs: Vec<Vec<Action>>,
+    },
+    /// The sending peer left the game
+    Quit,
+    /// Sent to a spectator when it connects or a new game starts, the game as a
+    /// JSON `spectate::View`
+    Watch {
+        /// The `PROTOCOL_VERSION` of the broadcast
+        version: u32,
+        /// The game as JSON
+        view: String,
+    },
+    /// Every action performed on a tick, sent to spectators
+    Frame {
+        /// The tick the actions are performed before
+        tick: u32,
+        /// The player and the action, in the order they were performed
+        actions: Vec<(usize, Action)>,
+    },
+    /// A JSON `lobby::Request` or `lobby::Event`, sent between the lobby server and its clients
+    Lobby(String),
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        match self {
+            Message::Join { version } => {
+                body.push(0);
+                body.extend_from_slice(&version.to_be_bytes());
+            }
+            Message::Start(setup) => {
+                body.push(1);
+                body.extend_from_slice(&setup.seed.to_be_bytes());
+                body.extend_from_slice(&(setup.init_level as u32).to_be_bytes());
+                body.push(setup.netcode as u8);
+                body.push(setup.mode as u8);
+                let rules = toml::to_string(&setup.rules).expect("Rules can always be serialized");
+                body.extend_from_slice(rules.as_bytes());
+            }
+            Message::Input { tick, actions } => {
+                body.push(2);
+                body.extend_from_slice(&tick.to_be_bytes());
+                body.extend(actions.iter().map(|action| *action as u8));
+            }
+            Message::Hash { tick, hash } => {
+                body.push(3);
+                body.extend_from_slice(&tick.to_be_bytes());
+                body.extend_from_slice(&hash.to_be_bytes());
+            }
+            Message::Quit => body.push(4),
+            Message::Inputs { first, ack, inputs } => {
+                body.push(5);
+                body.extend_from_slice(&first.to_be_bytes());
+                body.extend_from_slice(&ack.to_be_bytes());
+                for actions in inputs {
+                    body.push(actions.len() as u8);
+                    body.extend(actions.iter().map(|action| *action as u8));
+                }
+            }
+            Message::Watch { version, view } => {
+                body.push(6);
+                body.extend_from_slice(&version.to_be_bytes());
+                body.extend_from_slice(view.as_bytes());
+            }
+            Message::Frame { tick, actions } => {
+                body.push(7);
+                body.extend_from_slice(&tick.to_be_bytes());
+                for (player, action) in actions {
+                    body.push(*player as u8);
+                    body.push(*action as u8);
+                }
+            }
+            Message::Lobby(json) => {
+                body.push(8);
+                body.extend_from_slice(json.as_bytes());
+            }
+        }
+        let mut frame = (body.len() as u32).to_be_bytes().to_vec();
+        frame.append(&mut body);
+        frame
+    }
+
+    fn decode(body: &[u8]) -> Result<Message, String> {
+        let invalid = || "Received an invalid message".to_string();
+        let u32_at = |i: usize| -> Result<u32, String> {
+            let bytes = body.get(i..i + 4).ok_or_else(invalid)?;
+            Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+        let u64_at = |i: usize| -> Result<u64, String> {
+            Ok((u32_at(i)? as u64) << 32 | u32_at(i + 4)? as u64)
+        };
+        match body.first() {
+            Some(0) => Ok(Message::Join {
+                version: u32_at(1)?,
+            }),
+            Some(1) => {
+                let netcode = match body.get(13) {
+                    Some(0) => Netcode::Lockstep,
+                    Some(1) => Netcode::Rollback,
+                    _ => return Err(invalid()),
+                };
+                let mode = match body.get(14) {
+                    Some(0) => GameMode::Versus,
+                    Some(1) => GameMode::Marathon,
+                    _ => return Err(invalid()),
+                };
+                let rules = std::str::from_utf8(&body[15..]).map_err(|_| invalid())?;
+                Ok(Message::Start(MatchSetup {
+                    seed: u64_at(1)?,
+                    mode,
+                    init_level: u32_at(9)? as usize,
+                    netcode,
+                    rules: toml::from_str(rules).map_err(|e| format!("Invalid rules: {}", e))?,
+                }))
+            }
+            Some(2) => Ok(Message::Input {
+                tick: u32_at(1)?,
+                actions: body[5..]
+                    .iter()
+                    .map(|code| Action::ALL.get(*code as usize).copied().ok_or_else(invalid))
+                    .collect::<Result<_, _>>()?,
+            }),
+            Some(3) => Ok(Message::Hash {
+                tick: u32_at(1)?,
+                hash: u64_at(5)?,
+            }),
+            Some(4) => Ok(Message::Quit),
+            Some(5) => {
+                let mut inputs = Vec::new();
+                let mut i = 9;
+                while i < body.len() {
+                    let count = body[i] as usize;
+                    let codes = body.get(i + 1..i + 1 + count).ok_or_else(invalid)?;
+                    inputs.push(
+                        codes
+                            .iter()
+                            .map(|code| {
+                                Action::ALL.get(*code as usize).copied().ok_or_else(invalid)
+                            })
+                            .collect::<Result<_, _>>()?,
+                    );
+                    i += 1 + count;
+                }
+                Ok(Message::Inputs {
+                    first: u32_at(1)?,
+                    ack: u32_at(5)?,
+                    inputs,
+                })
+            }
+            Some(6) => Ok(Message::Watch {
+                version: u32_at(1)?,
+                view: String::from_utf8(body[5..].to_vec()).map_err(|_| invalid())?,
+            }),
+            Some(7) => {
+                let pairs = body.get(5..).ok_or_else(invalid)?;
+                if pairs.len() % 2 != 0 {
+                    return Err(invalid());
+                }
+                Ok(Message::Frame {
+                    tick: u32_at(1)?,
+                    actions: pairs
+                        .chunks(2)
+                        .map(|pair| {
+                            let action = Action::ALL.get(pair[1] as usize).ok_or_else(invalid)?;
+                            Ok((pair[0] as usize, *action))
+                        })
+                        .collect::<Result<_, String>>()?,
+                })
+            }
+            Some(8) => Ok(Message::Lobby(
+                String::from_utf8(body[1..].to_vec()).map_err(|_| invalid())?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Carries messages between two peers
+pub trait Transport {
+    /// Queues a message, it may be sent later
+    fn send(&mut self, message: &Message) -> Result<(), String>;
+    /// Sends as much of the queued messages as possible
+    fn flush(&mut self) -> Result<(), String>;
+    /// Returns the next message that has arrived, if any
+    fn receive(&mut self) -> Result<Option<Message>, String>;
+    /// True when the other peer has closed its end
+    fn closed(&self) -> bool;
+    /// Tells the other peer we are leaving
+    fn close(&mut self);
+}
+
+/// A framed, non-blocking TCP connection to the other peer
+pub struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    unsent: Vec<u8>,
+    /// Set when the other peer has closed the connection
+    closed: bool,
+}
+
+impl Connection {
+    /// Wraps a connected stream, which blocks until `start` is called
+    pub fn new(stream: TcpStream) -> Result<Connection, String> {
+        stream
+            .set_nodelay(true)
+            .and_then(|_| stream.set_read_timeout(Some(TIMEOUT)))
+            .map_err(|e| format!("Couldn't set up the connection: {}", e))?;
+        Ok(Connection {
+            stream,
+            received: Vec::new(),
+            unsent: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// Blocks until a message arrives, used during the handshake
+    fn receive_blocking(&mut self) -> Result<Message, String> {
+        match self.receive()? {
+            Some(message) => Ok(message),
+            None if self.closed => Err("The other player left".to_string()),
+            None => Err("The other player didn't answer".to_string()),
+        }
+    }
+
+    /// Bytes queued that the other peer hasn't taken yet
+    pub fn backlog(&self) -> usize {
+        self.unsent.len()
+    }
+
+    /// Ends the handshake, the game doesn't wait for the network
+    pub fn start(self) -> Result<Connection, String> {
+        self.stream
+            .set_nonblocking(true)
+            .map_err(|e| format!("Couldn't set up the connection: {}", e))?;
+        Ok(self)
+    }
+}
+
+impl Transport for Connection {
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        self.unsent.extend(message.encode());
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return Err("Connection closed".to_string()),
+                Ok(written) => {
+                    self.unsent.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(format!("Connection lost: {}", e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<Message>, String> {
+        loop {
+            if self.received.len() >= 4 {
+                let length = u32::from_be_bytes([
+                    self.received[0],
+                    self.received[1],
+                    self.received[2],
+                    self.received[3],
+                ]) as usize;
+                if length > MAX_MESSAGE_LEN {
+                    self.closed = true;
+                    self.received.clear();
+                    return Err("Received an invalid message".to_string());
+                }
+                if self.received.len() >= 4 + length {
+                    let message = Message::decode(&self.received[4..4 + length]);
+                    self.received.drain(..4 + length);
+                    return message.map(Some);
+                }
+            }
+            if self.closed {
+                return Ok(None);
+            }
+            let mut buffer = [0; 1024];
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.received.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::TimedOut => {
+                    return Err("The other player didn't answer".to_string())
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(format!("Connection lost: {}", e)),
+            }
+        }
+    }
+
+    fn closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Sends `Quit` and waits for the other peer to close the connection
+    fn close(&mut self) {
+        if self.send(&Message::Quit).is_err()
+            || self.stream.shutdown(Shutdown::Write).is_err()
+            || self.stream.set_nonblocking(false).is_err()
+            || self.stream.set_read_timeout(Some(CLOSE_TIMEOUT)).is_err()
+        {
+            return;
+        }
+        // reading everything the other peer sent lets both sides close cleanly
+        while let Ok(Some(_)) = self.receive() {}
+    }
+}
+
+/// Waits for the joining peer and sends it the game to play
+pub fn host(stream: TcpStream, setup: &MatchSetup) -> Result<Connection, String> {
+    stream
+        .set_nonblocking(false)
+        .map_err(|e| format!("Couldn't set up the connection: {}", e))?;
+    let mut connection = Connection::new(stream)?;
+    match connection.receive_blocking()? {
+        Message::Join { version } if version == PROTOCOL_VERSION => (),
+        Message::Join { version } => {
+            connection.send(&Message::Quit)?;
+            return Err(format!(
+                "The other player runs version {}, expected {}",
+                version, PROTOCOL_VERSION
+            ));
+        }
+        _ => return Err("Expected a join request".to_string()),
+    }
+    connection.send(&Message::Start(setup.clone()))?;
+    connection.start()
+}
+
+/// Asks the host to join its game, returns the game to play
+pub fn join(stream: TcpStream) -> Result<(Connection, MatchSetup), String> {
+    let mut connection = Connection::new(stream)?;
+    connection.send(&Message::Join {
+        version: PROTOCOL_VERSION,
+    })?;
+    match connection.receive_blocking()? {
+        Message::Start(setup) => {
+            setup.rules.validate()?;
+            Ok((connection.start()?, setup))
+        }
+        Message::Quit => Err("The host runs an incompatible version".to_string()),
+        _ => Err("Expected the game setup".to_string()),
+    }
+}
+
+/// Starts an online game with the netcode of the setup, the host is player 1
+/// and the joining peer player 2
+pub fn start_session<T: Transport + 'static>(
+    setup: &MatchSetup,
+    transport: T,
+    local: usize,
+) -> Box<dyn Session> {
+    match setup.netcode {
+        Netcode::Lockstep => Box::new(Lockstep::new(transport, local)),
+        Netcode::Rollback => Box::new(Rollback::new(transport, local)),
+    }
+}
+
+/// A game played against another peer
+pub trait Session {
+    /// The player controlled by this peer
+    fn local_player(&self) -> usize;
+    /// Queues an action of the local player
+    fn perform(&mut self, action: Action);
+    /// Called once per tick, exchanges inputs and advances the game
+    fn update(&mut self, game: &mut Game) -> Result<(), String>;
+    /// True if the game doesn't depend on guessed inputs, only then is a finished game final
+    fn confirmed(&self) -> bool;
+    /// The first tick that might still be replayed with other inputs
+    fn confirmed_tick(&self) -> u32;
+    /// Tells the other peer we are leaving
+    fn close(self: Box<Self>);
+}
+
+/// The state hashes of both peers waiting to be compared
+#[derive(Default)]
+struct HashCheck {
+    local: BTreeMap<u32, u64>,
+    remote: BTreeMap<u32, u64>,
+    /// How many hashes have matched
+    matched: u32,
+}
+
+impl HashCheck {
+    /// Returns an error for the first tick where the hashes differ
+    fn compare(&mut self) -> Result<(), String> {
+        let newest_remote = self.remote.keys().next_back().copied();
+        let newest_local = self.local.keys().next_back().copied();
+        let ticks: Vec<u32> = self
+            .local
+            .keys()
+            .filter(|tick| self.remote.contains_key(tick))
+            .copied()
+            .collect();
+        for tick in ticks {
+            if self.local.remove(&tick) != self.remote.remove(&tick) {
+                return Err(format!("Desync detected at tick {}", tick));
+            }
+            self.matched += 1;
+        }
+        // hashes older than the newest one of the other peer will never be compared,
+        // the other hash was lost
+        if let Some(newest) = newest_remote {
+            self.local.retain(|tick, _| *tick > newest);
+        }
+        if let Some(newest) = newest_local {
+            self.remote.retain(|tick, _| *tick > newest);
+        }
+        Ok(())
+    }
+}
+
+/// Runs a game in lockstep with another peer by only exchanging inputs.
+/// A tick is simulated once the inputs of both players for it are known, which
+/// keeps the deterministic engines, and with them the garbage sent between
+/// the boards, on the same tick on both machines.
+pub struct Lockstep<T: Transport = Connection> {
+    transport: T,
+    /// The player controlled by this peer
+    local: usize,
+    /// The next tick to simulate
+    tick: u32,
+    /// The next tick to send local inputs for
+    sent: u32,
+    /// Local actions waiting to be sent
+    pending: Vec<Action>,
+    /// Known inputs for every player by tick
+    inputs: [BTreeMap<u32, Vec<Action>>; PLAYER_AMOUNT],
+    hashes: HashCheck,
+    /// Set when the other peer has sent `Quit`
+    remote_left: bool,
+}
+
+impl<T: Transport> Lockstep<T> {
+    /// A session controlling player `local` of the game
+    pub fn new(transport: T, local: usize) -> Lockstep<T> {
+        Lockstep {
+            transport,
+            local,
+            tick: 0,
+            sent: INPUT_DELAY,
+            pending: Vec::new(),
+            inputs: [BTreeMap::new(), BTreeMap::new()],
+            hashes: HashCheck::default(),
+            remote_left: false,
+        }
+    }
+
+    /// True if the inputs of every player for the next tick are known
+    fn ready(&self) -> bool {
+        self.tick < INPUT_DELAY || self.inputs.iter().all(|i| i.contains_key(&self.tick))
+    }
+
+    fn receive(&mut self) -> Result<(), String> {
+        let remote = 1 - self.local;
+        while let Some(message) = self.transport.receive()? {
+            match message {
+                Message::Input { tick, actions } => {
+                    self.inputs[remote].insert(tick, actions);
+                }
+                Message::Hash { tick, hash } => {
+                    self.hashes.remote.insert(tick, hash);
+                }
+                Message::Quit => self.remote_left = true,
+                _ => return Err("Received an unexpected message".to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Session for Lockstep<T> {
+    fn local_player(&self) -> usize {
+        self.local
+    }
+
+    /// The action is performed `INPUT_DELAY` ticks later
+    fn perform(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    /// Sends the local inputs and simulates the next tick if the other peer's
+    /// inputs for it have arrived
+    fn update(&mut self, game: &mut Game) -> Result<(), String> {
+        self.receive()?;
+        let target = self.tick + INPUT_DELAY;
+        if self.sent <= target {
+            let actions: Vec<Action> = self.pending.drain(..).collect();
+            self.inputs[self.local].insert(target, actions.clone());
+            self.transport.send(&Message::Input {
+                tick: target,
+                actions,
+            })?;
+            self.sent = target + 1;
+        }
+        let ready = self.ready();
+        if ready {
+            for player in 0..PLAYER_AMOUNT {
+                for action in self.inputs[player].remove(&self.tick).unwrap_or_default() {
+                    game.perform(player, action);
+                }
+            }
+            game.update();
+            if (self.tick + 1).is_multiple_of(HASH_INTERVAL) {
+                let hash = game.state_hash();
+                self.hashes.local.insert(self.tick, hash);
+                self.transport.send(&Message::Hash {
+                    tick: self.tick,
+                    hash,
+                })?;
+            }
+            self.tick += 1;
+        }
+        self.transport.flush()?;
+        // a desync is reported even if the other peer left because of it
+        self.hashes.compare()?;
+        if !ready && (self.remote_left || self.transport.closed()) {
+            return Err("The other player left".to_string());
+        }
+        Ok(())
+    }
+
+    fn confirmed(&self) -> bool {
+        true
+    }
+
+    fn confirmed_tick(&self) -> u32 {
+        self.tick
+    }
+
+    fn close(mut self: Box<Self>) {
+        self.transport.close();
+    }
+}
+
+/// Runs a game against another peer without waiting for its inputs. Missing
+/// inputs of the other player are guessed to be empty, and when the real ones
+/// arrive the game is rewound to a snapshot and replayed. The game never runs
+/// more than `MAX_ROLLBACK` ticks ahead of the other peer's inputs.
+/// Inputs are resent until acknowledged, so lost messages only cause a delay.
+pub struct Rollback<T: Transport = Connection> {
+    transport: T,
+    /// The player controlled by this peer
+    local: usize,
+    /// The next tick to simulate
+    tick: u32,
+    /// Local actions waiting for the next tick
+    pending: Vec<Action>,
+    local_inputs: BTreeMap<u32, Vec<Action>>,
+    /// The other peer has every local input before this tick
+    acked: u32,
+    remote_inputs: BTreeMap<u32, Vec<Action>>,
+    /// Every remote input before this tick is known
+    remote_next: u32,
+    /// The state before every tick that used guessed inputs
+    snapshots: VecDeque<(u32, Snapshot)>,
+    /// The next tick to send a state hash for
+    next_hash: u32,
+    hashes: HashCheck,
+    /// Set when the other peer has sent `Quit`
+    remote_left: bool,
+    /// Ticks that have been simulated again because of a wrong guess
+    resimulated: u32,
+}
+
+impl<T: Transport> Rollback<T> {
+    /// A session controlling player `local` of the game
+    pub fn new(transport: T, local: usize) -> Rollback<T> {
+        Rollback {
+            transport,
+            local,
+            tick: 0,
+            pending: Vec::new(),
+            // nothing is performed before the input delay has passed
+            local_inputs: (0..ROLLBACK_INPUT_DELAY).map(|t| (t, Vec::new())).collect(),
+            acked: 0,
+            remote_inputs: BTreeMap::new(),
+            remote_next: 0,
+            snapshots: VecDeque::new(),
+            next_hash: HASH_INTERVAL - 1,
+            hashes: HashCheck::default(),
+            remote_left: false,
+            resimulated: 0,
+        }
+    }
+
+    /// Handles the arrived messages, returns the first tick that was simulated with a wrong guess
+    fn receive(&mut self) -> Result<Option<u32>, String> {
+        let mut wrong_guess = None;
+        while let Some(message) = self.transport.receive()? {
+            match message {
+                Message::Inputs { first, ack, inputs } => {
+                    self.acked = self.acked.max(ack);
+                    for (tick, actions) in (first..).zip(inputs) {
+                        if tick != self.remote_next {
+                            continue;
+                        }
+                        // the guess is always that nothing was pressed
+                        if tick < self.tick && !actions.is_empty() && wrong_guess.is_none() {
+                            wrong_guess = Some(tick);
+                        }
+                        self.remote_inputs.insert(tick, actions);
+                        self.remote_next += 1;
+                    }
+                }
+                Message::Hash { tick, hash } => {
+                    self.hashes.remote.insert(tick, hash);
+                }
+                Message::Quit => self.remote_left = true,
+                _ => return Err("Received an unexpected message".to_string()),
+            }
+        }
+        Ok(wrong_guess)
+    }
+
+    fn simulate(&self, game: &mut Game, tick: u32) {
+        let remote = 1 - self.local;
+        for player in 0..PLAYER_AMOUNT {
+            let inputs = if player == remote {
+                &self.remote_inputs
+            } else {
+                &self.local_inputs
+            };
+            for action in inputs.get(&tick).into_iter().flatten() {
+                game.perform(player, *action);
+            }
+        }
+        game.update();
+    }
+
+    /// Rewinds the game to before `from` and simulates it back to the current tick
+    fn rollback(&mut self, game: &mut Game, from: u32) {
+        let oldest = self.snapshots.front().map_or(self.tick, |(tick, _)| *tick);
+        game.restore(&self.snapshots[(from - oldest) as usize].1);
+        for tick in from..self.tick {
+            if tick > from {
+                self.snapshots[(tick - oldest) as usize].1 = game.snapshot();
+            }
+            self.simulate(game, tick);
+            self.resimulated += 1;
+        }
+    }
+
+    /// Sends the hashes of the ticks that can't change anymore
+    fn send_hashes(&mut self, game: &Game) -> Result<(), String> {
+        let oldest = self.snapshots.front().map_or(self.tick, |(tick, _)| *tick);
+        while self.next_hash < self.confirmed_tick() {
+            // the state after a tick is the state before the next one
+            let after = self.next_hash + 1;
+            let hash = if after == self.tick {
+                game.state_hash()
+            } else {
+                self.snapshots[(after - oldest) as usize].1.state_hash()
+            };
+            self.hashes.local.insert(self.next_hash, hash);
+            self.transport.send(&Message::Hash {
+                tick: self.next_hash,
+                hash,
+            })?;
+            self.next_hash += HASH_INTERVAL;
+        }
+        Ok(())
+    }
+
+    /// Forgets the snapshots and inputs that can't be needed anymore
+    fn prune(&mut self) {
+        let confirmed = self.confirmed_tick();
+        while self
+            .snapshots
+            .front()
+            .is_some_and(|(tick, _)| *tick < confirmed)
+        {
+            self.snapshots.pop_front();
+        }
+        self.remote_inputs = self.remote_inputs.split_off(&confirmed);
+        self.local_inputs = self.local_inputs.split_off(&confirmed.min(self.acked));
+    }
+}
+
+impl<T: Transport> Session for Rollback<T> {
+    fn local_player(&self) -> usize {
+        self.local
+    }
+
+    /// The action is performed `ROLLBACK_INPUT_DELAY` ticks later
+    fn perform(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    /// Replays the game if a guess was wrong and simulates the next tick unless
+    /// that would run too far ahead of the other peer. A finished game waits
+    /// for the other peer's inputs to tell if it really is over.
+    fn update(&mut self, game: &mut Game) -> Result<(), String> {
+        if let Some(from) = self.receive()? {
+            self.rollback(game, from);
+        }
+        let advance = self.tick < self.remote_next + MAX_ROLLBACK && !game.is_over();
+        if advance {
+            let actions = self.pending.drain(..).collect();
+            self.local_inputs
+                .insert(self.tick + ROLLBACK_INPUT_DELAY, actions);
+            self.snapshots.push_back((self.tick, game.snapshot()));
+            self.simulate(game, self.tick);
+            self.tick += 1;
+        }
+        // every unacknowledged input is sent again in case it was lost
+        self.transport.send(&Message::Inputs {
+            first: self.acked,
+            ack: self.remote_next,
+            inputs: self
+                .local_inputs
+                .range(self.acked..)
+                .map(|(_, a)| a.clone())
+                .collect(),
+        })?;
+        self.send_hashes(game)?;
+        self.prune();
+        self.transport.flush()?;
+        // a desync is reported even if the other peer left because of it
+        self.hashes.compare()?;
+        if !advance && !self.confirmed() && (self.remote_left || self.transport.closed()) {
+            return Err("The other player left".to_string());
+        }
+        Ok(())
+    }
+
+    fn confirmed(&self) -> bool {
+        self.remote_next >= self.tick
+    }
+
+    /// The first tick without guessed inputs
+    fn confirmed_tick(&self) -> u32 {
+        self.remote_next.min(self.tick)
+    }
+
+    fn close(mut self: Box<Self>) {
+        self.transport.close();
+    }
+}
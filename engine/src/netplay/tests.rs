@@ -0,0 +1,341 @@
+use super::{
+    host, join, Connection, Lockstep, MatchSetup, Message, Netcode, Rollback, Session, Transport,
+    HASH_INTERVAL, MAX_MESSAGE_LEN, MAX_ROLLBACK,
+};
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Game, GameMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// Address of the host, set for the second process of the two-process tests
+const PEER_ADDRESS: &str = "NETPLAY_TEST_ADDRESS";
+/// Set if the second process should desync on purpose
+const PEER_DESYNC: &str = "NETPLAY_TEST_DESYNC";
+
+const TICKS: u32 = 5 * HASH_INTERVAL;
+/// The tick the desyncing peer changes its game on
+const DESYNC_TICK: u32 = 100;
+
+/// Plays the game, pressing keys as it goes
+fn play(session: &mut Lockstep<Connection>, game: &mut Game, desync: bool) -> Result<(), String> {
+    let mut step = 0;
+    // stop once every local hash up to `TICKS` has been compared
+    while session.tick < TICKS || !session.hashes.local.is_empty() {
+        if step % 5 == 0 {
+            let action = Action::ALL[(step / 5 + session.local_player()) % Action::ALL.len()];
+            session.perform(action);
+        }
+        let tick = session.tick;
+        if let Err(e) = session.update(game) {
+            // the other peer leaves as soon as it is done, which can be a few ticks before us
+            let done = session.tick >= TICKS && session.hashes.local.is_empty();
+            if !done || e.starts_with("Desync") {
+                return Err(e);
+            }
+        }
+        if desync && tick < DESYNC_TICK && session.tick == DESYNC_TICK {
+            game.restart(1, 1);
+        }
+        if session.tick == tick {
+            thread::sleep(Duration::from_millis(1));
+        }
+        step += 1;
+    }
+    Ok(())
+}
+
+/// Starts a second test process that joins the game at the address
+fn spawn_peer(address: &str, desync: bool) -> Child {
+    let mut command = Command::new(env::current_exe().expect("no test executable"));
+    command
+        .args(["--exact", "netplay::tests::peer_process", "--ignored"])
+        .env(PEER_ADDRESS, address);
+    if desync {
+        command.env(PEER_DESYNC, "1");
+    }
+    command.spawn().expect("couldn't start the second process")
+}
+
+/// Hosts a game against a second process, returns how the game went for both
+fn host_against_peer(desync: bool) -> (Result<(), String>, bool) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't listen on localhost");
+    let address = listener.local_addr().unwrap().to_string();
+    let mut peer = spawn_peer(&address, desync);
+    let (stream, _) = listener
+        .accept()
+        .expect("the second process didn't connect");
+    let setup = MatchSetup {
+        seed: 42,
+        mode: GameMode::Versus,
+        init_level: 5,
+        netcode: Netcode::Lockstep,
+        rules: Rules::default(),
+    };
+    let mut session = Lockstep::new(host(stream, &setup).expect("handshake failed"), 0);
+    let mut game = setup.create_game();
+    let result = play(&mut session, &mut game, false);
+    Box::new(session).close();
+    let peer_ok = peer.wait().expect("second process crashed").success();
+    (result, peer_ok)
+}
+
+/// The second process of the two-process tests, does nothing when run on its own
+#[test]
+#[ignore]
+fn peer_process() {
+    let address = match env::var(PEER_ADDRESS) {
+        Ok(address) => address,
+        Err(_) => return,
+    };
+    let desync = env::var(PEER_DESYNC).is_ok();
+    let stream = TcpStream::connect(address).expect("couldn't connect to the host");
+    let (connection, setup) = join(stream).expect("handshake failed");
+    let mut session = Lockstep::new(connection, 1);
+    let mut game = setup.create_game();
+    let result = play(&mut session, &mut game, desync);
+    Box::new(session).close();
+    if desync {
+        assert!(result.unwrap_err().starts_with("Desync"));
+    } else {
+        result.expect("the game should stay in sync");
+    }
+}
+
+#[test]
+fn lockstep_two_processes() {
+    let (result, peer_ok) = host_against_peer(false);
+    result.expect("the game should stay in sync");
+    assert!(peer_ok);
+}
+
+#[test]
+fn desync_two_processes() {
+    let (result, peer_ok) = host_against_peer(true);
+    let error = result.unwrap_err();
+    // the first hash after the desync catches it
+    assert_eq!(
+        format!("Desync detected at tick {}", 2 * HASH_INTERVAL - 1),
+        error
+    );
+    assert!(peer_ok);
+}
+
+#[test]
+fn messages() {
+    let messages = [
+        Message::Join { version: 3 },
+        Message::Start(MatchSetup {
+            seed: u64::MAX - 5,
+            mode: GameMode::Marathon,
+            init_level: 7,
+            netcode: Netcode::Rollback,
+            rules: Rules {
+                gravity: vec![0.5, 0.25],
+                ..Rules::default()
+            },
+        }),
+        Message::Input {
+            tick: 1234,
+            actions: Action::ALL.to_vec(),
+        },
+        Message::Input {
+            tick: 0,
+            actions: Vec::new(),
+        },
+        Message::Hash {
+            tick: 59,
+            hash: 0xdead_beef_1234_5678,
+        },
+        Message::Quit,
+        Message::Lobby("{\"Join\":{\"code\":\"ABCD\"}}".to_string()),
+        Message::Inputs {
+            first: 17,
+            ack: 12,
+            inputs: vec![
+                Vec::new(),
+                vec![Action::Hold],
+                Action::ALL.to_vec(),
+                Vec::new(),
+            ],
+        },
+    ];
+    for message in &messages {
+        let frame = message.encode();
+        assert_eq!(
+            frame.len() - 4,
+            u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize
+        );
+        assert_eq!(Ok(message.clone()), Message::decode(&frame[4..]));
+    }
+    assert!(Message::decode(&[]).is_err());
+    assert!(Message::decode(&[2, 0, 0, 0, 1, 7]).is_err());
+    assert!(Message::decode(&[3, 0, 0]).is_err());
+    assert!(Message::decode(&[5, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1]).is_err());
+}
+
+/// Messages in flight with the tick they arrive on
+type Queue = Rc<RefCell<Vec<(u32, Vec<u8>)>>>;
+
+/// One end of an in-process link that delays, reorders and drops messages.
+/// Time is counted in ticks and advanced by the test.
+struct SimulatedLink {
+    clock: Rc<Cell<u32>>,
+    rng: Rc<RefCell<StdRng>>,
+    outgoing: Queue,
+    incoming: Queue,
+    latency: u32,
+    jitter: u32,
+    loss: f64,
+}
+
+/// Creates both ends of a link and the clock they share
+fn link(latency: u32, jitter: u32, loss: f64) -> (SimulatedLink, SimulatedLink, Rc<Cell<u32>>) {
+    let clock = Rc::new(Cell::new(0));
+    let rng = Rc::new(RefCell::new(StdRng::seed_from_u64(5)));
+    let a_to_b = Rc::new(RefCell::new(Vec::new()));
+    let b_to_a = Rc::new(RefCell::new(Vec::new()));
+    let end = |outgoing: &Rc<_>, incoming: &Rc<_>| SimulatedLink {
+        clock: clock.clone(),
+        rng: rng.clone(),
+        outgoing: Rc::clone(outgoing),
+        incoming: Rc::clone(incoming),
+        latency,
+        jitter,
+        loss,
+    };
+    let a = end(&a_to_b, &b_to_a);
+    let b = end(&b_to_a, &a_to_b);
+    (a, b, clock)
+}
+
+impl Transport for SimulatedLink {
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        let mut rng = self.rng.borrow_mut();
+        if !rng.gen_bool(self.loss) {
+            let arrival = self.clock.get() + self.latency + rng.gen_range(0, self.jitter + 1);
+            // messages go through the real encoding
+            self.outgoing.borrow_mut().push((arrival, message.encode()));
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Option<Message>, String> {
+        let mut incoming = self.incoming.borrow_mut();
+        let now = self.clock.get();
+        let next = (0..incoming.len())
+            .filter(|i| incoming[*i].0 <= now)
+            .min_by_key(|i| incoming[*i].0);
+        match next {
+            Some(i) => Message::decode(&incoming.remove(i).1[4..]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+
+    fn close(&mut self) {}
+}
+
+/// Plays two rollback peers against each other over a simulated link until
+/// both have confirmed `TICKS` ticks
+fn play_rollback(
+    latency: u32,
+    jitter: u32,
+    loss: f64,
+    desync: bool,
+) -> Result<[Rollback<SimulatedLink>; 2], String> {
+    let (a, b, clock) = link(latency, jitter, loss);
+    let setup = MatchSetup {
+        seed: 9,
+        mode: GameMode::Versus,
+        init_level: 5,
+        netcode: Netcode::Rollback,
+        rules: Rules::default(),
+    };
+    let mut peers = [Rollback::new(a, 0), Rollback::new(b, 1)];
+    let mut games = [setup.create_game(), setup.create_game()];
+    if desync {
+        // changing the game mid-match could be undone by a rollback
+        games[1].restart(setup.init_level, setup.seed + 1);
+    }
+    let mut rng = StdRng::seed_from_u64(1);
+    while peers
+        .iter()
+        .any(|peer| peer.confirmed_tick() < TICKS || !peer.hashes.local.is_empty())
+    {
+        clock.set(clock.get() + 1);
+        for (peer, game) in peers.iter_mut().zip(games.iter_mut()) {
+            if rng.gen_bool(0.2) {
+                peer.perform(Action::ALL[rng.gen_range(0, Action::ALL.len())]);
+            }
+            peer.update(game)?;
+            assert!(peer.tick <= peer.remote_next + MAX_ROLLBACK);
+        }
+        assert!(clock.get() < 100 * TICKS, "the game got stuck");
+    }
+    Ok(peers)
+}
+
+#[test]
+fn oversized_message() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let mut connection = Connection::new(listener.accept().unwrap().0).unwrap();
+    // a frame claiming to be longer than any message isn't waited for
+    let length = MAX_MESSAGE_LEN as u32 + 1;
+    sender.write_all(&length.to_be_bytes()).unwrap();
+    sender.write_all(&[0; 16]).unwrap();
+    assert_eq!(
+        Err("Received an invalid message".to_string()),
+        connection.receive()
+    );
+    assert!(connection.closed());
+}
+
+#[test]
+fn rollback_perfect_link() {
+    let peers = play_rollback(0, 0, 0.0, false).unwrap();
+    // inputs arrive before they are needed, nothing is replayed
+    for peer in &peers {
+        assert_eq!(0, peer.resimulated);
+        assert!(peer.hashes.matched >= TICKS / HASH_INTERVAL);
+    }
+}
+
+#[test]
+fn rollback_bad_link() {
+    // 100ms latency, 50ms jitter and a fifth of the messages lost
+    let peers = play_rollback(6, 3, 0.2, false).unwrap();
+    for peer in &peers {
+        assert!(peer.resimulated > 0);
+        // lost hashes are skipped, most should still be compared
+        assert!(peer.hashes.matched >= TICKS / HASH_INTERVAL / 2);
+    }
+}
+
+#[test]
+fn rollback_window() {
+    // with more latency than the window the game has to wait for the inputs
+    let peers = play_rollback(2 * MAX_ROLLBACK, 0, 0.0, false).unwrap();
+    assert!(peers[0].resimulated > 0);
+}
+
+#[test]
+fn rollback_desync() {
+    let error = play_rollback(6, 3, 0.2, true).err().unwrap();
+    assert!(error.starts_with("Desync"), "{}", error);
+}
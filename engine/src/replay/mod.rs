@@ -0,0 +1,107 @@
+//! Recording games and playing them back.
+
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Game, GameMode, Snapshot};
+use crate::netplay::PROTOCOL_VERSION;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// A recorded game: the state it was recorded from and every action performed
+/// since. Games play out the same given the same actions, so playing the
+/// actions back gives the same game, ai-scripts included.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// Replays are played back by the same version of the engine only
+    version: u32,
+    mode: GameMode,
+    rules: Rules,
+    snapshot: Snapshot,
+    /// The actions with the tick and the player, in the order they were performed
+    actions: Vec<(u32, usize, Action)>,
+    /// The tick the recording is up to
+    end: u32,
+}
+
+impl Replay {
+    /// Starts recording a game from its current state, which has to be
+    /// between two ticks with none of the next tick's actions performed yet
+    pub fn new(game: &Game) -> Replay {
+        Replay {
+            version: PROTOCOL_VERSION,
+            mode: game.mode(),
+            rules: game.rules().clone(),
+            snapshot: game.snapshot(),
+            actions: Vec::new(),
+            end: game.tick(),
+        }
+    }
+
+    /// Records the actions of the ticks before `before`, taken from the game
+    /// with `Game::take_performed`
+    pub fn record(&mut self, performed: &[(u32, usize, Action)], before: u32) {
+        let start = self.start();
+        self.actions.extend(
+            performed
+                .iter()
+                .filter(|(tick, _, _)| (start..before).contains(tick)),
+        );
+        self.end = self.end.max(before);
+    }
+
+    /// The tick the recording starts on
+    pub fn start(&self) -> u32 {
+        self.snapshot.tick()
+    }
+
+    /// The tick the recording is up to
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
+    /// Plays the recording back up to the tick `until`, or to the end if it's later
+    pub fn play(&self, until: u32) -> Result<Game, String> {
+        let mut game = self.snapshot_game()?;
+        let mut actions = self.actions.iter().peekable();
+        while game.tick() < until.min(self.end) {
+            let tick = game.tick();
+            while let Some((_, player, action)) = actions.next_if(|(t, _, _)| *t == tick) {
+                game.perform(*player, *action);
+            }
+            game.update();
+        }
+        Ok(game)
+    }
+
+    /// Writes the replay to a file as json
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+
+    /// Reads a replay saved with `save`
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        let replay: Replay = serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not a replay: {}", path.display(), e))?;
+        if replay.version != PROTOCOL_VERSION {
+            return Err(format!(
+                "{} was recorded by version {} of the game, this is version {}",
+                path.display(),
+                replay.version,
+                PROTOCOL_VERSION
+            ));
+        }
+        replay.rules.validate()?;
+        replay.snapshot_game()?;
+        Ok(replay)
+    }
+
+    fn snapshot_game(&self) -> Result<Game, String> {
+        Game::from_snapshot(&self.rules, self.mode, &self.snapshot)
+    }
+}
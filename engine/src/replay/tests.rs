@@ -0,0 +1,78 @@
+use super::Replay;
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Controller, Game, GameMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::env;
+use std::fs;
+
+/// Plays random actions for both players and records every tick
+fn play(game: &mut Game, replay: &mut Replay, rng: &mut StdRng, ticks: u32) {
+    for _ in 0..ticks {
+        for player in 0..game.player_count() {
+            if rng.gen_bool(0.2) {
+                game.perform(player, Action::ALL[rng.gen_range(0, Action::ALL.len())]);
+            }
+        }
+        game.update();
+        let tick = game.tick();
+        replay.record(&game.take_performed(tick), tick);
+    }
+}
+
+#[test]
+fn play_back() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard; 3],
+        21,
+    )
+    .expect("keyboard controllers can't fail");
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut replay = Replay::new(&game);
+    play(&mut game, &mut replay, &mut rng, 300);
+    let halfway = game.state_hash();
+    play(&mut game, &mut replay, &mut rng, 300);
+    assert_eq!((0, 600), (replay.start(), replay.end()));
+    assert_eq!(game.state_hash(), replay.play(600).unwrap().state_hash());
+    assert_eq!(halfway, replay.play(300).unwrap().state_hash());
+    assert_eq!(600, replay.play(1000).unwrap().tick());
+
+    // a recording can start in the middle of a game
+    let mut late = Replay::new(&game);
+    play(&mut game, &mut late, &mut rng, 100);
+    assert_eq!(600, late.start());
+    assert_eq!(game.state_hash(), late.play(700).unwrap().state_hash());
+}
+
+#[test]
+fn files() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Marathon,
+        vec![Controller::Keyboard; 2],
+        8,
+    )
+    .expect("keyboard controllers can't fail");
+    let mut replay = Replay::new(&game);
+    play(&mut game, &mut replay, &mut StdRng::seed_from_u64(4), 100);
+    let path = env::temp_dir().join("tetris-replay-test.json");
+    replay.save(&path).unwrap();
+    let loaded = Replay::load(&path).unwrap();
+    assert_eq!(
+        replay.play(100).unwrap().state_hash(),
+        loaded.play(100).unwrap().state_hash()
+    );
+
+    let mut old = replay.clone();
+    old.version = 0;
+    old.save(&path).unwrap();
+    let error = Replay::load(&path).err().unwrap();
+    assert!(error.contains("version 0"), "{}", error);
+    fs::write(&path, "not json").unwrap();
+    assert!(Replay::load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
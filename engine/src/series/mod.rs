@@ -0,0 +1,170 @@
+//! Match series: games between the same players until one has won enough of them.
+
+use crate::game_state::{Game, MAX_PLAYERS};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// How one game of a series ended
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Round {
+    /// The participant playing as each player of the game
+    pub seats: Vec<usize>,
+    /// The participant that won, `None` for a draw
+    pub winner: Option<usize>,
+    /// How long the game lasted
+    pub ticks: u32,
+    /// The score of every participant
+    pub scores: Vec<u32>,
+}
+
+/// Games between the same participants until one has won `first_to` of them.
+/// The participants keep their numbers when they swap sides, the players of a
+/// game are mapped to them with `seats`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Series {
+    names: Vec<String>,
+    first_to: u32,
+    swap_sides: bool,
+    rounds: Vec<Round>,
+}
+
+impl Series {
+    /// Starts a series between participants with the given names, with
+    /// `swap_sides` they move one player to the right every game
+    pub fn new(names: Vec<String>, first_to: u32, swap_sides: bool) -> Result<Series, String> {
+        if !(2..=MAX_PLAYERS).contains(&names.len()) {
+            return Err(format!(
+                "A series needs 2 to {} participants, found {}",
+                MAX_PLAYERS,
+                names.len()
+            ));
+        }
+        if first_to == 0 {
+            return Err("A series needs at least one win to be won".to_string());
+        }
+        Ok(Series {
+            names,
+            first_to,
+            swap_sides,
+            rounds: Vec::new(),
+        })
+    }
+
+    /// The names of the participants
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The wins needed to win the series
+    pub fn first_to(&self) -> u32 {
+        self.first_to
+    }
+
+    /// The games played so far
+    pub fn rounds(&self) -> &[Round] {
+        &self.rounds
+    }
+
+    /// The participant playing as each player of the next game
+    pub fn seats(&self) -> Vec<usize> {
+        let count = self.names.len();
+        let shift = if self.swap_sides {
+            self.rounds.len() % count
+        } else {
+            0
+        };
+        (0..count)
+            .map(|player| (player + count - shift) % count)
+            .collect()
+    }
+
+    /// Counts a finished game, played with the participants seated as `seats` says
+    pub fn record(&mut self, game: &Game) {
+        let seats = self.seats();
+        let mut scores = vec![0; self.names.len()];
+        for (player, score) in game.get_scores().into_iter().enumerate() {
+            scores[seats[player]] = score;
+        }
+        self.rounds.push(Round {
+            winner: game.get_winner().map(|player| seats[player]),
+            ticks: game.tick(),
+            scores,
+            seats,
+        });
+    }
+
+    /// The games won by every participant
+    pub fn wins(&self) -> Vec<u32> {
+        let mut wins = vec![0; self.names.len()];
+        for winner in self.rounds.iter().filter_map(|round| round.winner) {
+            wins[winner] += 1;
+        }
+        wins
+    }
+
+    /// The games no one won
+    pub fn draws(&self) -> u32 {
+        self.rounds
+            .iter()
+            .filter(|round| round.winner.is_none())
+            .count() as u32
+    }
+
+    /// The participant that won the series, once it's over
+    pub fn winner(&self) -> Option<usize> {
+        self.wins().iter().position(|wins| *wins >= self.first_to)
+    }
+
+    /// A row for every game, with the winner and the score of every participant
+    pub fn to_csv(&self) -> String {
+        let mut header = vec![
+            "round".to_string(),
+            "seats".to_string(),
+            "winner".to_string(),
+            "ticks".to_string(),
+        ];
+        header.extend(self.names.iter().map(|name| csv_field(name)));
+        let mut lines = vec![header.join(",")];
+        for (i, round) in self.rounds.iter().enumerate() {
+            let seats: Vec<String> = round
+                .seats
+                .iter()
+                .map(|seat| (seat + 1).to_string())
+                .collect();
+            let mut row = vec![
+                (i + 1).to_string(),
+                seats.join(" "),
+                round
+                    .winner
+                    .map_or("draw".to_string(), |winner| csv_field(&self.names[winner])),
+                round.ticks.to_string(),
+            ];
+            row.extend(round.scores.iter().map(u32::to_string));
+            lines.push(row.join(","));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Writes the series to a file, as csv if the path ends with `.csv` and as json otherwise
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = if path.extension().is_some_and(|extension| extension == "csv") {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        };
+        fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+}
+
+/// Quotes a csv field if it has to be
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
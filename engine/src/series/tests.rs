@@ -0,0 +1,94 @@
+use super::Series;
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Controller, Game, GameMode};
+
+fn names(count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("P{}", i)).collect()
+}
+
+/// A versus game played until everyone but `winner` has topped out by hard
+/// dropping every piece, with `None` they all drop until the game is over
+fn finished(players: usize, winner: Option<usize>) -> Game {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard; players],
+        0,
+    )
+    .expect("keyboard controllers can't fail");
+    while !game.is_over() {
+        for player in (0..players).filter(|player| Some(*player) != winner) {
+            if !game.get_losts()[player] {
+                game.perform(player, Action::HardDrop);
+            }
+        }
+        game.update();
+    }
+    game
+}
+
+#[test]
+fn first_to() {
+    assert!(Series::new(names(1), 2, false).is_err());
+    assert!(Series::new(names(2), 0, false).is_err());
+
+    let mut series = Series::new(names(2), 2, false).unwrap();
+    assert_eq!(vec![0, 1], series.seats());
+    series.record(&finished(2, Some(1)));
+    series.record(&finished(2, None));
+    assert_eq!(None, series.winner());
+    series.record(&finished(2, Some(0)));
+    series.record(&finished(2, Some(1)));
+    assert_eq!(vec![1, 2], series.wins());
+    assert_eq!(1, series.draws());
+    assert_eq!(Some(1), series.winner());
+    assert_eq!(vec![0, 1], series.rounds()[0].seats);
+}
+
+#[test]
+fn swap_sides() {
+    let mut series = Series::new(names(3), 3, true).unwrap();
+    assert_eq!(vec![0, 1, 2], series.seats());
+    series.record(&finished(3, Some(0)));
+    // everyone moves one player to the right
+    assert_eq!(vec![2, 0, 1], series.seats());
+    // the first player of the game is the third participant now
+    series.record(&finished(3, Some(0)));
+    assert_eq!(vec![1, 0, 1], series.wins());
+    assert_eq!(vec![1, 2, 0], series.seats());
+}
+
+#[test]
+fn export() {
+    let names = vec!["Keyboard".to_string(), "AI, v2".to_string()];
+    let mut series = Series::new(names, 1, true).unwrap();
+    series.record(&finished(2, Some(0)));
+    series.record(&finished(2, None));
+    let rounds = series.rounds();
+    let row = |i: usize, seats, winner| {
+        format!(
+            "{},{},{},{},{},{}\n",
+            i + 1,
+            seats,
+            winner,
+            rounds[i].ticks,
+            rounds[i].scores[0],
+            rounds[i].scores[1]
+        )
+    };
+    assert_eq!(
+        format!(
+            "round,seats,winner,ticks,Keyboard,\"AI, v2\"\n{}{}",
+            row(0, "1 2", "Keyboard"),
+            row(1, "2 1", "draw")
+        ),
+        series.to_csv()
+    );
+
+    let path = std::env::temp_dir().join("tetris-series-test.json");
+    series.save(&path).unwrap();
+    let saved: Series = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(series.rounds(), saved.rounds());
+    std::fs::remove_file(path).ok();
+}
@@ -0,0 +1,185 @@
+//! Streaming games to spectators over TCP.
+
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Game, GameMode, Snapshot};
+use crate::netplay::{Connection, Message, Transport, PROTOCOL_VERSION};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+
+#[cfg(test)]
+mod tests;
+
+/// Bytes queued for a spectator that isn't reading before it is dropped
+pub const MAX_BACKLOG: usize = 1 << 20;
+
+/// Everything a spectator needs to show a game it joins in the middle of
+#[derive(Serialize, Deserialize)]
+struct View {
+    mode: GameMode,
+    rules: Rules,
+    snapshot: Snapshot,
+}
+
+impl View {
+    fn of(game: &Game) -> View {
+        View {
+            mode: game.mode(),
+            rules: game.rules().clone(),
+            snapshot: game.snapshot(),
+        }
+    }
+
+    /// Creates the game, ai-scripts are replaced by the actions they performed
+    fn create_game(&self) -> Result<Game, String> {
+        Game::from_snapshot(&self.rules, self.mode, &self.snapshot)
+    }
+}
+
+/// Sends a running game to every spectator that connects to a port. Only the
+/// actions are streamed, a spectator joining late first gets a snapshot.
+pub struct Broadcast {
+    listener: TcpListener,
+    spectators: Vec<Connection>,
+    /// The game as the spectators see it, only played up to the confirmed ticks
+    view: Game,
+}
+
+impl Broadcast {
+    /// Starts listening for spectators of the game
+    pub fn new(port: u16, game: &Game) -> Result<Broadcast, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .map_err(|e| format!("Couldn't broadcast on port {}: {}", port, e))?;
+        Ok(Broadcast {
+            listener,
+            spectators: Vec::new(),
+            view: View::of(game)
+                .create_game()
+                .expect("a running game can be created again"),
+        })
+    }
+
+    /// Shows a new game to every spectator
+    pub fn restart(&mut self, game: &Game) {
+        self.view = View::of(game)
+            .create_game()
+            .expect("a running game can be created again");
+        let message = self.watch_message();
+        self.send_all(&message);
+    }
+
+    /// Accepts new spectators and sends the actions of every tick before `confirmed`,
+    /// later ticks might still be rolled back. `performed` are the actions of those
+    /// ticks, taken from the game with `Game::take_performed`.
+    pub fn update(&mut self, performed: &[(u32, usize, Action)], confirmed: u32) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Ok(spectator) = self.accept(stream) {
+                        self.spectators.push(spectator);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // a failed spectator doesn't stop the game
+                Err(_) => break,
+            }
+        }
+        let mut performed = performed.iter().copied().peekable();
+        for tick in self.view.tick()..confirmed {
+            let mut actions = Vec::new();
+            while let Some((_, player, action)) = performed.next_if(|(t, _, _)| *t == tick) {
+                actions.push((player, action));
+            }
+            for (player, action) in &actions {
+                self.view.perform(*player, *action);
+            }
+            self.view.update();
+            self.send_all(&Message::Frame { tick, actions });
+        }
+    }
+
+    /// Sends a message to every spectator, dropping the ones that left or
+    /// stopped reading so the host doesn't queue their frames forever
+    fn send_all(&mut self, message: &Message) {
+        self.spectators.retain_mut(|spectator| {
+            spectator.send(message).is_ok() && spectator.backlog() <= MAX_BACKLOG
+        });
+    }
+
+    fn accept(&self, stream: TcpStream) -> Result<Connection, String> {
+        stream
+            .set_nonblocking(false)
+            .map_err(|e| format!("Couldn't set up the connection: {}", e))?;
+        let mut spectator = Connection::new(stream)?.start()?;
+        spectator.send(&self.watch_message())?;
+        Ok(spectator)
+    }
+
+    fn watch_message(&self) -> Message {
+        Message::Watch {
+            version: PROTOCOL_VERSION,
+            view: serde_json::to_string(&View::of(&self.view))
+                .expect("a game can always be serialized"),
+        }
+    }
+}
+
+/// Watches a game broadcast by another instance, ignoring the local inputs
+pub struct Spectator {
+    connection: Connection,
+}
+
+impl Spectator {
+    /// Watches the broadcast on a connected stream
+    pub fn new(stream: TcpStream) -> Result<Spectator, String> {
+        Ok(Spectator {
+            connection: Connection::new(stream)?.start()?,
+        })
+    }
+
+    /// Plays the ticks that have arrived on the game, a new game replaces it
+    pub fn update(&mut self, game: &mut Option<Game>) -> Result<(), String> {
+        while let Some(message) = self.connection.receive()? {
+            match message {
+                Message::Watch { version, .. } if version != PROTOCOL_VERSION => {
+                    return Err(format!(
+                        "The game runs version {}, expected {}",
+                        version, PROTOCOL_VERSION
+                    ));
+                }
+                Message::Watch { view, .. } => {
+                    let view: View = serde_json::from_str(&view)
+                        .map_err(|e| format!("Received an invalid game: {}", e))?;
+                    view.rules.validate()?;
+                    *game = Some(view.create_game()?);
+                }
+                Message::Frame { tick, actions } => {
+                    let game = game.as_mut().ok_or("Received a tick before the game")?;
+                    if tick != game.tick() {
+                        return Err(format!(
+                            "Received tick {} while at tick {}",
+                            tick,
+                            game.tick()
+                        ));
+                    }
+                    for (player, action) in actions {
+                        if player >= game.player_count() {
+                            return Err("Received an invalid message".to_string());
+                        }
+                        game.perform(player, action);
+                    }
+                    game.update();
+                }
+                _ => return Err("Received an unexpected message".to_string()),
+            }
+        }
+        if self.connection.closed() {
+            return Err("The broadcast has ended".to_string());
+        }
+        Ok(())
+    }
+}
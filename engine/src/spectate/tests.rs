@@ -0,0 +1,171 @@
+use super::{Broadcast, Spectator, MAX_BACKLOG};
+use crate::game_data::{Action, Rules};
+use crate::game_state::{Controller, Game, GameMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Player 2 is the example ai-script where it is built, so its actions are streamed too
+fn controllers() -> Vec<Controller> {
+    let ai = PathBuf::from("../ai-example/unix/libai.so");
+    if cfg!(unix) && ai.exists() {
+        vec![Controller::Keyboard, Controller::AI(ai)]
+    } else {
+        vec![Controller::Keyboard, Controller::Keyboard]
+    }
+}
+
+/// Plays random actions for player 1 and broadcasts every tick
+fn play(game: &mut Game, broadcast: &mut Broadcast, rng: &mut StdRng, ticks: u32) {
+    for _ in 0..ticks {
+        if rng.gen_bool(0.2) {
+            game.perform(0, Action::ALL[rng.gen_range(0, Action::ALL.len())]);
+        }
+        game.update();
+        let tick = game.tick();
+        broadcast.update(&game.take_performed(tick), tick);
+    }
+}
+
+/// Updates the spectator until it has caught up with the game
+fn catch_up(spectator: &mut Spectator, watched: &mut Option<Game>, game: &Game) {
+    for _ in 0..1000 {
+        spectator
+            .update(watched)
+            .expect("the spectator should keep up");
+        if watched.as_ref().map(Game::tick) == Some(game.tick()) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("the spectator didn't catch up");
+}
+
+fn connect(broadcast: &mut Broadcast, game: &mut Game) -> Spectator {
+    let stream =
+        TcpStream::connect(("127.0.0.1", broadcast.listener.local_addr().unwrap().port())).unwrap();
+    let spectators = broadcast.spectators.len();
+    // the broadcast accepts spectators when it is updated
+    for _ in 0..1000 {
+        let tick = game.tick();
+        broadcast.update(&game.take_performed(tick), tick);
+        if broadcast.spectators.len() > spectators {
+            return Spectator::new(stream).unwrap();
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("the spectator wasn't accepted");
+}
+
+#[test]
+fn late_join() {
+    let mut game = Game::new(&Rules::default(), 5, GameMode::Versus, controllers(), 11)
+        .expect("the example ai should load");
+    let mut broadcast = Broadcast::new(0, &game).unwrap();
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut first = connect(&mut broadcast, &mut game);
+    let mut first_game = None;
+    play(&mut game, &mut broadcast, &mut rng, 200);
+    // the second spectator starts from a snapshot of tick 200
+    let mut second = connect(&mut broadcast, &mut game);
+    let mut second_game = None;
+    play(&mut game, &mut broadcast, &mut rng, 400);
+
+    catch_up(&mut first, &mut first_game, &game);
+    catch_up(&mut second, &mut second_game, &game);
+    assert_eq!(game.state_hash(), first_game.as_ref().unwrap().state_hash());
+    assert_eq!(
+        game.state_hash(),
+        second_game.as_ref().unwrap().state_hash()
+    );
+
+    // a new game replaces the watched one
+    game.restart(3, 12);
+    broadcast.restart(&game);
+    play(&mut game, &mut broadcast, &mut rng, 100);
+    catch_up(&mut first, &mut first_game, &game);
+    assert_eq!(game.state_hash(), first_game.as_ref().unwrap().state_hash());
+}
+
+#[test]
+fn unconfirmed_ticks() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        4,
+    )
+    .unwrap();
+    let mut broadcast = Broadcast::new(0, &game).unwrap();
+    let mut spectator = connect(&mut broadcast, &mut game);
+    let mut watched = None;
+    // the game runs ahead and is rolled back, the spectator only sees the final ticks
+    let snapshot = game.snapshot();
+    for _ in 0..10 {
+        game.perform(1, Action::HardDrop);
+        game.update();
+    }
+    broadcast.update(&game.take_performed(0), 0);
+    game.restore(&snapshot);
+    for _ in 0..10 {
+        game.perform(0, Action::MoveLeft);
+        game.update();
+    }
+    broadcast.update(&game.take_performed(10), 10);
+    catch_up(&mut spectator, &mut watched, &game);
+    assert_eq!(game.state_hash(), watched.unwrap().state_hash());
+}
+
+#[test]
+fn broadcast_ends() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Marathon,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        4,
+    )
+    .unwrap();
+    let mut broadcast = Broadcast::new(0, &game).unwrap();
+    let mut spectator = connect(&mut broadcast, &mut game);
+    let mut watched = None;
+    catch_up(&mut spectator, &mut watched, &game);
+    assert_eq!(Some(GameMode::Marathon), watched.as_ref().map(Game::mode));
+    drop(broadcast);
+    let mut result = Ok(());
+    for _ in 0..1000 {
+        result = spectator.update(&mut watched);
+        if result.is_err() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(Err("The broadcast has ended".to_string()), result);
+}
+
+#[test]
+fn stalled_spectator() {
+    let mut game = Game::new(
+        &Rules::default(),
+        5,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        5,
+    )
+    .unwrap();
+    let mut broadcast = Broadcast::new(0, &game).unwrap();
+    // a spectator that never reads is dropped once its frames pile up
+    let _stalled = connect(&mut broadcast, &mut game);
+    // every restart sends the whole game, at least a few hundred bytes
+    for _ in 0..MAX_BACKLOG {
+        broadcast.restart(&game);
+        if broadcast.spectators.is_empty() {
+            return;
+        }
+    }
+    panic!("the stalled spectator wasn't dropped");
+}
@@ -0,0 +1,187 @@
+//! Statistics of the players of a game, computed from its events.
+
+use crate::game_data::{finesse_inputs, Action, ClearKind, GameEvent, TICK_RATE};
+use crate::game_state::Game;
+
+#[cfg(test)]
+mod tests;
+
+/// What a player has done so far in a game
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerStats {
+    /// Pieces placed
+    pub pieces: u32,
+    /// Lines cleared
+    pub lines: u32,
+    /// Singles, doubles, triples and tetrises cleared
+    pub clears: [u32; 4],
+    /// Lines of garbage sent
+    pub attack: u32,
+    /// Lines of garbage that rose on the board
+    pub garbage_received: u32,
+    /// The current level
+    pub level: usize,
+    /// The longest combo
+    pub max_combo: u32,
+    /// Tetrises in a row, not counting the first one
+    pub back_to_back: u32,
+    /// Pieces placed with more moves and rotations than needed
+    pub finesse_faults: u32,
+    /// Ticks played since the stats started, until the player topped out
+    pub ticks: u32,
+    /// Pieces in a row that cleared lines
+    clears_in_row: u32,
+    /// Set if the last line clear was a tetris
+    last_tetris: bool,
+    /// Ticks of the moves and rotations pressed for the falling piece
+    inputs: Vec<u32>,
+    lost: bool,
+}
+
+impl PlayerStats {
+    /// Pieces in a row that cleared lines, not counting the first one
+    pub fn combo(&self) -> u32 {
+        self.clears_in_row.saturating_sub(1)
+    }
+
+    /// Pieces placed per second played
+    pub fn pieces_per_second(&self) -> f32 {
+        per_second(self.pieces, self.ticks)
+    }
+
+    /// Lines of garbage sent per minute played
+    pub fn attack_per_minute(&self) -> f32 {
+        60.0 * per_second(self.attack, self.ticks)
+    }
+
+    /// Removes the inputs pressed for the piece that left on `tick` and returns
+    /// how many there were
+    fn take_inputs(&mut self, tick: u32) -> u32 {
+        let before = self.inputs.len();
+        self.inputs.retain(|input| *input > tick);
+        (before - self.inputs.len()) as u32
+    }
+}
+
+fn per_second(amount: u32, ticks: u32) -> f32 {
+    if ticks == 0 {
+        0.0
+    } else {
+        amount as f32 * TICK_RATE as f32 / ticks as f32
+    }
+}
+
+/// The stats of every player of a game. Frontends pass it the events they take
+/// from the game and the inputs pressed by the players, ai-scripts don't press
+/// anything so they never get finesse faults.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    players: Vec<PlayerStats>,
+    /// The tick the stats started counting on
+    start: u32,
+    /// The tick the stats are up to
+    tick: u32,
+}
+
+impl Stats {
+    /// Starts counting from the current tick of a game
+    pub fn new(game: &Game) -> Stats {
+        let players = game
+            .get_levels()
+            .into_iter()
+            .zip(game.get_losts())
+            .map(|(level, lost)| PlayerStats {
+                level,
+                lost,
+                ..PlayerStats::default()
+            })
+            .collect();
+        Stats {
+            players,
+            start: game.tick(),
+            tick: game.tick(),
+        }
+    }
+
+    /// The tick the stats are up to, the events before it have been counted
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The stats of every player
+    pub fn players(&self) -> &[PlayerStats] {
+        &self.players
+    }
+
+    /// Counts a key press of a player, `tick` is the one the action is performed on
+    pub fn input(&mut self, tick: u32, player: usize, action: Action) {
+        let turns_or_moves = matches!(
+            action,
+            Action::MoveLeft
+                | Action::MoveRight
+                | Action::RotateClockwise
+                | Action::RotateCounterClockwise
+        );
+        if let Some(stats) = self.players.get_mut(player) {
+            if turns_or_moves {
+                stats.inputs.push(tick);
+            }
+        }
+    }
+
+    /// Counts the events of the ticks before `before`, taken with `Game::take_events`
+    pub fn update(&mut self, before: u32, events: &[(u32, usize, GameEvent)]) {
+        for (i, &(tick, player, event)) in events.iter().enumerate() {
+            let stats = match self.players.get_mut(player) {
+                Some(stats) => stats,
+                None => continue,
+            };
+            match event {
+                GameEvent::PieceLocked { blocks } => {
+                    stats.pieces += 1;
+                    // the clear of a piece comes right after it is placed
+                    let clears = matches!(
+                        events.get(i + 1),
+                        Some(&(t, p, GameEvent::LinesCleared { .. })) if t == tick && p == player
+                    );
+                    if !clears {
+                        stats.clears_in_row = 0;
+                    }
+                    let inputs = stats.take_inputs(tick);
+                    if finesse_inputs(&blocks).is_some_and(|fewest| inputs > fewest) {
+                        stats.finesse_faults += 1;
+                    }
+                }
+                GameEvent::LinesCleared { kind, rows } => {
+                    stats.lines += rows.count_ones();
+                    stats.clears[kind as usize] += 1;
+                    stats.clears_in_row += 1;
+                    stats.max_combo = stats.max_combo.max(stats.combo());
+                    let tetris = kind == ClearKind::Tetris;
+                    stats.back_to_back = if tetris && stats.last_tetris {
+                        stats.back_to_back + 1
+                    } else {
+                        0
+                    };
+                    stats.last_tetris = tetris;
+                }
+                GameEvent::GarbageSent { lines } => stats.attack += lines as u32,
+                GameEvent::GarbageReceived { lines } => stats.garbage_received += lines as u32,
+                GameEvent::Hold => {
+                    stats.take_inputs(tick);
+                }
+                GameEvent::LevelUp { level } => stats.level = level,
+                GameEvent::TopOut => {
+                    stats.lost = true;
+                    stats.ticks = (tick + 1).saturating_sub(self.start);
+                }
+            }
+        }
+        for stats in &mut self.players {
+            if !stats.lost {
+                stats.ticks = before - self.start;
+            }
+        }
+        self.tick = before;
+    }
+}
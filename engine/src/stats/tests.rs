@@ -0,0 +1,112 @@
+use super::Stats;
+use crate::game_data::{Action, ClearKind, GameEvent, Rules, TICK_RATE};
+use crate::game_state::{Controller, Game, GameMode};
+
+/// A t piece placed one column right of where it spawns, at the bottom
+const T_RIGHT: [[i32; 2]; 4] = [[5, 1], [6, 1], [6, 0], [7, 1]];
+
+fn new_stats() -> Stats {
+    let game = Game::new(
+        &Rules::default(),
+        3,
+        GameMode::Versus,
+        vec![Controller::Keyboard; 2],
+        0,
+    )
+    .expect("keyboard controllers can't fail");
+    Stats::new(&game)
+}
+
+fn lock(tick: u32, lines: Option<ClearKind>) -> Vec<(u32, usize, GameEvent)> {
+    let mut events = vec![(tick, 0, GameEvent::PieceLocked { blocks: T_RIGHT })];
+    if let Some(kind) = lines {
+        let rows = match kind {
+            ClearKind::Single => 0b1,
+            ClearKind::Double => 0b11,
+            ClearKind::Triple => 0b111,
+            ClearKind::Tetris => 0b1111,
+        };
+        events.push((tick, 0, GameEvent::LinesCleared { kind, rows }));
+    }
+    events
+}
+
+#[test]
+fn clears() {
+    let mut stats = new_stats();
+    assert_eq!(3, stats.players()[1].level);
+    stats.update(10, &lock(5, None));
+    stats.update(20, &lock(15, Some(ClearKind::Single)));
+    stats.update(30, &lock(25, Some(ClearKind::Tetris)));
+    let mut events = lock(35, Some(ClearKind::Tetris));
+    events.push((35, 0, GameEvent::GarbageSent { lines: 4 }));
+    events.push((36, 1, GameEvent::GarbageReceived { lines: 4 }));
+    events.push((37, 0, GameEvent::LevelUp { level: 4 }));
+    stats.update(40, &events);
+
+    let player = &stats.players()[0];
+    assert_eq!((4, 9), (player.pieces, player.lines));
+    assert_eq!((2, 1), (player.combo(), player.back_to_back));
+    assert_eq!([1, 0, 0, 2], player.clears);
+    assert_eq!((4, 4), (player.attack, player.level));
+    assert_eq!(4, stats.players()[1].garbage_received);
+    assert_eq!(40, stats.tick());
+
+    // a piece clearing nothing ends the combo but not the back to back
+    stats.update(50, &lock(45, None));
+    assert_eq!(
+        (0, 1),
+        (stats.players()[0].combo(), stats.players()[0].back_to_back)
+    );
+    stats.update(60, &lock(55, Some(ClearKind::Double)));
+    assert_eq!(0, stats.players()[0].back_to_back);
+    assert_eq!(2, stats.players()[0].max_combo);
+}
+
+#[test]
+fn rates() {
+    let mut stats = new_stats();
+    for second in 0..10 {
+        let tick = second * TICK_RATE;
+        let mut events = lock(tick, Some(ClearKind::Double));
+        events.push((tick, 0, GameEvent::GarbageSent { lines: 1 }));
+        stats.update(tick + TICK_RATE, &events);
+    }
+    stats.update(
+        20 * TICK_RATE,
+        &[(15 * TICK_RATE - 1, 1, GameEvent::TopOut)],
+    );
+    let player = &stats.players()[0];
+    assert_eq!(20 * TICK_RATE, player.ticks);
+    assert_eq!(0.5, player.pieces_per_second());
+    assert_eq!(30.0, player.attack_per_minute());
+    // the clock of a player stops when they top out
+    assert_eq!(15 * TICK_RATE, stats.players()[1].ticks);
+}
+
+#[test]
+fn finesse_faults() {
+    let mut stats = new_stats();
+    // one tap is enough to move the t piece a column
+    stats.input(3, 0, Action::MoveRight);
+    stats.input(4, 0, Action::HardDrop);
+    stats.update(10, &lock(5, None));
+    assert_eq!(0, stats.players()[0].finesse_faults);
+
+    stats.input(12, 0, Action::MoveRight);
+    stats.input(13, 0, Action::MoveLeft);
+    stats.input(14, 0, Action::MoveRight);
+    // pressed for the next piece
+    stats.input(16, 0, Action::RotateClockwise);
+    stats.update(20, &lock(15, None));
+    assert_eq!(1, stats.players()[0].finesse_faults);
+
+    // holding starts over with the new piece
+    stats.update(22, &[(21, 0, GameEvent::Hold)]);
+    stats.input(22, 0, Action::MoveRight);
+    stats.update(30, &lock(25, None));
+    assert_eq!(1, stats.players()[0].finesse_faults);
+    // nothing is counted for players without inputs
+    stats.update(40, &[(35, 1, GameEvent::PieceLocked { blocks: T_RIGHT })]);
+    assert_eq!(0, stats.players()[1].finesse_faults);
+}
@@ -0,0 +1,451 @@
+//! Tournaments between controllers: seeded versus games played in parallel,
+//! with Elo and Glicko ratings and the results of every pairing.
+
+use crate::game_data::Rules;
+use crate::game_state::{Controller, Game, GameMode};
+use crate::series::csv_field;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[cfg(test)]
+mod tests;
+
+/// The rating everyone starts with, in both systems
+pub const INITIAL_RATING: f64 = 1500.0;
+/// The rating deviation of a new Glicko rating
+pub const INITIAL_DEVIATION: f64 = 350.0;
+/// How far an Elo rating moves after a single game
+pub const ELO_K: f64 = 16.0;
+
+/// Who plays whom
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Pairing {
+    /// Everyone plays everyone once
+    RoundRobin,
+    /// The given number of rounds, each pairing entrants with similar points
+    /// that haven't played each other yet
+    Swiss(usize),
+}
+
+/// How a tournament is played
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    /// Who plays whom
+    pub pairing: Pairing,
+    /// Games played by every pairing, the entrants take turns being player 1
+    pub games: u32,
+    /// The seed every game seed is drawn from, the same seed gives the same tournament
+    pub seed: u64,
+    /// The level every game starts on
+    pub init_level: usize,
+    /// Games still going after this many ticks are draws
+    pub max_ticks: u32,
+    /// Games played at the same time
+    pub threads: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            pairing: Pairing::RoundRobin,
+            games: 10,
+            seed: 0,
+            init_level: 5,
+            max_ticks: 36000,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+}
+
+/// One game of a tournament
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    /// The round the game was played in, counting from 0
+    pub round: usize,
+    /// The entrant playing as player 1
+    pub first: usize,
+    /// The entrant playing as player 2
+    pub second: usize,
+    /// The seed the game was played with
+    pub seed: u64,
+    /// The entrant that won, `None` for a draw
+    pub winner: Option<usize>,
+    /// How long the game lasted
+    pub ticks: u32,
+}
+
+/// The results and ratings of an entrant
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    /// The name the entrant was given
+    pub name: String,
+    /// Games won
+    pub wins: u32,
+    /// Games no one won
+    pub draws: u32,
+    /// Games lost
+    pub losses: u32,
+    /// The Elo rating
+    pub elo: f64,
+    /// The Glicko rating
+    pub glicko: f64,
+    /// How uncertain the Glicko rating is
+    pub deviation: f64,
+}
+
+impl Standing {
+    fn new(name: String) -> Standing {
+        Standing {
+            name,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            elo: INITIAL_RATING,
+            glicko: INITIAL_RATING,
+            deviation: INITIAL_DEVIATION,
+        }
+    }
+
+    /// A point for every win and half a point for every draw
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+/// A played tournament
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tournament {
+    /// How the tournament was played
+    pub settings: Settings,
+    /// Every entrant in the order they were given
+    pub standings: Vec<Standing>,
+    /// The wins, draws and losses of the row's entrant against the column's
+    pub matrix: Vec<Vec<[u32; 3]>>,
+    /// Every game played, in the order of the rounds
+    pub games: Vec<Outcome>,
+}
+
+impl Tournament {
+    /// Plays a tournament between the named entrants. `play` plays one game
+    /// between two entrants with a seed and returns the winning player and the
+    /// ticks played, it is called from `settings.threads` threads at once.
+    pub fn run<F>(names: Vec<String>, settings: &Settings, play: F) -> Result<Tournament, String>
+    where
+        F: Fn(usize, usize, u64) -> Result<(Option<usize>, u32), String> + Sync,
+    {
+        if names.len() < 2 {
+            return Err(format!(
+                "A tournament needs at least 2 entrants, found {}",
+                names.len()
+            ));
+        }
+        let entrants = names.len();
+        let rounds = match settings.pairing {
+            Pairing::RoundRobin => round_robin(entrants),
+            Pairing::Swiss(rounds) => vec![Vec::new(); rounds],
+        };
+        let mut tournament = Tournament {
+            settings: settings.clone(),
+            standings: names.into_iter().map(Standing::new).collect(),
+            matrix: vec![vec![[0; 3]; entrants]; entrants],
+            games: Vec::new(),
+        };
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        for (round, pairs) in rounds.into_iter().enumerate() {
+            let pairs = match settings.pairing {
+                Pairing::RoundRobin => pairs,
+                Pairing::Swiss(_) => tournament.swiss_round(),
+            };
+            let mut jobs = Vec::new();
+            for &(a, b) in &pairs {
+                for game in 0..settings.games {
+                    let (first, second) = if game % 2 == 0 { (a, b) } else { (b, a) };
+                    jobs.push((first, second, rng.gen()));
+                }
+            }
+            let results = play_parallel(&jobs, settings.threads, &play);
+            let mut outcomes = Vec::new();
+            for (&(first, second, seed), result) in jobs.iter().zip(results) {
+                let (winner, ticks) = result?;
+                outcomes.push(Outcome {
+                    round,
+                    first,
+                    second,
+                    seed,
+                    winner: winner.map(|player| if player == 0 { first } else { second }),
+                    ticks,
+                });
+            }
+            tournament.rate(&outcomes);
+            tournament.games.extend(outcomes);
+        }
+        Ok(tournament)
+    }
+
+    /// The entrants from the highest Elo rating to the lowest
+    pub fn leaderboard(&self) -> Vec<&Standing> {
+        let mut leaderboard: Vec<&Standing> = self.standings.iter().collect();
+        leaderboard.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+        leaderboard
+    }
+
+    /// The leaderboard as csv, a row for every entrant
+    pub fn leaderboard_csv(&self) -> String {
+        let mut lines = vec!["rank,name,elo,glicko,deviation,wins,draws,losses,points".to_string()];
+        for (rank, standing) in self.leaderboard().into_iter().enumerate() {
+            lines.push(format!(
+                "{},{},{:.0},{:.0},{:.0},{},{},{},{}",
+                rank + 1,
+                csv_field(&standing.name),
+                standing.elo,
+                standing.glicko,
+                standing.deviation,
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.points()
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// The results of every pairing as csv, wins-draws-losses of the row's
+    /// entrant against the column's
+    pub fn matrix_csv(&self) -> String {
+        let names: Vec<String> = self
+            .standings
+            .iter()
+            .map(|standing| csv_field(&standing.name))
+            .collect();
+        let mut lines = vec![format!(",{}", names.join(","))];
+        for (name, row) in names.iter().zip(&self.matrix) {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|&[wins, draws, losses]| {
+                    if wins + draws + losses == 0 {
+                        String::new()
+                    } else {
+                        format!("{}-{}-{}", wins, draws, losses)
+                    }
+                })
+                .collect();
+            lines.push(format!("{},{}", name, cells.join(",")));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Writes `leaderboard.csv`, `matrix.csv` and everything as `tournament.json`
+    /// to a directory, creating it if needed
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let write = |name: &str, text: String| {
+            let path = dir.join(name);
+            fs::write(&path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+        };
+        fs::create_dir_all(dir).map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+        write("leaderboard.csv", self.leaderboard_csv())?;
+        write("matrix.csv", self.matrix_csv())?;
+        write(
+            "tournament.json",
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?,
+        )
+    }
+
+    /// Pairs the entrants from the most points to the fewest, everyone with
+    /// the next one they haven't played yet if there is one. With an odd
+    /// number of entrants the last one sits the round out.
+    fn swiss_round(&self) -> Vec<(usize, usize)> {
+        let mut order: Vec<usize> = (0..self.standings.len()).collect();
+        order.sort_by(|a, b| {
+            self.standings[*b]
+                .points()
+                .total_cmp(&self.standings[*a].points())
+        });
+        let mut pairs = Vec::new();
+        while order.len() > 1 {
+            let a = order.remove(0);
+            let played = |b: &usize| self.matrix[a][*b].iter().sum::<u32>() > 0;
+            let b = order.iter().position(|b| !played(b)).unwrap_or(0);
+            pairs.push((a, order.remove(b)));
+        }
+        pairs
+    }
+
+    /// Counts the games of a round, Elo is updated after every game and Glicko
+    /// once with the whole round
+    fn rate(&mut self, outcomes: &[Outcome]) {
+        let mut results = Vec::new();
+        for outcome in outcomes {
+            let (a, b) = (outcome.first, outcome.second);
+            let score = match outcome.winner {
+                Some(winner) if winner == a => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            let column = match outcome.winner {
+                Some(winner) if winner == a => 0,
+                Some(_) => 2,
+                None => 1,
+            };
+            self.matrix[a][b][column] += 1;
+            self.matrix[b][a][2 - column] += 1;
+            for (entrant, column) in [(a, column), (b, 2 - column)] {
+                let standing = &mut self.standings[entrant];
+                match column {
+                    0 => standing.wins += 1,
+                    1 => standing.draws += 1,
+                    _ => standing.losses += 1,
+                }
+            }
+            let (elo_a, elo_b) = elo(self.standings[a].elo, self.standings[b].elo, score);
+            self.standings[a].elo = elo_a;
+            self.standings[b].elo = elo_b;
+            results.push((a, b, score));
+        }
+        let ratings: Vec<(f64, f64)> = self
+            .standings
+            .iter()
+            .map(|standing| (standing.glicko, standing.deviation))
+            .collect();
+        for (standing, (rating, deviation)) in self
+            .standings
+            .iter_mut()
+            .zip(glicko_period(&ratings, &results))
+        {
+            standing.glicko = rating;
+            standing.deviation = deviation;
+        }
+    }
+}
+
+/// Plays the games on up to `threads` threads, the results are in the order of the games
+pub(crate) fn play_parallel<T, F>(
+    jobs: &[(usize, usize, u64)],
+    threads: usize,
+    play: &F,
+) -> Vec<Result<T, String>>
+where
+    T: Send,
+    F: Fn(usize, usize, u64) -> Result<T, String> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                // every thread takes the next game no one has started yet
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let (first, second, seed) = match jobs.get(i) {
+                    Some(job) => *job,
+                    None => break,
+                };
+                let result = play(first, second, seed);
+                results.lock().expect("a game panicked")[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .expect("a game panicked")
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err("A game wasn't played".to_string())))
+        .collect()
+}
+
+/// Plays a versus game between two controllers until someone wins or `max_ticks`
+/// have passed, returns the winning player and the ticks played
+pub fn play_game(
+    first: &Controller,
+    second: &Controller,
+    seed: u64,
+    settings: &Settings,
+) -> Result<(Option<usize>, u32), String> {
+    let mut game = Game::new(
+        &Rules::default(),
+        settings.init_level,
+        GameMode::Versus,
+        vec![first.clone(), second.clone()],
+        seed,
+    )?;
+    while !game.is_over() && game.tick() < settings.max_ticks {
+        game.update();
+    }
+    let winner = if game.is_over() {
+        game.get_winner()
+    } else {
+        None
+    };
+    Ok((winner, game.tick()))
+}
+
+/// The rounds of a round robin, every round has everyone play once, except
+/// for one entrant sitting out if there is an odd number of them
+pub fn round_robin(entrants: usize) -> Vec<Vec<(usize, usize)>> {
+    // the circle method: one entrant stays in place, the others rotate around it
+    let seats = entrants + entrants % 2;
+    let mut circle: Vec<usize> = (0..seats).collect();
+    let mut rounds = Vec::new();
+    for _ in 1..seats {
+        let round = (0..seats / 2)
+            .map(|i| (circle[i], circle[seats - 1 - i]))
+            .filter(|&(a, b)| a < entrants && b < entrants)
+            .collect();
+        rounds.push(round);
+        circle[1..].rotate_right(1);
+    }
+    rounds
+}
+
+/// The Elo ratings of two entrants after a game, `score` is 1 if the first
+/// won, 0.5 for a draw and 0 if the second won
+pub fn elo(first: f64, second: f64, score: f64) -> (f64, f64) {
+    let expected = 1.0 / (1.0 + 10f64.powf((second - first) / 400.0));
+    let change = ELO_K * (score - expected);
+    (first + change, second - change)
+}
+
+/// The Glicko ratings and deviations of everyone after a rating period with
+/// the given games, each with the two entrants and the score of the first
+pub fn glicko_period(ratings: &[(f64, f64)], games: &[(usize, usize, f64)]) -> Vec<(f64, f64)> {
+    let q = 10f64.ln() / 400.0;
+    let g = |deviation: f64| 1.0 / (1.0 + 3.0 * (q * deviation / PI).powi(2)).sqrt();
+    ratings
+        .iter()
+        .enumerate()
+        .map(|(entrant, &(rating, deviation))| {
+            // the opponent and the score of every game the entrant played
+            let played = games.iter().filter_map(|&(a, b, score)| {
+                if a == entrant {
+                    Some((b, score))
+                } else if b == entrant {
+                    Some((a, 1.0 - score))
+                } else {
+                    None
+                }
+            });
+            let mut variance_sum = 0.0;
+            let mut change_sum = 0.0;
+            for (opponent, score) in played {
+                let (other, other_deviation) = ratings[opponent];
+                let g = g(other_deviation);
+                let expected = 1.0 / (1.0 + 10f64.powf(-g * (rating - other) / 400.0));
+                variance_sum += g * g * expected * (1.0 - expected);
+                change_sum += g * (score - expected);
+            }
+            if variance_sum == 0.0 {
+                return (rating, deviation);
+            }
+            let precision = 1.0 / deviation.powi(2) + q * q * variance_sum;
+            (
+                rating + q / precision * change_sum,
+                (1.0 / precision).sqrt(),
+            )
+        })
+        .collect()
+}
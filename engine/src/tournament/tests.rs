@@ -0,0 +1,114 @@
+use super::{elo, glicko_period, round_robin, Pairing, Settings, Tournament};
+
+fn names(count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("AI {}", i)).collect()
+}
+
+/// The entrant with the lower number always wins, and 0 and 1 always draw
+fn ranked(first: usize, second: usize, _seed: u64) -> Result<(Option<usize>, u32), String> {
+    let winner = match (first.min(second), first.max(second)) {
+        (0, 1) => None,
+        _ if first < second => Some(0),
+        _ => Some(1),
+    };
+    Ok((winner, 100))
+}
+
+#[test]
+fn round_robin_pairings() {
+    for entrants in 2..=7 {
+        let rounds = round_robin(entrants);
+        let mut met = vec![vec![0; entrants]; entrants];
+        for round in &rounds {
+            let mut playing = vec![false; entrants];
+            for &(a, b) in round {
+                assert!(!playing[a] && !playing[b]);
+                playing[a] = true;
+                playing[b] = true;
+                met[a][b] += 1;
+                met[b][a] += 1;
+            }
+        }
+        for (a, row) in met.iter().enumerate() {
+            for (b, times) in row.iter().enumerate() {
+                assert_eq!(if a == b { 0 } else { 1 }, *times);
+            }
+        }
+    }
+}
+
+#[test]
+fn ratings() {
+    assert_eq!((1508.0, 1492.0), elo(1500.0, 1500.0, 1.0));
+    assert_eq!((1500.0, 1500.0), elo(1500.0, 1500.0, 0.5));
+
+    // the example from Glickman's description of the Glicko system
+    let ratings = [
+        (1500.0, 200.0),
+        (1400.0, 30.0),
+        (1550.0, 100.0),
+        (1700.0, 300.0),
+    ];
+    let games = [(0, 1, 1.0), (2, 0, 1.0), (0, 3, 0.0)];
+    let (rating, deviation) = glicko_period(&ratings, &games)[0];
+    assert!((rating - 1464.1).abs() < 0.1, "{}", rating);
+    assert!((deviation - 151.4).abs() < 0.1, "{}", deviation);
+}
+
+#[test]
+fn round_robin_tournament() {
+    let settings = Settings {
+        games: 4,
+        threads: 3,
+        ..Settings::default()
+    };
+    let tournament = Tournament::run(names(4), &settings, ranked).unwrap();
+    assert_eq!(6 * 4, tournament.games.len());
+    let leaderboard: Vec<&str> = tournament
+        .leaderboard()
+        .iter()
+        .map(|standing| standing.name.as_str())
+        .collect();
+    assert_eq!(vec!["AI 1", "AI 2", "AI 3", "AI 4"], leaderboard);
+    assert_eq!([8, 4, 0], {
+        let first = &tournament.standings[0];
+        [first.wins, first.draws, first.losses]
+    });
+    assert_eq!([0, 4, 0], tournament.matrix[1][0]);
+    assert_eq!([4, 0, 0], tournament.matrix[2][3]);
+    assert!(tournament
+        .matrix_csv()
+        .starts_with(",AI 1,AI 2,AI 3,AI 4\nAI 1,,0-4-0,4-0-0,4-0-0\n"));
+    assert!(tournament
+        .leaderboard_csv()
+        .starts_with("rank,name,elo,glicko,deviation,wins,draws,losses,points\n1,AI 1,"));
+
+    // the same seed gives the same games no matter how many threads play them
+    let alone = Settings {
+        threads: 1,
+        ..settings
+    };
+    assert_eq!(
+        tournament.games,
+        Tournament::run(names(4), &alone, ranked).unwrap().games
+    );
+}
+
+#[test]
+fn swiss_tournament() {
+    let settings = Settings {
+        pairing: Pairing::Swiss(3),
+        games: 2,
+        ..Settings::default()
+    };
+    let tournament = Tournament::run(names(5), &settings, ranked).unwrap();
+    // two pairings a round, the fifth entrant sits out
+    assert_eq!(3 * 2 * 2, tournament.games.len());
+    // no one plays the same entrant twice while there are others left
+    for (a, row) in tournament.matrix.iter().enumerate() {
+        for (b, results) in row.iter().enumerate() {
+            assert!(results.iter().sum::<u32>() <= 2, "{} and {}", a, b);
+        }
+    }
+    assert!(Tournament::run(names(1), &settings, ranked).is_err());
+}
@@ -0,0 +1,287 @@
+//! Evolves the weights of the heuristic bot with a genetic algorithm. Every
+//! generation plays its games on several threads and can be saved as a
+//! checkpoint to carry on from later.
+
+use crate::bots::{Bot, Weights, EL_TETRIS};
+use crate::game_data::Rules;
+use crate::game_state::{Controller, Game, GameMode};
+use crate::tournament::{self, play_game, play_parallel};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+#[cfg(test)]
+mod tests;
+
+/// Parents a child is picked from, the fittest of them is one of its parents
+const SELECTION_SIZE: usize = 3;
+
+/// How the weights of a generation are compared
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Fitness {
+    /// The score of marathon games played alone
+    Solo,
+    /// The share of versus games won against the best weights of the
+    /// generation before, or the default weights in the first generation
+    Versus,
+}
+
+/// How the weights are trained
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// How the weights are compared
+    pub fitness: Fitness,
+    /// Weights in every generation
+    pub population: usize,
+    /// The best weights of a generation that are kept as they are
+    pub elite: usize,
+    /// Games played by every weights each generation
+    pub games: u32,
+    /// How much a weight can change when a child is mutated
+    pub mutation: f64,
+    /// The seed every game seed and random choice is drawn from
+    pub seed: u64,
+    /// The level every game starts on
+    pub init_level: usize,
+    /// Games still going after this many ticks are stopped, versus games are draws
+    pub max_ticks: u32,
+    /// Games played at the same time
+    pub threads: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            fitness: Fitness::Solo,
+            population: 16,
+            elite: 4,
+            games: 4,
+            mutation: 0.2,
+            seed: 0,
+            init_level: 5,
+            max_ticks: 18000,
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+}
+
+/// Weights and how well they did in a generation
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Individual {
+    /// The weights the heuristic bot played with
+    pub weights: Weights,
+    /// The average score in solo games, or the share of versus games won
+    pub fitness: f64,
+}
+
+/// A training run, saved whole as a checkpoint
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trainer {
+    /// How the weights are trained
+    pub settings: Settings,
+    /// The weights the next generation plays with
+    pub population: Vec<Weights>,
+    /// The best weights of every generation played so far
+    pub history: Vec<Individual>,
+}
+
+impl Trainer {
+    /// Starts a training run from random weights
+    pub fn new(settings: Settings) -> Trainer {
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let population = (0..settings.population.max(1))
+            .map(|_| {
+                let mut weights = [0.0; 6];
+                for weight in &mut weights {
+                    *weight = rng.gen_range(-1.0, 1.0);
+                }
+                from_array(normalize(weights))
+            })
+            .collect();
+        Trainer {
+            settings,
+            population,
+            history: Vec::new(),
+        }
+    }
+
+    /// Generations played so far
+    pub fn generation(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The best weights of the last generation played
+    pub fn best(&self) -> Option<&Individual> {
+        self.history.last()
+    }
+
+    /// Plays the games of a generation, then breeds the next one from the
+    /// fittest weights. Returns the best weights of the generation played.
+    pub fn step(&mut self) -> Result<Individual, String> {
+        let settings = &self.settings;
+        let mut rng =
+            StdRng::seed_from_u64(settings.seed.wrapping_add(self.generation() as u64 + 1));
+        // everyone plays the same games in a generation
+        let seeds: Vec<u64> = (0..settings.games.max(1)).map(|_| rng.gen()).collect();
+        let jobs: Vec<(usize, usize, u64)> = (0..self.population.len())
+            .flat_map(|i| {
+                seeds
+                    .iter()
+                    .enumerate()
+                    .map(move |(game, seed)| (i, game, *seed))
+            })
+            .collect();
+        let champion = self.best().map_or(EL_TETRIS, |best| best.weights);
+        let versus = tournament::Settings {
+            init_level: settings.init_level,
+            max_ticks: settings.max_ticks,
+            ..tournament::Settings::default()
+        };
+        let results = play_parallel(&jobs, settings.threads, &|i, game, seed| {
+            let bot = Controller::Bot(Bot::Heuristic(self.population[i]));
+            match settings.fitness {
+                Fitness::Solo => play_solo(&bot, seed, settings),
+                Fitness::Versus => {
+                    let champion = Controller::Bot(Bot::Heuristic(champion));
+                    // the sides are swapped every other game
+                    let (winner, _) = if game % 2 == 0 {
+                        play_game(&bot, &champion, seed, &versus)?
+                    } else {
+                        play_game(&champion, &bot, seed, &versus)
+                            .map(|(winner, ticks)| (winner.map(|winner| 1 - winner), ticks))?
+                    };
+                    Ok(match winner {
+                        Some(0) => 1.0,
+                        Some(_) => 0.0,
+                        None => 0.5,
+                    })
+                }
+            }
+        });
+        let mut ranked: Vec<Individual> = self
+            .population
+            .iter()
+            .map(|weights| Individual {
+                weights: *weights,
+                fitness: 0.0,
+            })
+            .collect();
+        for ((i, _, _), result) in jobs.iter().zip(results) {
+            ranked[*i].fitness += result? / seeds.len() as f64;
+        }
+        ranked.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        self.population = breed(&ranked, settings, &mut rng);
+        self.history.push(ranked[0]);
+        Ok(ranked[0])
+    }
+
+    /// Writes the training run to a file as json
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+
+    /// Reads a training run saved with `save`
+    pub fn load(path: &Path) -> Result<Trainer, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not a training checkpoint: {}", path.display(), e))
+    }
+}
+
+/// Plays a marathon game alone until topping out or `max_ticks` have passed,
+/// returns the score
+fn play_solo(bot: &Controller, seed: u64, settings: &Settings) -> Result<f64, String> {
+    // a game has at least two players, the other one tops out on its own
+    let mut game = Game::new(
+        &Rules::default(),
+        settings.init_level,
+        GameMode::Marathon,
+        vec![bot.clone(), Controller::Keyboard],
+        seed,
+    )?;
+    while !game.get_losts()[0] && game.tick() < settings.max_ticks {
+        game.update();
+    }
+    Ok(game.get_scores()[0] as f64)
+}
+
+/// The next generation: the elite as they are and children of the fittest
+fn breed(ranked: &[Individual], settings: &Settings, rng: &mut StdRng) -> Vec<Weights> {
+    let mut population: Vec<Weights> = ranked
+        .iter()
+        .take(settings.elite)
+        .map(|individual| individual.weights)
+        .collect();
+    while population.len() < ranked.len() {
+        let first = select(ranked, rng);
+        let second = select(ranked, rng);
+        // the child is closer to the fitter parent
+        let total = first.fitness + second.fitness;
+        let share = if total > 0.0 {
+            first.fitness / total
+        } else {
+            0.5
+        };
+        let (first, second) = (to_array(&first.weights), to_array(&second.weights));
+        let mut child = [0.0; 6];
+        for (i, weight) in child.iter_mut().enumerate() {
+            *weight = first[i] * share + second[i] * (1.0 - share);
+        }
+        let gene = rng.gen_range(0, child.len());
+        child[gene] += rng.gen_range(-settings.mutation, settings.mutation);
+        population.push(from_array(normalize(child)));
+    }
+    population
+}
+
+/// The fittest of `SELECTION_SIZE` random individuals
+fn select<'a>(ranked: &'a [Individual], rng: &mut StdRng) -> &'a Individual {
+    // the list is sorted, so the lowest index is the fittest
+    let best = (0..SELECTION_SIZE)
+        .map(|_| rng.gen_range(0, ranked.len()))
+        .min()
+        .unwrap_or(0);
+    &ranked[best]
+}
+
+/// Scales the weights to length 1, only how they compare to each other matters
+fn normalize(mut weights: [f64; 6]) -> [f64; 6] {
+    let length = weights
+        .iter()
+        .map(|weight| weight * weight)
+        .sum::<f64>()
+        .sqrt();
+    if length > 0.0 {
+        for weight in &mut weights {
+            *weight /= length;
+        }
+    }
+    weights
+}
+
+fn to_array(weights: &Weights) -> [f64; 6] {
+    [
+        weights.landing_height,
+        weights.eroded_cells,
+        weights.row_transitions,
+        weights.column_transitions,
+        weights.holes,
+        weights.wells,
+    ]
+}
+
+fn from_array(weights: [f64; 6]) -> Weights {
+    Weights {
+        landing_height: weights[0],
+        eroded_cells: weights[1],
+        row_transitions: weights[2],
+        column_transitions: weights[3],
+        holes: weights[4],
+        wells: weights[5],
+    }
+}
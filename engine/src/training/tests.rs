@@ -0,0 +1,62 @@
+use super::{normalize, to_array, Fitness, Settings, Trainer};
+use std::env;
+
+fn settings(fitness: Fitness) -> Settings {
+    Settings {
+        fitness,
+        population: 4,
+        elite: 1,
+        games: 2,
+        max_ticks: 300,
+        threads: 4,
+        ..Settings::default()
+    }
+}
+
+#[test]
+fn solo_training() {
+    let mut trainer = Trainer::new(settings(Fitness::Solo));
+    assert_eq!(4, trainer.population.len());
+    for _ in 0..2 {
+        trainer.step().unwrap();
+        assert_eq!(4, trainer.population.len());
+        // the best weights are kept and every child is scaled to length 1
+        assert_eq!(trainer.best().unwrap().weights, trainer.population[0]);
+        for weights in &trainer.population {
+            let length: f64 = to_array(weights).iter().map(|weight| weight * weight).sum();
+            assert!((length - 1.0).abs() < 1e-9);
+        }
+    }
+    assert_eq!(2, trainer.generation());
+
+    // the same seed trains the same weights
+    let mut again = Trainer::new(settings(Fitness::Solo));
+    again.step().unwrap();
+    again.step().unwrap();
+    assert_eq!(trainer, again);
+}
+
+#[test]
+fn versus_checkpoint() {
+    let mut trainer = Trainer::new(settings(Fitness::Versus));
+    let best = trainer.step().unwrap();
+    assert!((0.0..=1.0).contains(&best.fitness));
+
+    let path = env::temp_dir().join("tetris-training-checkpoint.json");
+    trainer.save(&path).unwrap();
+    let mut loaded = Trainer::load(&path).unwrap();
+    assert_eq!(trainer, loaded);
+    // carrying on from a checkpoint plays the same as not stopping
+    trainer.step().unwrap();
+    loaded.step().unwrap();
+    assert_eq!(trainer, loaded);
+}
+
+#[test]
+fn normalized() {
+    assert_eq!(
+        [0.6, 0.0, -0.8, 0.0, 0.0, 0.0],
+        normalize([3.0, 0.0, -4.0, 0.0, 0.0, 0.0])
+    );
+    assert_eq!([0.0; 6], normalize([0.0; 6]));
+}
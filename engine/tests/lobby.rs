@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use tetris_engine::game_data::{Action, Rules};
+use tetris_engine::game_state::GameMode;
+use tetris_engine::lobby::{Client, Event, Relay, Request, RoomState, Server, Settings};
+use tetris_engine::netplay::{self, Netcode, Transport};
+
+fn server() -> (Server, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    (Server::new(listener).unwrap(), address)
+}
+
+fn client(address: SocketAddr) -> Client {
+    Client::new(TcpStream::connect(address).unwrap()).unwrap()
+}
+
+fn settings(mode: GameMode) -> Settings {
+    Settings {
+        mode,
+        init_level: 5,
+        netcode: Netcode::Lockstep,
+        rules: Rules::default(),
+    }
+}
+
+/// Runs the server until the client gets an event
+fn next_event(server: &mut Server, client: &mut Client) -> Event {
+    for _ in 0..2000 {
+        server.poll();
+        if let Some(event) = client.poll().expect("lost the lobby") {
+            return event;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("no event arrived");
+}
+
+fn next_room(server: &mut Server, client: &mut Client) -> RoomState {
+    match next_event(server, client) {
+        Event::Room(room) => room,
+        event => panic!("expected the room, got {:?}", event),
+    }
+}
+
+fn next_error(server: &mut Server, client: &mut Client) -> String {
+    match next_event(server, client) {
+        Event::Error(error) => error,
+        event => panic!("expected an error, got {:?}", event),
+    }
+}
+
+/// Creates a room with two players in it, returns its code
+fn pair(server: &mut Server, owner: &mut Client, guest: &mut Client) -> String {
+    owner
+        .request(&Request::Create(settings(GameMode::Versus)))
+        .unwrap();
+    let code = next_room(server, owner).code;
+    guest
+        .request(&Request::Join {
+            code: code.to_lowercase(),
+        })
+        .unwrap();
+    assert_eq!(vec![false, false], next_room(server, owner).ready);
+    let room = next_room(server, guest);
+    assert_eq!((vec![false, false], 1), (room.ready, room.seat));
+    code
+}
+
+/// Marks both players as ready, returns the match each of them got
+fn start(server: &mut Server, mut clients: [&mut Client; 2]) -> Vec<(usize, netplay::MatchSetup)> {
+    let mut starts = Vec::new();
+    for client in &mut clients {
+        client.request(&Request::Ready(true)).unwrap();
+    }
+    for client in clients {
+        loop {
+            match next_event(server, client) {
+                Event::Start { player, setup } => {
+                    starts.push((player, setup));
+                    break;
+                }
+                Event::Room(_) => (),
+                event => panic!("expected the match to start, got {:?}", event),
+            }
+        }
+    }
+    starts
+}
+
+#[test]
+fn rooms() {
+    let (mut server, address) = server();
+    let (mut owner, mut guest, mut third) = (client(address), client(address), client(address));
+    let code = pair(&mut server, &mut owner, &mut guest);
+    assert_eq!(4, code.len());
+
+    third
+        .request(&Request::Join { code: code.clone() })
+        .unwrap();
+    assert!(next_error(&mut server, &mut third).contains("full"));
+    third
+        .request(&Request::Join {
+            code: "NOPE".to_string(),
+        })
+        .unwrap();
+    assert!(next_error(&mut server, &mut third).contains("no room"));
+
+    guest
+        .request(&Request::Configure(settings(GameMode::Marathon)))
+        .unwrap();
+    assert!(next_error(&mut server, &mut guest).contains("owner"));
+    let mut invalid = settings(GameMode::Marathon);
+    invalid.init_level = 100;
+    owner.request(&Request::Configure(invalid)).unwrap();
+    assert!(next_error(&mut server, &mut owner).contains("start level"));
+
+    guest.request(&Request::Ready(true)).unwrap();
+    assert_eq!(vec![false, true], next_room(&mut server, &mut owner).ready);
+    // changing the settings makes everyone confirm them again
+    owner
+        .request(&Request::Configure(settings(GameMode::Marathon)))
+        .unwrap();
+    let room = next_room(&mut server, &mut owner);
+    assert_eq!(GameMode::Marathon, room.settings.mode);
+    assert_eq!(vec![false, false], room.ready);
+
+    owner.request(&Request::Leave).unwrap();
+    assert_eq!(Event::Left, next_event(&mut server, &mut owner));
+    // the guest now owns the room
+    let room = loop {
+        let room = next_room(&mut server, &mut guest);
+        if room.ready.len() == 1 {
+            break room;
+        }
+    };
+    assert_eq!(0, room.seat);
+}
+
+#[test]
+fn match_series() {
+    let (mut server, address) = server();
+    let owner = Rc::new(RefCell::new(client(address)));
+    let guest = Rc::new(RefCell::new(client(address)));
+    pair(
+        &mut server,
+        &mut owner.borrow_mut(),
+        &mut guest.borrow_mut(),
+    );
+    let starts = start(
+        &mut server,
+        [&mut owner.borrow_mut(), &mut guest.borrow_mut()],
+    );
+    assert_eq!(0, starts[0].0);
+    assert_eq!(1, starts[1].0);
+    assert_eq!(starts[0].1, starts[1].1);
+
+    // the match is played through the lobby
+    let setup = &starts[0].1;
+    let mut sessions = [
+        netplay::start_session(setup, Relay::new(owner.clone()), 0),
+        netplay::start_session(setup, Relay::new(guest.clone()), 1),
+    ];
+    let mut games = [setup.create_game(), setup.create_game()];
+    for step in 0..3000 {
+        server.poll();
+        for (i, (session, game)) in sessions.iter_mut().zip(games.iter_mut()).enumerate() {
+            if step % 7 == i {
+                session.perform(Action::ALL[step % Action::ALL.len()]);
+            }
+            session.update(game).expect("the match should stay in sync");
+        }
+        if games.iter().all(|game| game.tick() >= 200) {
+            break;
+        }
+    }
+    assert!(games[0].tick() >= 200 && games[1].tick() >= 200);
+    assert_eq!(
+        games[0].snapshot().state_hash(),
+        games[1].snapshot().state_hash()
+    );
+
+    for client in [&owner, &guest] {
+        client
+            .borrow_mut()
+            .request(&Request::Finished { winner: Some(1) })
+            .unwrap();
+    }
+    let room = loop {
+        let room = next_room(&mut server, &mut owner.borrow_mut());
+        if !room.playing {
+            break room;
+        }
+    };
+    assert_eq!([0, 1], room.wins);
+
+    // players that disagree don't change the series
+    start(
+        &mut server,
+        [&mut owner.borrow_mut(), &mut guest.borrow_mut()],
+    );
+    owner
+        .borrow_mut()
+        .request(&Request::Finished { winner: Some(0) })
+        .unwrap();
+    guest
+        .borrow_mut()
+        .request(&Request::Finished { winner: Some(1) })
+        .unwrap();
+    let room = loop {
+        let room = next_room(&mut server, &mut guest.borrow_mut());
+        if !room.playing {
+            break room;
+        }
+    };
+    assert_eq!(([0, 1], 0), (room.wins, room.draws));
+
+    start(
+        &mut server,
+        [&mut owner.borrow_mut(), &mut guest.borrow_mut()],
+    );
+    for client in [&owner, &guest] {
+        client
+            .borrow_mut()
+            .request(&Request::Finished { winner: None })
+            .unwrap();
+    }
+    let room = loop {
+        let room = next_room(&mut server, &mut guest.borrow_mut());
+        if !room.playing {
+            break room;
+        }
+    };
+    assert_eq!(([0, 1], 1), (room.wins, room.draws));
+}
+
+#[test]
+fn opponent_leaves() {
+    let (mut server, address) = server();
+    let mut owner = client(address);
+    let guest = Rc::new(RefCell::new(client(address)));
+    pair(&mut server, &mut owner, &mut guest.borrow_mut());
+    start(&mut server, [&mut owner, &mut guest.borrow_mut()]);
+    let relay = Relay::new(guest.clone());
+    assert!(!relay.closed());
+
+    drop(owner);
+    loop {
+        match next_event(&mut server, &mut guest.borrow_mut()) {
+            Event::OpponentLeft => break,
+            Event::Room(room) => assert!(room.playing),
+            event => panic!("expected the opponent to leave, got {:?}", event),
+        }
+    }
+    assert!(relay.closed());
+    let room = next_room(&mut server, &mut guest.borrow_mut());
+    assert_eq!((vec![false], false), (room.ready, room.playing));
+}
+
+#[test]
+fn server_binary() {
+    let mut lobby = Command::new(env!("CARGO_BIN_EXE_lobby"))
+        .arg("0")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("couldn't start the lobby");
+    let mut line = String::new();
+    BufReader::new(lobby.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let port: u16 = line.trim().rsplit(' ').next().unwrap().parse().unwrap();
+
+    let mut client = Client::new(TcpStream::connect(("127.0.0.1", port)).unwrap()).unwrap();
+    client
+        .request(&Request::Create(settings(GameMode::Versus)))
+        .unwrap();
+    let mut event = None;
+    for _ in 0..2000 {
+        event = client.poll().unwrap();
+        if event.is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    lobby.kill().unwrap();
+    lobby.wait().unwrap();
+    match event {
+        Some(Event::Room(room)) => assert_eq!(vec![false], room.ready),
+        event => panic!("expected the new room, got {:?}", event),
+    }
+}
@@ -0,0 +1,144 @@
+use crate::game_data::GameEvent;
+
+#[cfg(test)]
+mod tests;
+
+/// How long the cleared rows flash, in ticks
+pub const ROW_FLASH: u32 = 20;
+/// How long a placed piece flashes
+pub const LOCK_FLASH: u32 = 8;
+/// How long the stack takes to rise when garbage arrives
+pub const GARBAGE_RISE: u32 = 10;
+/// How long the screen shakes after a big attack
+pub const SHAKE: u32 = 18;
+
+/// Attacks of at least this many lines shake the screen
+const SHAKE_LINES: u8 = 4;
+/// How far the screen moves when it starts shaking, in blocks
+const SHAKE_AMPLITUDE: f32 = 0.4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Effect {
+    /// Bit `i` is set for row `i`
+    Rows(u32),
+    Lock([[i32; 2]; 4]),
+    Rise(u8),
+    Shake(u8),
+}
+
+impl Effect {
+    fn duration(&self) -> u32 {
+        match self {
+            Effect::Rows(_) => ROW_FLASH,
+            Effect::Lock(_) => LOCK_FLASH,
+            Effect::Rise(_) => GARBAGE_RISE,
+            Effect::Shake(_) => SHAKE,
+        }
+    }
+}
+
+struct Animation {
+    player: usize,
+    effect: Effect,
+    /// Ticks since the animation started
+    age: u32,
+}
+
+impl Animation {
+    /// How far the animation has come, from 0 to 1
+    fn progress(&self) -> f32 {
+        self.age as f32 / self.effect.duration() as f32
+    }
+}
+
+/// The animations started by the game events. They are advanced once per game
+/// tick so they play at the same speed no matter the frame rate.
+#[derive(Default)]
+pub struct Animations {
+    playing: Vec<Animation>,
+}
+
+impl Animations {
+    /// Stops every animation, e.g. when a new game starts
+    pub fn clear(&mut self) {
+        self.playing.clear();
+    }
+
+    /// Advances the animations one tick and starts the ones of new events
+    pub fn update(&mut self, events: &[(u32, usize, GameEvent)]) {
+        for animation in &mut self.playing {
+            animation.age += 1;
+        }
+        self.playing
+            .retain(|animation| animation.age < animation.effect.duration());
+        for &(_, player, event) in events {
+            let effect = match event {
+                GameEvent::LinesCleared { rows, .. } => Effect::Rows(rows),
+                GameEvent::PieceLocked { blocks } => Effect::Lock(blocks),
+                GameEvent::GarbageReceived { lines } => Effect::Rise(lines),
+                GameEvent::GarbageSent { lines } if lines >= SHAKE_LINES => Effect::Shake(lines),
+                _ => continue,
+            };
+            self.playing.push(Animation {
+                player,
+                effect,
+                age: 0,
+            });
+        }
+    }
+
+    /// The rows of a board that were just cleared, with the progress of their flash
+    pub fn cleared_rows(&self, player: usize) -> Vec<(usize, f32)> {
+        let mut rows = Vec::new();
+        for animation in self.of(player) {
+            if let Effect::Rows(mask) = animation.effect {
+                for row in (0..32).filter(|row| mask & 1 << row != 0) {
+                    rows.push((row, animation.progress()));
+                }
+            }
+        }
+        rows
+    }
+
+    /// The blocks of pieces that were just placed, with the progress of their flash
+    pub fn locked_pieces(&self, player: usize) -> Vec<([[i32; 2]; 4], f32)> {
+        self.of(player)
+            .filter_map(|animation| match animation.effect {
+                Effect::Lock(blocks) => Some((blocks, animation.progress())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// How many rows below its place the stack is drawn, it rises back as garbage arrives
+    pub fn stack_offset(&self, player: usize) -> f32 {
+        self.of(player)
+            .map(|animation| match animation.effect {
+                Effect::Rise(lines) => lines as f32 * (1.0 - animation.progress()),
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// How far the screen is moved by shaking, in blocks
+    pub fn shake(&self) -> (f32, f32) {
+        let mut offset = (0.0, 0.0);
+        for animation in &self.playing {
+            if let Effect::Shake(lines) = animation.effect {
+                let strength = SHAKE_AMPLITUDE
+                    * (lines as f32 / SHAKE_LINES as f32)
+                    * (1.0 - animation.progress());
+                let age = animation.age as f32;
+                offset.0 += strength * (age * 2.5).sin();
+                offset.1 += strength * (age * 3.7).cos();
+            }
+        }
+        offset
+    }
+
+    fn of(&self, player: usize) -> impl Iterator<Item = &Animation> {
+        self.playing
+            .iter()
+            .filter(move |animation| animation.player == player)
+    }
+}
@@ -0,0 +1,44 @@
+use super::{Animations, GARBAGE_RISE, ROW_FLASH};
+use crate::game_data::{ClearKind, GameEvent};
+
+#[test]
+fn cleared_rows() {
+    let mut animations = Animations::default();
+    let clear = GameEvent::LinesCleared {
+        kind: ClearKind::Double,
+        rows: 0b101,
+    };
+    animations.update(&[(0, 1, clear)]);
+    assert_eq!(vec![(0, 0.0), (2, 0.0)], animations.cleared_rows(1));
+    assert!(animations.cleared_rows(0).is_empty());
+    for _ in 0..ROW_FLASH / 2 {
+        animations.update(&[]);
+    }
+    assert_eq!(vec![(0, 0.5), (2, 0.5)], animations.cleared_rows(1));
+    for _ in 0..ROW_FLASH / 2 {
+        animations.update(&[]);
+    }
+    assert!(animations.cleared_rows(1).is_empty());
+}
+
+#[test]
+fn garbage_and_shake() {
+    let mut animations = Animations::default();
+    animations.update(&[
+        (0, 0, GameEvent::GarbageReceived { lines: 2 }),
+        (0, 1, GameEvent::GarbageSent { lines: 1 }),
+    ]);
+    assert_eq!(2.0, animations.stack_offset(0));
+    assert_eq!(0.0, animations.stack_offset(1));
+    // small attacks don't shake the screen
+    assert_eq!((0.0, 0.0), animations.shake());
+    for _ in 0..GARBAGE_RISE {
+        animations.update(&[]);
+    }
+    assert_eq!(0.0, animations.stack_offset(0));
+
+    animations.update(&[(0, 1, GameEvent::GarbageSent { lines: 4 })]);
+    assert_ne!((0.0, 0.0), animations.shake());
+    animations.clear();
+    assert_eq!((0.0, 0.0), animations.shake());
+}
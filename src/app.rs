@@ -1,423 +1,1793 @@
-use crate::game_state::Game;
+use crate::animation::Animations;
+use crate::bots::Bot;
+use crate::config::{Config, CONFIG_FILE};
+use crate::game_data::{Action, COLS, ROWS, TICK_RATE};
+use crate::game_state::{
+    find_ai_libraries, Controller, Game, GameMode, MAX_PLAYERS, PLAYER_AMOUNT,
+};
+use crate::hud::{results_rows, series_line, stat_lines};
+use crate::input::{axis_direction, AutoRepeat};
+use crate::keymap::{conflict_message, key_name, pad_input_name, KeyMap, PadInput};
+use crate::layout::{layout_match, ui_scale, BoardLayout, GRID_SIZE};
+use crate::lobby::{self, Event, Relay, Request, RoomState};
+use crate::netplay::{self, MatchSetup, Session};
+use crate::replay::Replay;
+use crate::series::Series;
+use crate::sound::{Sound, Sounds};
+use crate::spectate::{Broadcast, Spectator};
+use crate::stats::Stats;
+use crate::theme::{load_themes, BuiltBatch, Skin, Theme, THEME_DIR};
 
-use ggez::event::{self, KeyCode, KeyMods};
-use ggez::graphics::{self, Color, DrawMode, Font, Mesh, MeshBuilder, Rect, Scale, Text};
+use ggez::event::{self, Axis, Button, GamepadId, KeyCode, KeyMods};
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Drawable, Font, Mesh, Rect, Scale, Text};
+use ggez::input::gamepad;
+use ggez::timer;
 
 use ggez::{Context, GameResult};
 use graphics::TextFragment;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-/// size of the window
-pub const SCREEN_SIZE: (f32, f32) = (800.0, 600.0);
-
-/// Visible size of the tetris board
-pub const GRID_SIZE: (i32, i32) = (10, 20);
-const GRID_LINE_WIDTH: f32 = 1.0;
-
-/// Size of each block
+/// Size of each block in the meshes, they are scaled to the layout when drawn
 pub const BLOCK_SIZE: (f32, f32) = (20.0, 20.0);
 
-/// Size of the scaled-down blocks
-const SMALL_BLOCK_SIZE: (f32, f32) = (BLOCK_SIZE.0 * 0.5, BLOCK_SIZE.1 * 0.5);
-
-/// The top-left corner of the boards
-pub const P1_BOARD_PLACEMENT: (f32, f32) = (100.0, 100.0);
-pub const P2_BOARD_PLACEMENT: (f32, f32) = (SCREEN_SIZE.0 / 2.0 + 100.0, 100.0);
-
-/// The x y w h of the boards
-pub const P1_BOARD: (f32, f32, f32, f32) = (
-    P1_BOARD_PLACEMENT.0,                // x
-    P1_BOARD_PLACEMENT.1,                // y
-    (GRID_SIZE.0 as f32) * BLOCK_SIZE.0, // width
-    (GRID_SIZE.1 as f32) * BLOCK_SIZE.0, // height
-);
-/// The x y w h of the boards
-pub const P2_BOARD: (f32, f32, f32, f32) = (
-    P2_BOARD_PLACEMENT.0,                // x
-    P2_BOARD_PLACEMENT.1,                // y
-    (GRID_SIZE.0 as f32) * BLOCK_SIZE.0, // width
-    (GRID_SIZE.1 as f32) * BLOCK_SIZE.0, // height
-);
-
-// for the next piece and saved piece boxes
-const INFO_BOX: (f32, f32) = (SMALL_BLOCK_SIZE.0 * 6.0, SMALL_BLOCK_SIZE.1 * 6.0);
-const INFO_BOX_MARGIN: (f32, f32) = (SMALL_BLOCK_SIZE.0, SMALL_BLOCK_SIZE.1);
-
 // size of the attack meter increments
 const ATTACK_METER: (f32, f32) = (BLOCK_SIZE.0 / 2.0, BLOCK_SIZE.1);
 
-// the center of the score text
-const P1_SCORE_PLACEMENT: (f32, f32) = (
-    P1_BOARD.0 + P1_BOARD.2 / 2.0,
-    P1_BOARD.1 + P1_BOARD.3 + 30.0,
-);
-const P2_SCORE_PLACEMENT: (f32, f32) = (
-    P2_BOARD.0 + P2_BOARD.2 / 2.0,
-    P2_BOARD.1 + P2_BOARD.3 + 30.0,
-);
-
-const BACKGROUND_COLOR: Color = Color::new(25.0 / 255.0, 172.0 / 255.0, 244.0 / 255.0, 1.0);
-const BOARD_BACKGROUND: Color = Color::new(0.0, 0.0, 0.0, 0.8);
-const GRID_COLOR: Color = Color::new(100.0 / 255.0, 100.0 / 255.0, 100.0 / 255.0, 1.0);
-
-pub const PALETTE: [Color; 8] = [
-    Color::new(0.0 / 255.0, 255.0 / 255.0, 255.0 / 255.0, 1.0), // Cyan
-    Color::new(255.0 / 255.0, 255.0 / 255.0, 0.0 / 255.0, 1.0), // Yellow
-    Color::new(128.0 / 255.0, 0.0 / 255.0, 128.0 / 255.0, 1.0), // Purple
-    Color::new(0.0 / 255.0, 255.0 / 255.0, 0.0 / 255.0, 1.0),   // Green
-    Color::new(255.0 / 255.0, 0.0 / 255.0, 0.0 / 255.0, 1.0),   // Red
-    Color::new(0.0 / 255.0, 0.0 / 255.0, 255.0 / 255.0, 1.0),   // Blue
-    Color::new(255.0 / 255.0, 127.0 / 255.0, 0.0 / 255.0, 1.0), // Orange
-    Color::new(127.0 / 255.0, 127.0 / 255.0, 127.0 / 255.0, 1.0), // Grey
-];
-
-pub const GHOST_PALETTE: [Color; 7] = [
-    Color::new(0.0 / 255.0, 255.0 / 255.0, 255.0 / 255.0, 0.3), // Cyan
-    Color::new(255.0 / 255.0, 255.0 / 255.0, 0.0 / 255.0, 0.3), // Yellow
-    Color::new(128.0 / 255.0, 0.0 / 255.0, 128.0 / 255.0, 0.5), // Purple
-    Color::new(0.0 / 255.0, 255.0 / 255.0, 0.0 / 255.0, 0.3),   // Green
-    Color::new(255.0 / 255.0, 0.0 / 255.0, 0.0 / 255.0, 0.3),   // Red
-    Color::new(0.0 / 255.0, 0.0 / 255.0, 175.0 / 255.0, 0.5),   // Blue
-    Color::new(255.0 / 255.0, 127.0 / 255.0, 0.0 / 255.0, 0.3), // Orange
-];
-
-const INIT_LEVEL: usize = 5;
+// size of the score text
+const SCORE_SCALE: f32 = 25.0;
+
+// size of the text in the stats panels
+const STATS_SCALE: f32 = 14.0;
+
+// size of the player labels and the text on boards that are out
+const LABEL_SCALE: f32 = 20.0;
+const OUT_SCALE: f32 = 50.0;
+const OUT_SHADE: Color = Color::new(0.0, 0.0, 0.0, 0.6);
+/// Color of the labels of the players targeting the focus board
+const TARGETING_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+
+/// Directories that are searched for ai-scripts in the player setup
+#[cfg(windows)]
+const AI_DIRECTORIES: [&str; 3] = [".", "./ai", "./ai-example/windows"];
+#[cfg(not(windows))]
+const AI_DIRECTORIES: [&str; 3] = [".", "./ai", "./ai-example/unix"];
+
+// menu layout
+const MENU_TITLE_SCALE: f32 = 60.0;
+const MENU_ITEM_SCALE: f32 = 30.0;
+const MENU_ITEM_SPACING: f32 = 45.0;
+const MENU_TOP: f32 = 200.0;
+const MENU_SELECTED_COLOR: Color = Color::new(1.0, 1.0, 0.0, 1.0);
+
+const MAIN_MENU: [&str; 3] = ["Play", "Online", "Controls"];
+/// The index of the theme item in the main menu, after the items above
+const THEME_ITEM: usize = MAIN_MENU.len();
+// results table layout
+const RESULTS_TITLE_SCALE: f32 = 80.0;
+const RESULTS_TITLE_TOP: f32 = 70.0;
+const RESULTS_TABLE_TOP: f32 = 130.0;
+const RESULTS_ROW_SCALE: f32 = 20.0;
+const RESULTS_ROW_SPACING: f32 = 26.0;
+const RESULTS_NAME_WIDTH: f32 = 180.0;
+const RESULTS_COLUMN_WIDTH: f32 = 70.0;
+const RESULTS_MENU_TOP: f32 = 450.0;
+const RESULTS_SHADE: Color = Color::new(0.0, 0.0, 0.0, 0.7);
+
+/// Where saved replays are written
+const REPLAY_DIR: &str = "./replays";
+/// Where exported series are written
+const SERIES_DIR: &str = "./series";
+/// The most wins a series can be played to
+const MAX_FIRST_TO: u32 = 9;
+const SERIES_TOP: f32 = 15.0;
+
+/// How long joining an online game may take before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ticks counted down before a local game starts
+const COUNTDOWN: u32 = 3 * TICK_RATE;
+
+/// What is holding down an action
+#[derive(Copy, Clone, PartialEq)]
+enum InputSource {
+    Key(KeyCode),
+    Pad(GamepadId, PadInput),
+}
+
+/// A gamepad that has sent at least one event
+struct ConnectedPad {
+    id: GamepadId,
+    name: String,
+    /// The player slot the gamepad controls
+    player: Option<usize>,
+    /// The direction each pushed axis is held in
+    axes: Vec<(Axis, bool)>,
+}
+
+/// The different screens of the application
+#[derive(Copy, Clone, PartialEq)]
+enum Scene {
+    Menu,
+    ModeSelect,
+    PlayerSetup,
+    Controls,
+    /// Hosting or joining an online game
+    Online,
+    /// Waiting for someone to join the hosted game
+    Hosting,
+    /// Connected to the lobby server, creating or joining a room
+    Lobby,
+    /// In a room of the lobby, waiting for both players to be ready
+    Room,
+    Game,
+    Results,
+}
+
+/// What the batch of a board was built from, the batch is only rebuilt when
+/// any of it changes
+#[derive(PartialEq)]
+struct BoardFrame {
+    layout: BoardLayout,
+    board: [[u32; COLS]; ROWS],
+    next_piece: [[u32; 4]; 4],
+    saved_piece: [[u32; 4]; 4],
+    meter: u32,
+    stack_offset: f32,
+}
 
 // contains fields like the game struct, ai-script, etc. Basically stores the game-state + resources
 pub struct AppState {
-    game_state: Game,
-    block_palatte: [Mesh; 15],
-    grid_mesh: Mesh,
-    small_block_palatte: [Mesh; 8],
+    scene: Scene,
+    /// The selected menu item in the current scene
+    cursor: usize,
+    game_state: Option<Game>,
+    /// Set while playing online, the game is then only advanced through it
+    session: Option<Box<dyn Session>>,
+    /// Listens for the opponent while hosting
+    listener: Option<TcpListener>,
+    /// Sends the games played to spectators, kept between games
+    broadcast: Option<Broadcast>,
+    /// Set while watching a broadcast, the game is then only advanced through it
+    spectator: Option<Spectator>,
+    /// Set while connected to the lobby server, shared with the session of a lobby match
+    lobby: Option<Rc<RefCell<lobby::Client>>>,
+    /// The room the lobby server last sent
+    room: Option<RoomState>,
+    /// The code of the room to join, typed in the lobby scene
+    room_code: String,
+    mode: GameMode,
+    init_level: usize,
+    /// Index into `ais` for every player, `None` means keyboard or gamepad.
+    /// Its length is the amount of players in local games.
+    controllers: Vec<Option<usize>>,
+    /// The built-in bots followed by the ai-scripts found so far
+    ais: Vec<Controller>,
+    /// Error shown in the player setup, e.g. if an ai-script failed to load
+    message: Option<String>,
+    keymap: KeyMap,
+    /// The player whose bindings are shown in the controls scene
+    controls_player: usize,
+    /// Set while waiting for a key to bind to the selected action
+    rebinding: bool,
+    auto_repeat: AutoRepeat<InputSource>,
+    gamepads: Vec<ConnectedPad>,
+    animations: Animations,
+    /// The stats of the current game
+    stats: Option<Stats>,
+    /// Records the current game so it can be saved once it's over
+    replay: Option<Replay>,
+    /// The wins needed to win a local series, 0 plays single games
+    first_to: u32,
+    /// Set if the participants of a series move one player to the right every game
+    swap_sides: bool,
+    /// The local series being played, its participants are the controllers
+    series: Option<Series>,
+    /// The participant of the series playing as each player of the current
+    /// game, empty without a series
+    seats: Vec<usize>,
+    sounds: Sounds,
+    /// Ticks left before the game starts, local games count down from `COUNTDOWN`
+    countdown: u32,
+    config: Config,
+    /// The built-in themes followed by the ones in the themes directory
+    themes: Vec<Theme>,
+    /// Index into `themes` of the theme the skin is made from
+    theme: usize,
+    skin: Skin,
+    /// The boards of the last frame, with the boxes, blocks and attack meters
+    /// of each drawn at once
+    board_batches: Vec<(BoardFrame, BuiltBatch)>,
+    /// The draw calls made so far this frame
+    draw_calls: Cell<usize>,
+    /// Shows the frame rate and the draw calls of every frame
+    show_stats: bool,
     font: Font,
 }
 
 impl AppState {
-    pub fn new(ctx: &mut Context) -> AppState {
-        let state = AppState {
+    /// Creates the app from an already validated config, `audio` is false if
+    /// the context has no audio device
+    pub fn new(ctx: &mut Context, config: Config, audio: bool) -> AppState {
+        let (themes, theme_errors) = load_themes(&config.visuals, Path::new(THEME_DIR));
+        let theme = themes
+            .iter()
+            .position(|theme| theme.name == config.visuals.theme);
+        let (skin, skin_error) = Skin::new(ctx, &themes[theme.unwrap_or(0)]);
+        let mut state = AppState {
+            scene: Scene::Menu,
+            cursor: 0,
+            game_state: None,
+            session: None,
+            listener: None,
+            broadcast: None,
+            spectator: None,
+            lobby: None,
+            room: None,
+            room_code: String::new(),
+            mode: config.game.mode,
+            init_level: config.game.init_level,
+            controllers: vec![None; config.game.controllers.len()],
+            ais: Bot::ALL.iter().map(|bot| Controller::Bot(*bot)).collect(),
+            message: None,
+            keymap: KeyMap::from_config(&config.controls).expect("invalid controls in config"),
+            controls_player: 0,
+            rebinding: false,
+            auto_repeat: AutoRepeat::new(config.controls.das(), config.controls.arr()),
+            gamepads: Vec::new(),
+            animations: Animations::default(),
+            stats: None,
+            replay: None,
+            first_to: 0,
+            swap_sides: false,
+            series: None,
+            seats: Vec::new(),
+            sounds: Sounds::load(ctx, &config.audio, audio),
+            countdown: 0,
+            theme: theme.unwrap_or(0),
+            themes,
+            skin,
+            board_batches: Vec::new(),
+            draw_calls: Cell::new(0),
+            show_stats: false,
             // Load/create resources here: images, fonts, sounds, etc.
-            game_state: Game::new(5),
-            block_palatte: generate_blocks(ctx),
-            grid_mesh: generate_grid_mesh(ctx).expect("grid mesh err"),
-            small_block_palatte: generate_small_blocks(ctx),
             font: Font::new(ctx, "/Roboto-Regular.ttf").expect("font loading error"),
+            config,
         };
+        state.message = state.conflict_message();
+        if theme.is_none() {
+            state.message = Some(format!("Unknown theme {}", state.config.visuals.theme));
+        }
+        if let Some(error) = theme_errors.into_iter().next().or(skin_error) {
+            state.message = Some(error);
+        }
+        state.scan_ai_libraries();
+        // the controllers from the config are preselected
+        let ais = state.config.game.ai_controllers().unwrap_or_default();
+        for (player, ai) in ais.into_iter().enumerate() {
+            if let Some(ai) = ai {
+                state.controllers[player] = Some(state.add_ai(ai));
+            }
+        }
+        // bots and ai-scripts given as arguments are preselected, player 2 gets the first one
+        let players = state.controllers.len();
+        for (i, arg) in env::args().skip(1).take(players).enumerate() {
+            match Controller::from_arg(&arg) {
+                Ok(ai) => state.controllers[players - 1 - i] = Some(state.add_ai(ai)),
+                Err(e) => state.message = Some(e),
+            }
+        }
         state
     }
-}
 
-impl event::EventHandler for AppState {
-    // update the game logic
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
-        let [p1_lost, p2_lost] = self.game_state.get_losts();
+    /// Returns the index of a bot or ai-script, adding it to the list if it's new
+    fn add_ai(&mut self, ai: Controller) -> usize {
+        match self.ais.iter().position(|known| *known == ai) {
+            Some(index) => index,
+            None => {
+                self.ais.push(ai);
+                self.ais.len() - 1
+            }
+        }
+    }
 
-        if p1_lost || p2_lost {
-            // if either player has lost
-        } else {
-            self.game_state.update();
+    /// Looks for new ai-scripts, keeping the already known ones so the selections stay valid
+    fn scan_ai_libraries(&mut self) {
+        let dirs: Vec<&Path> = AI_DIRECTORIES.iter().map(Path::new).collect();
+        for lib in find_ai_libraries(&dirs) {
+            self.add_ai(Controller::AI(lib));
         }
-        Ok(())
     }
 
-    // update the graphics
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        // Clear screen with the background color
-        graphics::clear(ctx, BACKGROUND_COLOR);
+    /// Describes the first key binding conflict, if any
+    fn conflict_message(&self) -> Option<String> {
+        self.keymap.conflicts().first().map(conflict_message)
+    }
 
-        // draw boards
-        let rectangle = Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, P1_BOARD.2 as i32, P1_BOARD.3 as i32),
-            BOARD_BACKGROUND,
-        )?;
+    /// Binds a key to the action selected in the controls scene
+    fn rebind(&mut self, key: KeyCode) {
+        self.rebinding = false;
+        let action = Action::ALL[self.cursor - 1];
+        self.message = match self.keymap.bind(self.controls_player, action, key) {
+            Ok(()) => self.conflict_message(),
+            Err(e) => Some(e),
+        };
+    }
 
-        graphics::draw(
-            ctx,
-            &rectangle,
-            (ggez::mint::Point2 {
-                x: P1_BOARD.0,
-                y: P1_BOARD.1,
-            },),
-        )?;
+    /// Binds a gamepad input to the action selected in the controls scene
+    fn rebind_pad(&mut self, input: PadInput) {
+        self.rebinding = false;
+        self.keymap.bind_pad(Action::ALL[self.cursor - 1], input);
+        self.message = self.conflict_message();
+    }
 
-        graphics::draw(
-            ctx,
-            &rectangle,
-            (ggez::mint::Point2 {
-                x: P2_BOARD.0,
-                y: P2_BOARD.1,
-            },),
-        )?;
+    /// Returns the index of a gamepad, registering it if it's new. New gamepads
+    /// get the first player slot that no other gamepad has.
+    fn connect_pad(&mut self, ctx: &Context, id: GamepadId) -> usize {
+        if let Some(index) = self.gamepads.iter().position(|pad| pad.id == id) {
+            return index;
+        }
+        let player =
+            (0..MAX_PLAYERS).find(|p| self.gamepads.iter().all(|pad| pad.player != Some(*p)));
+        self.gamepads.push(ConnectedPad {
+            id,
+            name: gamepad::gamepad(ctx, id).name().to_string(),
+            player,
+            axes: Vec::new(),
+        });
+        self.gamepads.len() - 1
+    }
 
-        // draw next piece boxes
-        let info_box = Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, INFO_BOX.0 as i32, INFO_BOX.1 as i32),
-            BOARD_BACKGROUND,
-        )?;
+    /// Starts holding the actions bound to an input
+    fn press(&mut self, source: InputSource, actions: Vec<(usize, Action)>) {
+        if self.spectator.is_some() || self.countdown > 0 {
+            return;
+        }
+        if let Some(game) = &mut self.game_state {
+            let now = Instant::now();
+            for (participant, action) in actions {
+                let player = game_player(&self.seats, participant);
+                if self.auto_repeat.press(source, player, action, now) {
+                    if let Some(sound) = Sound::of_action(action) {
+                        self.sounds.play(sound);
+                    }
+                    let player = match &mut self.session {
+                        // every local input controls the local player online
+                        Some(session) => {
+                            session.perform(action);
+                            session.local_player()
+                        }
+                        None => {
+                            game.perform(player, action);
+                            player
+                        }
+                    };
+                    if let Some(stats) = &mut self.stats {
+                        stats.input(game.tick(), player, action);
+                    }
+                }
+            }
+        }
+    }
 
-        graphics::draw(
-            ctx,
-            &info_box,
-            (ggez::mint::Point2 {
-                x: P1_BOARD.0 + P1_BOARD.2,
-                y: P1_BOARD.1,
-            },),
-        )?;
+    /// Handles a gamepad input being pressed in any scene
+    fn pad_down(&mut self, ctx: &mut Context, index: usize, input: PadInput) {
+        let pad = &self.gamepads[index];
+        if self.scene == Scene::Game {
+            if input == PadInput::Button(Button::Start) {
+                self.back(ctx);
+            } else if let Some(player) = pad.player {
+                let actions = self
+                    .keymap
+                    .pad_actions(input)
+                    .into_iter()
+                    .map(|action| (player, action))
+                    .collect();
+                self.press(InputSource::Pad(pad.id, input), actions);
+            }
+        } else if self.rebinding {
+            self.rebind_pad(input);
+        } else {
+            // gamepads can navigate the menus too
+            let key =
+                match input {
+                    PadInput::Button(Button::DPadUp) | PadInput::Axis(Axis::LeftStickY, true) => {
+                        KeyCode::Up
+                    }
+                    PadInput::Button(Button::DPadDown)
+                    | PadInput::Axis(Axis::LeftStickY, false) => KeyCode::Down,
+                    PadInput::Button(Button::DPadLeft)
+                    | PadInput::Axis(Axis::LeftStickX, false) => KeyCode::Left,
+                    PadInput::Button(Button::DPadRight)
+                    | PadInput::Axis(Axis::LeftStickX, true) => KeyCode::Right,
+                    PadInput::Button(Button::South) | PadInput::Button(Button::Start) => {
+                        KeyCode::Return
+                    }
+                    PadInput::Button(Button::East) => KeyCode::Escape,
+                    _ => return,
+                };
+            self.menu_key(ctx, key);
+        }
+    }
 
-        graphics::draw(
-            ctx,
-            &info_box,
-            (ggez::mint::Point2 {
-                x: P2_BOARD.0 + P2_BOARD.2,
-                y: P2_BOARD.1,
-            },),
-        )?;
+    /// Handles a key press outside of the game
+    fn menu_key(&mut self, ctx: &mut Context, keycode: KeyCode) {
+        let items = self.menu_items().len();
+        match keycode {
+            KeyCode::Up => self.cursor = (self.cursor + items - 1) % items,
+            KeyCode::Down => self.cursor = (self.cursor + 1) % items,
+            KeyCode::Left => self.change_setting(ctx, -1),
+            KeyCode::Right => self.change_setting(ctx, 1),
+            KeyCode::Return | KeyCode::Space => self.select(ctx),
+            KeyCode::Escape => self.back(ctx),
+            KeyCode::R if self.scene == Scene::Results => self.restart_game(),
+            _ => (),
+        }
+    }
 
-        // draw saved piece boxes
-        graphics::draw(
-            ctx,
-            &info_box,
-            (ggez::mint::Point2 {
-                x: P1_BOARD.0 - INFO_BOX.0,
-                y: P1_BOARD.1,
-            },),
-        )?;
+    fn save_keymap(&mut self) {
+        self.message = match self.conflict_message() {
+            Some(conflict) => Some(format!("Can't save: {}", conflict)),
+            None => {
+                self.keymap.update_config(&mut self.config.controls);
+                match self.config.save(Path::new(CONFIG_FILE)) {
+                    Ok(()) => Some(format!("Saved to {}", CONFIG_FILE)),
+                    Err(e) => Some(e),
+                }
+            }
+        };
+    }
 
-        graphics::draw(
-            ctx,
-            &info_box,
-            (ggez::mint::Point2 {
-                x: P2_BOARD.0 - INFO_BOX.0,
-                y: P2_BOARD.1,
-            },),
-        )?;
+    fn set_scene(&mut self, scene: Scene) {
+        self.scene = scene;
+        self.cursor = 0;
+        self.auto_repeat.clear();
+        if scene != Scene::Hosting {
+            self.listener = None;
+        }
+        if scene == Scene::Game {
+            self.sounds.start_music();
+        }
+        if scene != Scene::Game && scene != Scene::Results {
+            self.sounds.stop_music();
+            if let Some(session) = self.session.take() {
+                session.close();
+            }
+            self.spectator = None;
+        }
+        if !matches!(
+            scene,
+            Scene::Lobby | Scene::Room | Scene::Game | Scene::Results
+        ) {
+            // disconnecting leaves the room
+            self.lobby = None;
+            self.room = None;
+        }
+    }
+
+    /// Creates a new game from the current settings, the first of a series if one is set
+    fn start_game(&mut self) {
+        self.series = None;
+        if self.first_to > 0 {
+            let names = (0..self.controllers.len())
+                .map(|player| self.controller_name(player))
+                .collect();
+            match Series::new(names, self.first_to, self.swap_sides) {
+                Ok(series) => self.series = Some(series),
+                Err(e) => {
+                    self.message = Some(e);
+                    return;
+                }
+            }
+        }
+        self.start_round();
+    }
+
+    /// Creates a game with the controllers seated as the series says
+    fn start_round(&mut self) {
+        self.seats = self.series.as_ref().map_or(Vec::new(), Series::seats);
+        let controllers = (0..self.controllers.len())
+            .map(|player| {
+                let participant = self.seats.get(player).copied().unwrap_or(player);
+                match self.controllers[participant] {
+                    Some(index) => self.ais[index].clone(),
+                    None => Controller::Keyboard,
+                }
+            })
+            .collect();
+        match Game::new(
+            &self.config.rules,
+            self.init_level,
+            self.mode,
+            controllers,
+            rand::random(),
+        ) {
+            Ok(game) => {
+                self.game_state = Some(game);
+                self.message = None;
+                self.start_records();
+                self.countdown = COUNTDOWN;
+                self.set_scene(Scene::Game);
+                self.broadcast_game();
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    /// Restarts a local game, online and watched games can't be restarted
+    fn restart_game(&mut self) {
+        if self.session.is_some() || self.spectator.is_some() {
+            return;
+        }
+        if let Some(series) = &self.series {
+            // a won series starts over between the same participants
+            if series.winner().is_some() {
+                let names = series.names().to_vec();
+                self.series = Series::new(names, series.first_to(), self.swap_sides).ok();
+            }
+            self.start_round();
+            return;
+        }
+        if let Some(game) = &mut self.game_state {
+            game.restart(self.init_level, rand::random());
+            self.start_records();
+            self.countdown = COUNTDOWN;
+            self.set_scene(Scene::Game);
+            self.broadcast_game();
+        }
+    }
 
-        // draw next pieces
-        let [p1_next_piece, p2_next_piece] = self.game_state.get_next_pieces();
-
-        for y in 0..p1_next_piece.len() {
-            for x in 0..p1_next_piece[y].len() {
-                if p1_next_piece[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.small_block_palatte[p1_next_piece[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: x as f32 * SMALL_BLOCK_SIZE.0
-                                + P1_BOARD.0
-                                + P1_BOARD.2
-                                + INFO_BOX_MARGIN.0,
-                            y: y as f32 * SMALL_BLOCK_SIZE.1 + P1_BOARD.1 + INFO_BOX_MARGIN.1,
-                        },),
-                    )?
+    /// Shows a new game to the spectators, the broadcast is started with the first game
+    fn broadcast_game(&mut self) {
+        let game = match &self.game_state {
+            Some(game) => game,
+            None => return,
+        };
+        match &mut self.broadcast {
+            Some(broadcast) => broadcast.restart(game),
+            None if self.config.online.broadcast => {
+                match Broadcast::new(self.config.online.spectator_port, game) {
+                    Ok(broadcast) => self.broadcast = Some(broadcast),
+                    Err(e) => self.message = Some(e),
                 }
             }
+            None => (),
         }
+    }
+
+    /// Starts listening for an opponent on the configured port
+    fn host_online(&mut self) {
+        let port = self.config.online.port;
+        let listener = TcpListener::bind(("0.0.0.0", port)).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        });
+        match listener {
+            Ok(listener) => {
+                self.set_scene(Scene::Hosting);
+                self.listener = Some(listener);
+                self.message = Some(format!("Waiting for an opponent on port {}", port));
+            }
+            Err(e) => self.message = Some(format!("Couldn't host on port {}: {}", port, e)),
+        }
+    }
 
-        for y in 0..p2_next_piece.len() {
-            for x in 0..p2_next_piece[y].len() {
-                if p2_next_piece[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.small_block_palatte[p2_next_piece[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: x as f32 * SMALL_BLOCK_SIZE.0
-                                + P2_BOARD.0
-                                + P2_BOARD.2
-                                + INFO_BOX_MARGIN.0,
-                            y: y as f32 * SMALL_BLOCK_SIZE.1 + P2_BOARD.1 + INFO_BOX_MARGIN.1,
-                        },),
-                    )?
+    /// Starts the hosted game if an opponent has connected
+    fn accept_opponent(&mut self) {
+        let accepted = match &self.listener {
+            Some(listener) => listener.accept(),
+            None => return,
+        };
+        match accepted {
+            Ok((stream, _)) => {
+                let setup = MatchSetup {
+                    seed: rand::random(),
+                    mode: GameMode::Versus,
+                    init_level: self.init_level,
+                    netcode: self.config.online.netcode,
+                    rules: self.config.rules.clone(),
+                };
+                match netplay::host(stream, &setup) {
+                    Ok(connection) => {
+                        let session = netplay::start_session(&setup, connection, 0);
+                        self.start_online(session, &setup);
+                    }
+                    // keep waiting for someone else
+                    Err(e) => self.message = Some(e),
                 }
             }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+            Err(e) => {
+                self.set_scene(Scene::Online);
+                self.message = Some(format!("Stopped hosting: {}", e));
+            }
+        }
+    }
+
+    /// Joins the game hosted at the configured address
+    fn join_online(&mut self) {
+        let address = self.config.online.address.clone();
+        let joined = connect(&address).and_then(netplay::join);
+        match joined {
+            Ok((connection, setup)) => {
+                let session = netplay::start_session(&setup, connection, 1);
+                self.start_online(session, &setup);
+            }
+            Err(e) => self.message = Some(format!("Couldn't join {}: {}", address, e)),
         }
+    }
 
-        // draw saved pieces
-        let [p1_saved_piece, p2_saved_piece] = self.game_state.get_saved_pieces();
+    fn start_online(&mut self, session: Box<dyn Session>, setup: &MatchSetup) {
+        self.game_state = Some(setup.create_game());
+        self.series = None;
+        self.seats.clear();
+        self.start_records();
+        // both players start right away to stay in step
+        self.countdown = 0;
+        self.set_scene(Scene::Game);
+        self.session = Some(session);
+        self.message = None;
+        self.broadcast_game();
+    }
 
-        for y in 0..p1_saved_piece.len() {
-            for x in 0..p1_saved_piece[y].len() {
-                if p1_saved_piece[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.small_block_palatte[p1_saved_piece[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: x as f32 * SMALL_BLOCK_SIZE.0 + P1_BOARD.0 - INFO_BOX.0
-                                + INFO_BOX_MARGIN.0,
-                            y: y as f32 * SMALL_BLOCK_SIZE.1 + P1_BOARD.1 + INFO_BOX_MARGIN.1,
-                        },),
-                    )?
+    /// Connects to the lobby server at the configured address
+    fn enter_lobby(&mut self) {
+        let address = self.config.online.lobby_address.clone();
+        match connect(&address).and_then(lobby::Client::new) {
+            Ok(client) => {
+                self.set_scene(Scene::Lobby);
+                self.lobby = Some(Rc::new(RefCell::new(client)));
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("Couldn't reach the lobby {}: {}", address, e)),
+        }
+    }
+
+    /// Sends a request to the lobby server
+    fn lobby_request(&mut self, request: Request) {
+        let result = match &self.lobby {
+            Some(lobby) => lobby.borrow_mut().request(&request),
+            None => return,
+        };
+        if let Err(e) = result {
+            self.set_scene(Scene::Online);
+            self.message = Some(e);
+        }
+    }
+
+    /// The settings of the room with a change made by the owner
+    fn room_settings(&self, step: i32) -> Option<lobby::Settings> {
+        let room = self.room.as_ref().filter(|room| room.seat == 0)?;
+        let mut settings = room.settings.clone();
+        match self.cursor {
+            1 => {
+                settings.mode = match settings.mode {
+                    GameMode::Versus => GameMode::Marathon,
+                    GameMode::Marathon => GameMode::Versus,
                 }
             }
+            2 => {
+                let levels = settings.rules.levels() as i32;
+                settings.init_level =
+                    ((settings.init_level as i32 + step + levels) % levels) as usize;
+            }
+            _ => return None,
         }
+        Some(settings)
+    }
 
-        for y in 0..p2_saved_piece.len() {
-            for x in 0..p2_saved_piece[y].len() {
-                if p2_saved_piece[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.small_block_palatte[p2_saved_piece[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: x as f32 * SMALL_BLOCK_SIZE.0 + P2_BOARD.0 - INFO_BOX.0
-                                + INFO_BOX_MARGIN.0,
-                            y: y as f32 * SMALL_BLOCK_SIZE.1 + P2_BOARD.1 + INFO_BOX_MARGIN.1,
-                        },),
-                    )?
+    /// Handles the events from the lobby server
+    fn poll_lobby(&mut self) {
+        loop {
+            let event = match &self.lobby {
+                Some(lobby) => lobby.borrow_mut().poll(),
+                None => return,
+            };
+            match event {
+                Ok(Some(Event::Room(room))) => {
+                    if self.scene == Scene::Lobby {
+                        self.set_scene(Scene::Room);
+                        self.message = None;
+                    }
+                    self.room = Some(room);
+                }
+                Ok(Some(Event::Left)) => {
+                    self.room = None;
+                    self.set_scene(Scene::Lobby);
                 }
+                Ok(Some(Event::Error(e))) => self.message = Some(e),
+                Ok(Some(Event::Start { player, setup })) => {
+                    let relay = Relay::new(self.lobby.clone().expect("connected to the lobby"));
+                    let session = netplay::start_session(&setup, relay, player);
+                    self.start_online(session, &setup);
+                }
+                // the session notices it through the relay
+                Ok(Some(Event::OpponentLeft)) => (),
+                Ok(None) => return,
+                Err(e) => {
+                    self.set_scene(Scene::Online);
+                    self.message = Some(e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Where an online game goes back to when it ends
+    fn online_scene(&self) -> Scene {
+        if self.room.is_some() {
+            Scene::Room
+        } else {
+            Scene::Online
+        }
+    }
+
+    /// Watches the broadcast at the configured address
+    fn watch_online(&mut self) {
+        let address = self.config.online.watch_address.clone();
+        match connect(&address).and_then(Spectator::new) {
+            Ok(spectator) => {
+                // nothing is shown until the broadcast sends its game
+                self.game_state = None;
+                self.series = None;
+                self.seats.clear();
+                self.countdown = 0;
+                self.set_scene(Scene::Game);
+                self.spectator = Some(spectator);
+                self.message = None;
             }
+            Err(e) => self.message = Some(format!("Couldn't watch {}: {}", address, e)),
         }
-        let boards = self.game_state.get_boards();
-        let p1_board = boards[0];
-        let p2_board = boards[1];
-
-        // draw blocks
-        for y in 0..(p1_board.len() - 4) {
-            for x in 0..p1_board[y].len() {
-                if p1_board[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.block_palatte[p1_board[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: P1_BOARD.0 + (x as f32) * BLOCK_SIZE.0,
-                            y: P1_BOARD.1 + P1_BOARD.3 - ((y as f32) + 1.0) * BLOCK_SIZE.1,
-                        },),
-                    )
-                    .expect("msg");
+    }
+
+    /// Plays the ticks received from the broadcast, switching between the game
+    /// and the results as games end and start
+    fn watch_tick(&mut self) {
+        let spectator = match &mut self.spectator {
+            Some(spectator) => spectator,
+            None => return,
+        };
+        if let Err(e) = spectator.update(&mut self.game_state) {
+            self.set_scene(Scene::Online);
+            self.message = Some(e);
+            return;
+        }
+        let tick = self.game_state.as_ref().map_or(0, Game::tick);
+        self.confirm_ticks(tick);
+        let over = self.game_state.as_ref().is_some_and(Game::is_over);
+        if over && self.scene == Scene::Game {
+            self.set_scene(Scene::Results);
+        } else if !over && self.scene == Scene::Results {
+            self.set_scene(Scene::Game);
+        }
+    }
+
+    /// Advances the game one tick
+    fn tick(&mut self) {
+        if self.spectator.is_some() {
+            self.watch_tick();
+            return;
+        }
+        if self.scene != Scene::Game {
+            return;
+        }
+        let game = match &mut self.game_state {
+            Some(game) => game,
+            None => return,
+        };
+        // a predicted game over might still be rolled back
+        let confirmed = self.session.as_ref().is_none_or(|s| s.confirmed());
+        if game.is_over() && confirmed {
+            if let Some(series) = &mut self.series {
+                series.record(game);
+            }
+            let winner = game.get_winner();
+            self.set_scene(Scene::Results);
+            if self.room.is_some() {
+                self.lobby_request(Request::Finished { winner });
+            }
+            return;
+        }
+        if self.countdown > 0 {
+            if self.countdown.is_multiple_of(TICK_RATE) {
+                self.sounds.play(Sound::Countdown);
+            }
+            self.countdown -= 1;
+            return;
+        }
+        let actions = self.auto_repeat.update(Instant::now());
+        for &(_, action) in &actions {
+            if let Some(sound) = Sound::of_action(action) {
+                self.sounds.play(sound);
+            }
+        }
+        let result = match &mut self.session {
+            Some(session) => {
+                for (_, action) in actions {
+                    session.perform(action);
+                }
+                session.update(game)
+            }
+            None => {
+                for (player, action) in actions {
+                    game.perform(player, action);
                 }
+                game.update();
+                Ok(())
             }
+        };
+        if let Err(e) = result {
+            self.set_scene(self.online_scene());
+            self.message = Some(e);
+            return;
         }
+        // ticks that might still be rolled back are held back
+        let confirmed = self
+            .session
+            .as_ref()
+            .map_or(game.tick(), |session| session.confirmed_tick());
+        self.confirm_ticks(confirmed);
+    }
+
+    /// Starts the animations, stats and replay of the current game over
+    fn start_records(&mut self) {
+        self.animations.clear();
+        self.stats = self.game_state.as_ref().map(Stats::new);
+        self.replay = self.game_state.as_ref().map(Replay::new);
+    }
 
-        for y in 0..(p2_board.len() - 4) {
-            for x in 0..p2_board[y].len() {
-                if p2_board[y][x] > 0 {
-                    graphics::draw(
-                        ctx,
-                        &self.block_palatte[p2_board[y][x] as usize - 1],
-                        (ggez::mint::Point2 {
-                            x: P2_BOARD.0 + (x as f32) * BLOCK_SIZE.0,
-                            y: P2_BOARD.1 + P2_BOARD.3 - ((y as f32) + 1.0) * BLOCK_SIZE.1,
-                        },),
-                    )
-                    .expect("msg");
+    /// Takes the events and actions of the ticks before `before`, which can't be
+    /// rolled back anymore. The events start animations and sounds and are
+    /// counted in the stats, the actions go to the broadcast and the replay.
+    fn confirm_ticks(&mut self, before: u32) {
+        // spectators see new games start without being told
+        if self
+            .stats
+            .as_ref()
+            .is_none_or(|stats| stats.tick() > before)
+        {
+            self.start_records();
+        }
+        let game = match &mut self.game_state {
+            Some(game) => game,
+            None => return,
+        };
+        let performed = game.take_performed(before);
+        if let Some(broadcast) = &mut self.broadcast {
+            // only the games played here are broadcast
+            if self.spectator.is_none() {
+                broadcast.update(&performed, before);
+            }
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.record(&performed, before);
+        }
+        let events = game.take_events(before);
+        if let Some(stats) = &mut self.stats {
+            stats.update(before, &events);
+        }
+        if self.config.visuals.animations {
+            self.animations.update(&events);
+        }
+        self.sounds.play_events(&events);
+    }
+
+    /// Saves the replay of the last game to the replays directory
+    fn save_replay(&mut self) {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => return,
+        };
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = Path::new(REPLAY_DIR).join(format!("replay-{}.json", seconds));
+        let saved = fs::create_dir_all(REPLAY_DIR)
+            .map_err(|e| format!("Couldn't create {}: {}", REPLAY_DIR, e))
+            .and_then(|()| replay.save(&path));
+        self.message = Some(match saved {
+            Ok(()) => format!("Saved to {}", path.display()),
+            Err(e) => e,
+        });
+    }
+
+    /// Exports the series as csv and json to the series directory
+    fn export_series(&mut self) {
+        let series = match &self.series {
+            Some(series) => series,
+            None => return,
+        };
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = Path::new(SERIES_DIR).join(format!("series-{}", seconds));
+        let saved = fs::create_dir_all(SERIES_DIR)
+            .map_err(|e| format!("Couldn't create {}: {}", SERIES_DIR, e))
+            .and_then(|()| series.save(&path.with_extension("csv")))
+            .and_then(|()| series.save(&path.with_extension("json")));
+        self.message = Some(match saved {
+            Ok(()) => format!("Saved to {}.csv and .json", path.display()),
+            Err(e) => e,
+        });
+    }
+
+    /// The label of a player of the current game, the participant's number in a series
+    fn player_name(&self, player: usize) -> String {
+        format!("P{}", self.seats.get(player).copied().unwrap_or(player) + 1)
+    }
+
+    /// Display name of a players controller
+    fn controller_name(&self, player: usize) -> String {
+        match self.controllers[player].map(|index| &self.ais[index]) {
+            Some(Controller::AI(path)) => format!(
+                "AI ({})",
+                path.file_name()
+                    .map_or(String::new(), |name| name.to_string_lossy().to_string())
+            ),
+            Some(Controller::Bot(bot)) if Bot::ALL.contains(bot) => bot.name().to_string(),
+            Some(Controller::Bot(bot)) => format!("{} (own weights)", bot.name()),
+            // only the first players have keyboard bindings
+            Some(Controller::Keyboard) | None if player < PLAYER_AMOUNT => "Keyboard".to_string(),
+            Some(Controller::Keyboard) | None => "Gamepad".to_string(),
+        }
+    }
+
+    /// The items of the menu in the current scene
+    fn menu_items(&self) -> Vec<String> {
+        match self.scene {
+            Scene::Menu => {
+                let mut items: Vec<String> =
+                    MAIN_MENU.iter().map(|item| item.to_string()).collect();
+                items.push(format!("< Theme: {} >", self.themes[self.theme].name));
+                items.push("Quit".to_string());
+                items
+            }
+            Scene::ModeSelect => vec![
+                format!("< Mode: {} >", self.mode.name()),
+                format!("< Start level: {} >", self.init_level),
+                format!("< Players: {} >", self.controllers.len()),
+                match self.first_to {
+                    0 => "< Series: Off >".to_string(),
+                    wins => format!("< Series: First to {} >", wins),
+                },
+                format!(
+                    "< Swap sides: {} >",
+                    if self.swap_sides { "On" } else { "Off" }
+                ),
+                "Continue".to_string(),
+            ],
+            Scene::PlayerSetup => {
+                let mut items: Vec<String> = (0..self.controllers.len())
+                    .map(|p| format!("< Player {}: {} >", p + 1, self.controller_name(p)))
+                    .collect();
+                items.push("Start".to_string());
+                items
+            }
+            Scene::Controls => {
+                let mut items = vec![format!("< Player {} >", self.controls_player + 1)];
+                for action in &Action::ALL {
+                    let keys: Vec<String> = self
+                        .keymap
+                        .keys(self.controls_player, *action)
+                        .into_iter()
+                        .map(key_name)
+                        .collect();
+                    let pad_inputs: Vec<String> = self
+                        .keymap
+                        .pad_inputs(*action)
+                        .into_iter()
+                        .map(pad_input_name)
+                        .collect();
+                    items.push(format!(
+                        "{}: {} | {}",
+                        action.name(),
+                        keys.join(", "),
+                        pad_inputs.join(", ")
+                    ));
+                }
+                for (i, pad) in self.gamepads.iter().enumerate() {
+                    let player = match pad.player {
+                        Some(player) => format!("Player {}", player + 1),
+                        None => "Unassigned".to_string(),
+                    };
+                    items.push(format!("< Gamepad {} ({}): {} >", i + 1, pad.name, player));
+                }
+                items.push("Reset to defaults".to_string());
+                items.push("Save".to_string());
+                items
+            }
+            Scene::Online => vec![
+                format!("< Start level: {} >", self.init_level),
+                format!("Host on port {}", self.config.online.port),
+                format!("Join {}", self.config.online.address),
+                format!("Watch {}", self.config.online.watch_address),
+                format!("Lobby {}", self.config.online.lobby_address),
+            ],
+            Scene::Lobby => vec![
+                "Create room".to_string(),
+                format!("Join room: {}_", self.room_code),
+            ],
+            Scene::Room => match &self.room {
+                Some(room) => {
+                    let ready = if room.ready.get(room.seat) == Some(&true) {
+                        "Not ready"
+                    } else {
+                        "Ready"
+                    };
+                    vec![
+                        ready.to_string(),
+                        format!("< Mode: {} >", room.settings.mode.name()),
+                        format!("< Start level: {} >", room.settings.init_level),
+                        "Leave".to_string(),
+                    ]
+                }
+                None => vec!["Leave".to_string()],
+            },
+            Scene::Hosting => vec!["Cancel".to_string()],
+            Scene::Results => {
+                let next = match &self.series {
+                    Some(series) if series.winner().is_some() => "New series",
+                    Some(_) => "Next game",
+                    None => "Rematch",
+                };
+                let mut items = vec![next.to_string(), "Save replay".to_string()];
+                if self.series.is_some() {
+                    items.push("Export series".to_string());
                 }
+                items.push("Main menu".to_string());
+                items
             }
+            Scene::Game => Vec::new(),
         }
+    }
 
-        // draw attack meters
-        let [p1_meter, p2_meter] = self.game_state.get_attackbars();
+    /// Handles left/right on a menu item, `step` is -1 or 1
+    fn change_setting(&mut self, ctx: &mut Context, step: i32) {
+        match (self.scene, self.cursor) {
+            (Scene::Menu, THEME_ITEM) => {
+                let themes = self.themes.len() as i32;
+                self.theme = ((self.theme as i32 + step + themes) % themes) as usize;
+                let (skin, error) = Skin::new(ctx, &self.themes[self.theme]);
+                self.skin = skin;
+                self.board_batches.clear();
+                self.message = error;
+            }
+            (Scene::ModeSelect, 0) => {
+                self.mode = match self.mode {
+                    GameMode::Versus => GameMode::Marathon,
+                    GameMode::Marathon => GameMode::Versus,
+                }
+            }
+            (Scene::Room, _) => {
+                if let Some(settings) = self.room_settings(step) {
+                    self.lobby_request(Request::Configure(settings));
+                }
+            }
+            (Scene::ModeSelect, 1) | (Scene::Online, 0) => {
+                let levels = self.config.rules.levels() as i32;
+                self.init_level = ((self.init_level as i32 + step + levels) % levels) as usize;
+            }
+            (Scene::ModeSelect, 2) => {
+                let options = (MAX_PLAYERS - PLAYER_AMOUNT + 1) as i32;
+                let current = (self.controllers.len() - PLAYER_AMOUNT) as i32;
+                let next = (current + step + options) % options;
+                self.controllers.resize(next as usize + PLAYER_AMOUNT, None);
+            }
+            (Scene::ModeSelect, 3) => {
+                let options = MAX_FIRST_TO as i32 + 1;
+                self.first_to = ((self.first_to as i32 + step + options) % options) as u32;
+            }
+            (Scene::ModeSelect, 4) => self.swap_sides = !self.swap_sides,
+            (Scene::PlayerSetup, p) if p < self.controllers.len() => {
+                // option 0 is the keyboard, the rest are the bots and ai-scripts
+                let options = self.ais.len() as i32 + 1;
+                let current = self.controllers[p].map_or(0, |index| index as i32 + 1);
+                let next = (current + step + options) % options;
+                self.controllers[p] = if next == 0 {
+                    None
+                } else {
+                    Some(next as usize - 1)
+                };
+                self.message = None;
+            }
+            (Scene::Controls, 0) => {
+                let players = PLAYER_AMOUNT as i32;
+                self.controls_player =
+                    ((self.controls_player as i32 + step + players) % players) as usize;
+            }
+            (Scene::Controls, c) if self.gamepad_row(c).is_some() => {
+                // option 0 is unassigned, the rest are the player slots
+                let pad = &mut self.gamepads[c - Action::ALL.len() - 1];
+                let options = MAX_PLAYERS as i32 + 1;
+                let current = pad.player.map_or(0, |player| player as i32 + 1);
+                let next = (current + step + options) % options;
+                pad.player = if next == 0 {
+                    None
+                } else {
+                    Some(next as usize - 1)
+                };
+            }
+            _ => (),
+        }
+    }
+
+    /// Handles enter on the selected menu item
+    fn select(&mut self, ctx: &mut Context) {
+        match (self.scene, self.cursor) {
+            (Scene::Menu, 0) => self.set_scene(Scene::ModeSelect),
+            (Scene::Menu, 1) => self.set_scene(Scene::Online),
+            (Scene::Menu, 2) => self.set_scene(Scene::Controls),
+            (Scene::Menu, THEME_ITEM) => self.change_setting(ctx, 1),
+            (Scene::Menu, _) => event::quit(ctx),
+            (Scene::ModeSelect, 5) => {
+                self.scan_ai_libraries();
+                self.set_scene(Scene::PlayerSetup);
+            }
+            (Scene::ModeSelect, _) => self.change_setting(ctx, 1),
+            (Scene::PlayerSetup, p) if p == self.controllers.len() => self.start_game(),
+            (Scene::PlayerSetup, _) => self.change_setting(ctx, 1),
+            (Scene::Controls, 0) => self.change_setting(ctx, 1),
+            (Scene::Controls, c) if c <= Action::ALL.len() => {
+                self.rebinding = true;
+                self.message = Some(format!(
+                    "Press a key for {} (Escape to cancel)",
+                    Action::ALL[c - 1].name()
+                ));
+            }
+            (Scene::Controls, c) if self.gamepad_row(c).is_some() => self.change_setting(ctx, 1),
+            (Scene::Controls, c) if c == Action::ALL.len() + self.gamepads.len() + 1 => {
+                self.keymap = KeyMap::default();
+                self.message = None;
+            }
+            (Scene::Controls, _) => self.save_keymap(),
+            (Scene::Online, 0) => self.change_setting(ctx, 1),
+            (Scene::Online, 1) => self.host_online(),
+            (Scene::Online, 2) => self.join_online(),
+            (Scene::Online, 3) => self.watch_online(),
+            (Scene::Online, _) => self.enter_lobby(),
+            (Scene::Lobby, 0) => self.lobby_request(Request::Create(lobby::Settings {
+                mode: self.mode,
+                init_level: self.init_level,
+                netcode: self.config.online.netcode,
+                rules: self.config.rules.clone(),
+            })),
+            (Scene::Lobby, _) => {
+                let code = self.room_code.clone();
+                self.lobby_request(Request::Join { code });
+            }
+            (Scene::Room, 0) => {
+                let ready = self
+                    .room
+                    .as_ref()
+                    .is_some_and(|room| room.ready.get(room.seat) == Some(&true));
+                self.lobby_request(Request::Ready(!ready));
+            }
+            (Scene::Room, 1) | (Scene::Room, 2) => self.change_setting(ctx, 1),
+            (Scene::Room, _) => self.lobby_request(Request::Leave),
+            (Scene::Hosting, _) => self.set_scene(Scene::Online),
+            (Scene::Results, 0) if self.session.is_some() || self.spectator.is_some() => {
+                self.set_scene(self.online_scene())
+            }
+            (Scene::Results, 0) => self.restart_game(),
+            (Scene::Results, 1) => self.save_replay(),
+            (Scene::Results, 2) if self.series.is_some() => self.export_series(),
+            (Scene::Results, _) => self.set_scene(Scene::Menu),
+            (Scene::Game, _) => (),
+        }
+    }
+
+    /// The gamepad shown on a row of the controls scene
+    fn gamepad_row(&self, cursor: usize) -> Option<usize> {
+        let first = Action::ALL.len() + 1;
+        if cursor >= first && cursor < first + self.gamepads.len() {
+            Some(cursor - first)
+        } else {
+            None
+        }
+    }
 
-        let rectangle = Mesh::new_rectangle(
+    /// Goes back to the previous scene
+    fn back(&mut self, ctx: &mut Context) {
+        match self.scene {
+            Scene::Menu => event::quit(ctx),
+            Scene::ModeSelect | Scene::Controls | Scene::Online | Scene::Game | Scene::Results => {
+                self.set_scene(Scene::Menu)
+            }
+            Scene::Hosting | Scene::Lobby => self.set_scene(Scene::Online),
+            Scene::Room => self.lobby_request(Request::Leave),
+            Scene::PlayerSetup => self.set_scene(Scene::ModeSelect),
+        }
+    }
+
+    /// Draws text centered on a point
+    fn draw_text(
+        &self,
+        ctx: &mut Context,
+        text: &str,
+        scale: f32,
+        color: Color,
+        center: (f32, f32),
+    ) -> GameResult {
+        let fragment = TextFragment::new(text)
+            .font(self.font)
+            .scale(Scale { x: scale, y: scale })
+            .color(color);
+        let text = Text::new(fragment);
+        let dimensions = text.dimensions(ctx);
+
+        self.draw_call(
             ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, ATTACK_METER.0 as i32, ATTACK_METER.1 as i32),
-            PALETTE[7],
-        )?;
+            &text,
+            (ggez::mint::Point2 {
+                x: center.0 - (dimensions.0 as f32) / 2.0,
+                y: center.1 - (dimensions.1 as f32) / 2.0,
+            },),
+        )
+    }
 
-        for i in 1..(p1_meter + 1) {
-            graphics::draw(
+    /// Draws a title with the menu items of the current scene below it
+    fn draw_menu(&self, ctx: &mut Context, title: &str) -> GameResult {
+        let screen_size = screen_size(ctx);
+        let ui = ui_scale(screen_size);
+        let top = MENU_TOP * ui;
+        self.draw_text(
+            ctx,
+            title,
+            MENU_TITLE_SCALE * ui,
+            graphics::WHITE,
+            (screen_size.0 / 2.0, top / 2.0),
+        )?;
+        let items = self.menu_items();
+        // long menus are squeezed to fit the window
+        let spacing =
+            (MENU_ITEM_SPACING * ui).min((screen_size.1 - top) / (items.len() as f32 + 1.5));
+        let scale = MENU_ITEM_SCALE * spacing / MENU_ITEM_SPACING;
+        for (i, item) in items.iter().enumerate() {
+            let color = if i == self.cursor {
+                MENU_SELECTED_COLOR
+            } else {
+                graphics::WHITE
+            };
+            self.draw_text(
                 ctx,
-                &rectangle,
-                (ggez::mint::Point2 {
-                    x: P1_BOARD.0 - ATTACK_METER.0,
-                    y: P1_BOARD.1 + P1_BOARD.3 - i as f32 * ATTACK_METER.1,
-                },),
+                item,
+                scale,
+                color,
+                (screen_size.0 / 2.0, top + i as f32 * spacing),
             )?;
         }
-
-        for i in 1..(p2_meter + 1) {
-            graphics::draw(
+        if let Some(message) = &self.message {
+            self.draw_text(
                 ctx,
-                &rectangle,
-                (ggez::mint::Point2 {
-                    x: P2_BOARD.0 - ATTACK_METER.0,
-                    y: P2_BOARD.1 + P2_BOARD.3 - i as f32 * ATTACK_METER.1,
-                },),
+                message,
+                MENU_ITEM_SCALE * ui / 1.5,
+                self.skin.palette[4],
+                (
+                    screen_size.0 / 2.0,
+                    top + (items.len() as f32 + 0.5) * spacing,
+                ),
             )?;
         }
+        Ok(())
+    }
 
-        // draw grids
-        graphics::draw(
+    /// Draws who is in the room and the score of the series below the title
+    fn draw_room(&self, ctx: &mut Context) -> GameResult {
+        let room = match &self.room {
+            Some(room) => room,
+            None => return Ok(()),
+        };
+        let screen_size = screen_size(ctx);
+        let ui = ui_scale(screen_size);
+        let players: Vec<String> = room
+            .ready
+            .iter()
+            .enumerate()
+            .map(|(seat, ready)| {
+                let name = if seat == room.seat {
+                    "You".to_string()
+                } else {
+                    format!("P{}", seat + 1)
+                };
+                format!("{}{}", name, if *ready { " (ready)" } else { "" })
+            })
+            .collect();
+        let status = format!(
+            "{}   Series {} - {}, {} draws",
+            players.join(", "),
+            room.wins[0],
+            room.wins[1],
+            room.draws
+        );
+        self.draw_text(
             ctx,
-            &self.grid_mesh,
-            (ggez::mint::Point2 {
-                x: P1_BOARD.0,
-                y: P1_BOARD.1,
-            },),
+            &status,
+            MENU_ITEM_SCALE * ui / 1.5,
+            graphics::WHITE,
+            (screen_size.0 / 2.0, MENU_TOP * ui * 0.8),
+        )
+    }
+
+    /// Draws the seconds left before the game starts
+    fn draw_countdown(&self, ctx: &mut Context) -> GameResult {
+        if self.countdown == 0 {
+            return Ok(());
+        }
+        let screen_size = screen_size(ctx);
+        let seconds = self.countdown.div_ceil(TICK_RATE);
+        self.draw_text(
+            ctx,
+            &seconds.to_string(),
+            150.0 * ui_scale(screen_size),
+            graphics::WHITE,
+            (screen_size.0 / 2.0, screen_size.1 / 2.0),
+        )
+    }
+
+    /// Draws the tally of the series at the top of the screen
+    fn draw_series(&self, ctx: &mut Context) -> GameResult {
+        let series = match &self.series {
+            Some(series) => series,
+            None => return Ok(()),
+        };
+        let screen_size = screen_size(ctx);
+        let ui = ui_scale(screen_size);
+        self.draw_text(
+            ctx,
+            &series_line(&series.wins(), series.first_to()),
+            LABEL_SCALE * ui,
+            graphics::WHITE,
+            (screen_size.0 / 2.0, SERIES_TOP * ui),
+        )
+    }
+
+    /// Draws the winner, the stats of every player and the results menu on top
+    /// of the shaded boards
+    fn draw_results(&self, ctx: &mut Context) -> GameResult {
+        let screen_size = screen_size(ctx);
+        let ui = ui_scale(screen_size);
+        let game = match &self.game_state {
+            Some(game) => game,
+            None => return Ok(()),
+        };
+        let shade = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, screen_size.0, screen_size.1),
+            RESULTS_SHADE,
         )?;
-        graphics::draw(
+        self.draw_call(ctx, &shade, DrawParam::default())?;
+
+        let winner = game.get_winner();
+        let result = match (winner, &self.session) {
+            (Some(player), Some(session)) if player == session.local_player() => {
+                "You win!".to_string()
+            }
+            (Some(_), Some(_)) => "You lose!".to_string(),
+            (Some(player), None) => format!("{} wins!", self.player_name(player)),
+            (None, _) => "Draw!".to_string(),
+        };
+        let center = screen_size.0 / 2.0;
+        self.draw_text(
             ctx,
-            &self.grid_mesh,
-            (ggez::mint::Point2 {
-                x: P2_BOARD.0,
-                y: P2_BOARD.1,
-            },),
+            &result,
+            RESULTS_TITLE_SCALE * ui,
+            graphics::WHITE,
+            (center, RESULTS_TITLE_TOP * ui),
         )?;
 
-        // draw text
-        let scores = self.game_state.get_scores();
-        let p1_score = TextFragment::new(scores[0].to_string())
-            .font(self.font)
-            .scale(Scale { x: 25.0, y: 25.0 });
-        let p1_score_text = Text::new(p1_score);
-        let p1_dimensions = p1_score_text.dimensions(ctx);
-        let p2_score = TextFragment::new(scores[1].to_string())
-            .font(self.font)
-            .scale(Scale { x: 25.0, y: 25.0 });
-        let p2_score_text = Text::new(p2_score);
-        let p2_dimensions = p1_score_text.dimensions(ctx);
+        // a column of stats for every player, the winner's is highlighted
+        if let Some(stats) = &self.stats {
+            let players = stats.players().len();
+            let width = RESULTS_NAME_WIDTH + players as f32 * RESULTS_COLUMN_WIDTH;
+            let left = center - width / 2.0 * ui;
+            let column = |i: usize| {
+                left + (RESULTS_NAME_WIDTH + (i as f32 + 0.5) * RESULTS_COLUMN_WIDTH) * ui
+            };
+            let color = |i: usize| {
+                if Some(i) == winner {
+                    MENU_SELECTED_COLOR
+                } else {
+                    graphics::WHITE
+                }
+            };
+            let scale = RESULTS_ROW_SCALE * ui;
+            let row_y = |row: usize| (RESULTS_TABLE_TOP + row as f32 * RESULTS_ROW_SPACING) * ui;
+            for i in 0..players {
+                let name = self.player_name(i);
+                self.draw_text(ctx, &name, scale, color(i), (column(i), row_y(0)))?;
+            }
+            for (row, (name, values)) in results_rows(stats.players()).iter().enumerate() {
+                let name_center = (left + RESULTS_NAME_WIDTH / 2.0 * ui, row_y(row + 1));
+                self.draw_text(ctx, name, scale, graphics::WHITE, name_center)?;
+                for (i, value) in values.iter().enumerate() {
+                    self.draw_text(ctx, value, scale, color(i), (column(i), row_y(row + 1)))?;
+                }
+            }
+        }
 
-        graphics::draw(
+        let top = RESULTS_MENU_TOP * ui;
+        let items = self.menu_items();
+        for (i, item) in items.iter().enumerate() {
+            let color = if i == self.cursor {
+                MENU_SELECTED_COLOR
+            } else {
+                graphics::WHITE
+            };
+            self.draw_text(
+                ctx,
+                item,
+                MENU_ITEM_SCALE * ui,
+                color,
+                (center, top + i as f32 * MENU_ITEM_SPACING * ui),
+            )?;
+        }
+        if let Some(message) = &self.message {
+            self.draw_text(
+                ctx,
+                message,
+                MENU_ITEM_SCALE * ui / 1.5,
+                self.skin.palette[4],
+                (
+                    center,
+                    top + (items.len() as f32 - 0.25) * MENU_ITEM_SPACING * ui,
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws something and counts the draw call
+    fn draw_call<D: Drawable>(
+        &self,
+        ctx: &mut Context,
+        drawable: &D,
+        param: impl Into<DrawParam>,
+    ) -> GameResult {
+        self.draw_calls.set(self.draw_calls.get() + 1);
+        graphics::draw(ctx, drawable, param)
+    }
+
+    /// The player whose board is drawn biggest: the local player online, the
+    /// first player not controlled by an ai-script in local games
+    fn focus_player(&self) -> Option<usize> {
+        if self.spectator.is_some() {
+            return None;
+        }
+        match &self.session {
+            Some(session) => Some(session.local_player()),
+            None => self
+                .controllers
+                .iter()
+                .position(Option::is_none)
+                .map(|participant| game_player(&self.seats, participant)),
+        }
+    }
+
+    /// Places the boards of the current game
+    fn layout_game(&self, ctx: &Context) -> Vec<BoardLayout> {
+        let boards = self.game_state.as_ref().map_or(0, Game::player_count);
+        layout_match(screen_size(ctx), boards, self.focus_player())
+    }
+
+    /// Draws the game, moved around while the screen shakes
+    fn draw_game(&mut self, ctx: &mut Context) -> GameResult {
+        let (width, height) = screen_size(ctx);
+        // the screen shakes as far as the biggest board's blocks
+        let block = self
+            .layout_game(ctx)
+            .iter()
+            .map(|layout| layout.block)
+            .fold(0.0, f32::max);
+        let (shake_x, shake_y) = self.animations.shake();
+        graphics::set_screen_coordinates(
             ctx,
-            &p1_score_text,
-            (ggez::mint::Point2 {
-                x: P1_SCORE_PLACEMENT.0 - (p1_dimensions.0 as f32) / 2.0,
-                y: P1_SCORE_PLACEMENT.1 - (p1_dimensions.1 as f32) / 2.0,
-            },),
+            Rect::new(-shake_x * block, -shake_y * block, width, height),
         )?;
+        let result = self.draw_boards(ctx);
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height))?;
+        result
+    }
 
-        graphics::draw(
+    /// Draws the flashes of the pieces that were just placed and the rows that were just cleared
+    fn draw_flashes(
+        &self,
+        ctx: &mut Context,
+        flash: &Mesh,
+        player: usize,
+        layout: &BoardLayout,
+    ) -> GameResult {
+        let visible =
+            |x: i32, y: i32| (0..GRID_SIZE.0).contains(&x) && (0..GRID_SIZE.1).contains(&y);
+        for (blocks, progress) in self.animations.locked_pieces(player) {
+            for &[x, y] in blocks.iter().filter(|[x, y]| visible(*x, *y)) {
+                let (cell_x, cell_y) = layout.cell(x as usize, y as usize);
+                self.draw_call(
+                    ctx,
+                    flash,
+                    DrawParam::default()
+                        .dest([cell_x, cell_y])
+                        .scale([layout.block, layout.block])
+                        .color(Color::new(1.0, 1.0, 1.0, 0.6 * (1.0 - progress))),
+                )?;
+            }
+        }
+        // cleared rows fade and shrink towards the middle of the board
+        let (board_x, _, board_w, _) = layout.board();
+        for (row, progress) in self.animations.cleared_rows(player) {
+            if !visible(0, row as i32) {
+                continue;
+            }
+            let width = board_w * (1.0 - progress);
+            let (_, cell_y) = layout.cell(0, row);
+            self.draw_call(
+                ctx,
+                flash,
+                DrawParam::default()
+                    .dest([board_x + (board_w - width) / 2.0, cell_y])
+                    .scale([width, layout.block])
+                    .color(Color::new(1.0, 1.0, 1.0, 0.8 * (1.0 - progress))),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Batches the boxes, blocks and attack meter of a board
+    fn build_board(&self, ctx: &mut Context, frame: &BoardFrame) -> GameResult<BuiltBatch> {
+        let layout = &frame.layout;
+        let mut batch = self.skin.batch();
+        for &(x, y, w, h) in &[layout.board(), layout.next_box(), layout.hold_box()] {
+            batch.rectangle(Rect::new(x, y, w, h), self.skin.board_background);
+        }
+
+        // the next and saved pieces use small blocks
+        let size = layout.small_block();
+        for (piece, info_box) in &[
+            (frame.next_piece, layout.next_box()),
+            (frame.saved_piece, layout.hold_box()),
+        ] {
+            for (y, row) in piece.iter().enumerate() {
+                for (x, &block) in row.iter().enumerate() {
+                    if block > 0 {
+                        let dest = [
+                            info_box.0 + (x as f32 + 1.0) * size,
+                            info_box.1 + (y as f32 + 1.0) * size,
+                        ];
+                        self.skin.add_block(&mut batch, block, dest, size);
+                    }
+                }
+            }
+        }
+
+        // the top four rows are hidden. The stack is drawn lower while garbage
+        // rises, the blocks below the board aren't drawn.
+        for (y, row) in frame.board.iter().take(GRID_SIZE.1 as usize).enumerate() {
+            for (x, &block) in row.iter().enumerate() {
+                if block > 0 && y as f32 >= frame.stack_offset {
+                    let (cell_x, cell_y) = layout.cell(x, y);
+                    let cell_y = cell_y + frame.stack_offset * layout.block;
+                    self.skin
+                        .add_block(&mut batch, block, [cell_x, cell_y], layout.block);
+                }
+            }
+        }
+
+        let scale = layout.block / BLOCK_SIZE.0;
+        let (board_x, board_y, _, board_h) = layout.board();
+        for j in 1..(frame.meter + 1) {
+            batch.rectangle(
+                Rect::new(
+                    board_x - ATTACK_METER.0 * scale,
+                    board_y + board_h - j as f32 * ATTACK_METER.1 * scale,
+                    ATTACK_METER.0 * scale,
+                    ATTACK_METER.1 * scale,
+                ),
+                self.skin.palette[7],
+            );
+        }
+        batch.build(ctx)
+    }
+
+    /// Draws the boards, pieces, attack meters and scores
+    fn draw_boards(&mut self, ctx: &mut Context) -> GameResult {
+        let game = match &self.game_state {
+            Some(game) => game,
+            None => return Ok(()),
+        };
+        let boards = game.get_boards();
+        let next_pieces = game.get_next_pieces();
+        let saved_pieces = game.get_saved_pieces();
+        let meters = game.get_attackbars();
+        let scores = game.get_scores();
+        let losts = game.get_losts();
+        // who sends garbage to whom is only shown when there is a choice, and
+        // who is who when the participants of a series can swap sides
+        let targets: Vec<Option<usize>> = (0..boards.len()).map(|p| game.target(p)).collect();
+        let show_labels = boards.len() > PLAYER_AMOUNT || !self.seats.is_empty();
+        let focus = self.focus_player();
+        let layouts = self.layout_game(ctx);
+
+        // a white unit square for the flashes
+        let flash = Mesh::new_rectangle(
             ctx,
-            &p2_score_text,
-            (ggez::mint::Point2 {
-                x: P2_SCORE_PLACEMENT.0 - (p2_dimensions.0 as f32) / 2.0,
-                y: P2_SCORE_PLACEMENT.1 - (p2_dimensions.1 as f32) / 2.0,
-            },),
+            DrawMode::fill(),
+            Rect::new(0.0, 0.0, 1.0, 1.0),
+            graphics::WHITE,
         )?;
 
-        // if anyone lost draw
-        let [p1_lost, p2_lost] = self.game_state.get_losts();
+        self.board_batches.truncate(layouts.len());
+        for (i, layout) in layouts.iter().enumerate() {
+            let frame = BoardFrame {
+                layout: *layout,
+                board: boards[i],
+                next_piece: next_pieces[i],
+                saved_piece: saved_pieces[i],
+                meter: meters[i],
+                stack_offset: self.animations.stack_offset(i),
+            };
+            // the batch is kept as long as the board looks the same
+            if self
+                .board_batches
+                .get(i)
+                .is_none_or(|(built, _)| *built != frame)
+            {
+                let batch = self.build_board(ctx, &frame)?;
+                if i < self.board_batches.len() {
+                    self.board_batches[i] = (frame, batch);
+                } else {
+                    self.board_batches.push((frame, batch));
+                }
+            }
+            let batch = &self.board_batches[i].1;
+            self.draw_call(ctx, &batch.shapes, DrawParam::default())?;
+            if let Some(sprites) = &batch.sprites {
+                self.draw_call(ctx, sprites, DrawParam::default())?;
+            }
 
-        if p1_lost {
-            let p2_win = TextFragment::new("P2 wins!")
-                .font(self.font)
-                .scale(Scale { x: 100.0, y: 100.0 });
-            let p2_win_text = Text::new(p2_win);
-            let dimensions = p2_win_text.dimensions(ctx);
+            self.draw_flashes(ctx, &flash, i, layout)?;
 
-            graphics::draw(
+            // draw grid
+            let scale = layout.block / BLOCK_SIZE.0;
+            let (board_x, board_y, _, _) = layout.board();
+            self.draw_call(
                 ctx,
-                &p2_win_text,
-                (ggez::mint::Point2 {
-                    x: SCREEN_SIZE.0 / 2.0 - (dimensions.0 as f32) / 2.0,
-                    y: SCREEN_SIZE.1 / 2.0 - (dimensions.1 as f32) / 2.0,
-                },),
+                &self.skin.grid,
+                DrawParam::default()
+                    .dest([board_x, board_y])
+                    .scale([scale, scale]),
             )?;
-        } else if p2_lost {
-            let p1_win = TextFragment::new("P1 wins!")
-                .font(self.font)
-                .scale(Scale { x: 100.0, y: 100.0 });
-            let p1_win_text = Text::new(p1_win);
-            let dimensions = p1_win_text.dimensions(ctx);
-
-            graphics::draw(
+
+            // draw score
+            self.draw_text(
                 ctx,
-                &p1_win_text,
-                (ggez::mint::Point2 {
-                    x: SCREEN_SIZE.0 / 2.0 - (dimensions.0 as f32) / 2.0,
-                    y: SCREEN_SIZE.1 / 2.0 - (dimensions.1 as f32) / 2.0,
-                },),
+                &scores[i].to_string(),
+                SCORE_SCALE * scale,
+                graphics::WHITE,
+                layout.score_center(),
             )?;
+
+            if let Some(stats) = self.stats.as_ref().and_then(|stats| stats.players().get(i)) {
+                let lines = stat_lines(stats, &self.config.hud);
+                let fragment = TextFragment::new(lines.join("\n"))
+                    .font(self.font)
+                    .scale(Scale::uniform(STATS_SCALE * scale));
+                let (x, y) = layout.stats_corner();
+                self.draw_call(ctx, &Text::new(fragment), ([x, y],))?;
+            }
+
+            if show_labels {
+                let name = self.player_name(i);
+                let (label, color) = match targets[i] {
+                    Some(target) if Some(target) == focus => (
+                        format!("{} > {}", name, self.player_name(target)),
+                        TARGETING_COLOR,
+                    ),
+                    Some(target) => (
+                        format!("{} > {}", name, self.player_name(target)),
+                        graphics::WHITE,
+                    ),
+                    None => (name, graphics::WHITE),
+                };
+                self.draw_text(
+                    ctx,
+                    &label,
+                    LABEL_SCALE * scale,
+                    color,
+                    layout.label_center(),
+                )?;
+            }
+
+            // boards of players that are out are shaded
+            if losts[i] {
+                let (x, y, w, h) = layout.board();
+                self.draw_call(
+                    ctx,
+                    &flash,
+                    DrawParam::default()
+                        .dest([x, y])
+                        .scale([w, h])
+                        .color(OUT_SHADE),
+                )?;
+                self.draw_text(
+                    ctx,
+                    "Out",
+                    OUT_SCALE * scale,
+                    graphics::WHITE,
+                    (x + w / 2.0, y + h / 2.0),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws the frame rate and the draw calls made this frame in the corner
+    fn draw_stats(&self, ctx: &mut Context) -> GameResult {
+        let stats = format!(
+            "{:.0} fps, {} draw calls",
+            timer::fps(ctx),
+            self.draw_calls.get()
+        );
+        let fragment = TextFragment::new(stats)
+            .font(self.font)
+            .scale(Scale { x: 16.0, y: 16.0 });
+        graphics::draw(ctx, &Text::new(fragment), ([5.0, 5.0],))
+    }
+}
+
+impl event::EventHandler for AppState {
+    // update the game logic
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if self.scene == Scene::Hosting {
+            self.accept_opponent();
+        }
+        self.poll_lobby();
+        // the game runs at a fixed tick rate no matter the frame rate
+        while timer::check_update_time(ctx, TICK_RATE) {
+            self.tick();
+        }
+        Ok(())
+    }
+
+    // update the graphics
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        // Clear screen with the background color
+        graphics::clear(ctx, self.skin.background);
+        self.draw_calls.set(0);
+
+        match self.scene {
+            Scene::Menu => self.draw_menu(ctx, "Tetris goes brrr")?,
+            Scene::ModeSelect => self.draw_menu(ctx, "Game mode")?,
+            Scene::PlayerSetup => self.draw_menu(ctx, "Players")?,
+            Scene::Controls => self.draw_menu(ctx, "Controls")?,
+            Scene::Online => self.draw_menu(ctx, "Online")?,
+            Scene::Hosting => self.draw_menu(ctx, "Hosting")?,
+            Scene::Lobby => self.draw_menu(ctx, "Lobby")?,
+            Scene::Room => {
+                let code = self.room.as_ref().map_or("", |room| &room.code);
+                self.draw_menu(ctx, &format!("Room {}", code))?;
+                self.draw_room(ctx)?;
+            }
+            Scene::Game => {
+                self.draw_game(ctx)?;
+                self.draw_countdown(ctx)?;
+                self.draw_series(ctx)?;
+            }
+            Scene::Results => {
+                self.draw_game(ctx)?;
+                self.draw_results(ctx)?;
+                self.draw_series(ctx)?;
+            }
+        }
+
+        if self.show_stats {
+            self.draw_stats(ctx)?;
         }
 
         // present the graphics to the graphics engine
@@ -426,234 +1796,126 @@ impl event::EventHandler for AppState {
         Ok(())
     }
 
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        // keep one unit per pixel so the layout follows the window instead of stretching
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height))
+            .expect("Failed resizing the screen");
+    }
+
     fn key_down_event(
         &mut self,
         ctx: &mut Context,
         keycode: KeyCode,
         _keymods: KeyMods,
-        _repeat: bool,
+        repeat: bool,
     ) {
-        if keycode == KeyCode::R {
-            self.game_state.restart(INIT_LEVEL);
-        } else {
-            self.game_state.key_down(keycode);
+        if keycode == KeyCode::F3 && !self.rebinding {
+            self.show_stats = !self.show_stats;
+            return;
+        }
+        if self.scene == Scene::Game {
+            match keycode {
+                KeyCode::R => self.restart_game(),
+                KeyCode::Escape => self.back(ctx),
+                // held keys are repeated with DAS and ARR instead of the system key repeat
+                _ if repeat => (),
+                _ => self.press(InputSource::Key(keycode), self.keymap.actions(keycode)),
+            }
+            return;
+        }
+        if self.scene == Scene::Lobby {
+            let name = key_name(keycode);
+            if keycode == KeyCode::Back {
+                self.room_code.pop();
+                return;
+            } else if name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+                if self.room_code.len() < lobby::CODE_LENGTH {
+                    self.room_code.push_str(&name);
+                }
+                self.cursor = 1;
+                return;
+            }
+        }
+        if self.rebinding {
+            if keycode == KeyCode::Escape {
+                self.rebinding = false;
+                self.message = self.conflict_message();
+            } else {
+                self.rebind(keycode);
+            }
+            return;
+        }
+        self.menu_key(ctx, keycode);
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        self.auto_repeat.release(InputSource::Key(keycode));
+    }
+
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, btn: Button, id: GamepadId) {
+        let index = self.connect_pad(ctx, id);
+        self.pad_down(ctx, index, PadInput::Button(btn));
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, btn: Button, id: GamepadId) {
+        self.auto_repeat
+            .release(InputSource::Pad(id, PadInput::Button(btn)));
+    }
+
+    fn gamepad_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        let index = self.connect_pad(ctx, id);
+        let pad = &mut self.gamepads[index];
+        let old = pad
+            .axes
+            .iter()
+            .position(|(a, _)| *a == axis)
+            .map(|i| pad.axes.remove(i).1);
+        let new = axis_direction(value);
+        if old == new {
+            if let Some(positive) = new {
+                pad.axes.push((axis, positive));
+            }
+            return;
+        }
+        if let Some(positive) = old {
+            self.auto_repeat
+                .release(InputSource::Pad(id, PadInput::Axis(axis, positive)));
+        }
+        if let Some(positive) = new {
+            self.gamepads[index].axes.push((axis, positive));
+            self.pad_down(ctx, index, PadInput::Axis(axis, positive));
         }
     }
 }
-/// Generates the meshes for the tetromino block
-fn generate_blocks(ctx: &mut Context) -> [Mesh; 15] {
-    [
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[0],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[1],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[2],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[3],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[4],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[5],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[6],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            PALETTE[7],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[0],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[1],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[2],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[3],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[4],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[5],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, BLOCK_SIZE.0 as i32, BLOCK_SIZE.1 as i32),
-            GHOST_PALETTE[6],
-        )
-        .expect("Failed creating blocks"),
-    ]
+/// The size of the window in screen coordinates
+/// The player of the current game a participant of the series plays as
+fn game_player(seats: &[usize], participant: usize) -> usize {
+    seats
+        .iter()
+        .position(|seat| *seat == participant)
+        .unwrap_or(participant)
 }
-/// generates the mesh for the grid-lines
-fn generate_grid_mesh(ctx: &mut Context) -> GameResult<Mesh> {
-    let mut mesh = MeshBuilder::new();
-    for x in 0..(GRID_SIZE.0 + 1) {
-        mesh.line(
-            &[
-                ggez::mint::Point2 {
-                    x: (x as f32) * BLOCK_SIZE.0,
-                    y: 0.0,
-                },
-                ggez::mint::Point2 {
-                    x: (x as f32) * BLOCK_SIZE.0,
-                    y: P1_BOARD.3,
-                },
-            ],
-            GRID_LINE_WIDTH,
-            GRID_COLOR,
-        )
-        .expect("msg");
-    }
-    for y in 0..(GRID_SIZE.1 + 1) {
-        mesh.line(
-            &[
-                ggez::mint::Point2 {
-                    x: 0.0,
-                    y: (y as f32) * BLOCK_SIZE.1,
-                },
-                ggez::mint::Point2 {
-                    x: P1_BOARD.2,
-                    y: (y as f32) * BLOCK_SIZE.1,
-                },
-            ],
-            GRID_LINE_WIDTH,
-            GRID_COLOR,
-        )
-        .expect("msg");
-    }
 
-    mesh.build(ctx)
+fn screen_size(ctx: &Context) -> (f32, f32) {
+    let coordinates = graphics::screen_coordinates(ctx);
+    (coordinates.w, coordinates.h)
 }
 
-/// generates the meshes for the scaled-down tetromino for next_piece and saved_piece
-fn generate_small_blocks(ctx: &mut Context) -> [Mesh; 8] {
-    [
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[0],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[1],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[2],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[3],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[4],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[5],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[6],
-        )
-        .expect("Failed creating blocks"),
-        Mesh::new_rectangle(
-            ctx,
-            DrawMode::fill(),
-            Rect::new_i32(0, 0, SMALL_BLOCK_SIZE.0 as i32, SMALL_BLOCK_SIZE.1 as i32),
-            PALETTE[7],
-        )
-        .expect("Failed creating blocks"),
-    ]
+/// Connects to a host:port, giving up after `CONNECT_TIMEOUT`
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let socket = address
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| "unknown address".to_string())?;
+    TcpStream::connect_timeout(&socket, CONNECT_TIMEOUT).map_err(|e| e.to_string())
 }
+
+#[cfg(test)]
 mod tests {
-    use super::{AppState, SCREEN_SIZE};
+    use super::AppState;
+    use crate::config::Config;
+    use crate::layout::REFERENCE_SIZE;
     use ggez::event::{self, EventHandler};
     use ggez::graphics;
     use ggez::{Context, ContextBuilder, GameResult};
@@ -667,13 +1929,13 @@ mod tests {
             .window_setup(ggez::conf::WindowSetup::default().title("Test goes brrr"))
             .window_mode(
                 ggez::conf::WindowMode::default()
-                    .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1) // Set window dimenstions
+                    .dimensions(REFERENCE_SIZE.0, REFERENCE_SIZE.1) // Set window dimenstions
                     .resizable(true), // Fixate window size
             );
 
         let (contex, event_loop) = &mut context_builder.build().expect("context builder error");
 
-        let state = &mut AppState::new(contex);
+        let state = &mut AppState::new(contex, Config::default(), true);
 
         event::run(contex, event_loop, state);
     }
@@ -0,0 +1,339 @@
+use crate::bots::Bot;
+use crate::game_data::Rules;
+use crate::game_state::{is_ai_library, Controller, GameMode, MAX_PLAYERS, PLAYER_AMOUNT};
+use crate::keymap::{ControlsConfig, KeyMap};
+use crate::netplay::Netcode;
+use crate::theme::{Theme, DEFAULT_THEME};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// Where the configuration is loaded from and saved to
+pub const CONFIG_FILE: &str = "./config.toml";
+
+/// Everything that can be tweaked without recompiling. Every section and field
+/// is optional, missing ones get the default value.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub game: GameConfig,
+    pub rules: Rules,
+    pub visuals: Visuals,
+    pub controls: ControlsConfig,
+    pub online: OnlineConfig,
+    pub audio: AudioConfig,
+    pub hud: HudConfig,
+}
+
+/// The settings preselected in the menus
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub mode: GameMode,
+    pub init_level: usize,
+    /// "keyboard", the id of a bot or the path to an ai-script for every
+    /// player, 2 to 8 entries
+    pub controllers: Vec<String>,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            mode: GameMode::Versus,
+            init_level: 5,
+            controllers: vec![KEYBOARD.to_string(); PLAYER_AMOUNT],
+        }
+    }
+}
+
+const KEYBOARD: &str = "keyboard";
+
+impl GameConfig {
+    /// The bot or ai-script of every player, `None` for keyboard players.
+    /// Fails if the weights of a bot can't be loaded.
+    pub fn ai_controllers(&self) -> Result<Vec<Option<Controller>>, String> {
+        self.controllers
+            .iter()
+            .map(|controller| {
+                if controller == KEYBOARD {
+                    Ok(None)
+                } else {
+                    Controller::from_arg(controller).map(Some)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Where online games are hosted and joined
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OnlineConfig {
+    /// The port a hosted game listens on
+    pub port: u16,
+    /// The host:port of the game to join
+    pub address: String,
+    /// The host's netcode is used by both players
+    pub netcode: Netcode,
+    /// Broadcast every game played to spectators
+    pub broadcast: bool,
+    /// The port spectators connect to
+    pub spectator_port: u16,
+    /// The host:port of the broadcast to watch
+    pub watch_address: String,
+    /// The host:port of the lobby server
+    pub lobby_address: String,
+}
+
+impl Default for OnlineConfig {
+    fn default() -> OnlineConfig {
+        OnlineConfig {
+            port: 7777,
+            address: "127.0.0.1:7777".to_string(),
+            netcode: Netcode::Rollback,
+            broadcast: false,
+            spectator_port: 7778,
+            watch_address: "127.0.0.1:7778".to_string(),
+            lobby_address: "127.0.0.1:7780".to_string(),
+        }
+    }
+}
+
+impl OnlineConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, address) in &[
+            ("address", &self.address),
+            ("watch_address", &self.watch_address),
+            ("lobby_address", &self.lobby_address),
+        ] {
+            match address.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => (),
+                _ => {
+                    return Err(format!(
+                        "{} must be written as host:port, found '{}'",
+                        name, address
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Colors are written as [r, g, b] or [r, g, b, a] with values from 0 to 255,
+/// they make up the default theme
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visuals {
+    pub screen_size: [f32; 2],
+    pub background: [u8; 3],
+    pub board_background: [u8; 4],
+    pub grid: [u8; 3],
+    /// The seven tetrominoes followed by the garbage blocks
+    pub palette: Vec<[u8; 3]>,
+    /// The ghost pieces showing where the tetrominoes will land
+    pub ghost_palette: Vec<[u8; 4]>,
+    /// Animates line clears, placed pieces, rising garbage and big attacks
+    pub animations: bool,
+    /// The name of the theme used at start, it can be changed in the main menu
+    pub theme: String,
+}
+
+impl Default for Visuals {
+    fn default() -> Visuals {
+        Visuals {
+            screen_size: [800.0, 600.0],
+            background: [25, 172, 244],
+            board_background: [0, 0, 0, 204],
+            grid: [100, 100, 100],
+            palette: vec![
+                [0, 255, 255],   // Cyan
+                [255, 255, 0],   // Yellow
+                [128, 0, 128],   // Purple
+                [0, 255, 0],     // Green
+                [255, 0, 0],     // Red
+                [0, 0, 255],     // Blue
+                [255, 127, 0],   // Orange
+                [127, 127, 127], // Grey
+            ],
+            ghost_palette: vec![
+                [0, 255, 255, 77],  // Cyan
+                [255, 255, 0, 77],  // Yellow
+                [128, 0, 128, 128], // Purple
+                [0, 255, 0, 77],    // Green
+                [255, 0, 0, 77],    // Red
+                [0, 0, 175, 128],   // Blue
+                [255, 127, 0, 77],  // Orange
+            ],
+            animations: true,
+            theme: DEFAULT_THEME.to_string(),
+        }
+    }
+}
+
+/// Colors needed in the palette, the seven tetrominoes and the garbage
+pub const PALETTE_SIZE: usize = 8;
+/// Colors needed in the ghost palette, one per tetromino
+pub const GHOST_PALETTE_SIZE: usize = 7;
+
+impl Visuals {
+    pub fn validate(&self) -> Result<(), String> {
+        let [width, height] = self.screen_size;
+        if !(width >= 1.0 && height >= 1.0) {
+            return Err(format!(
+                "screen_size must be positive, found {}x{}",
+                width, height
+            ));
+        }
+        Theme::from_visuals(self).validate()
+    }
+}
+
+/// Sound effects and music, the volumes go from 0 to 1
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    /// The directory in resources/sounds the sounds are loaded from
+    pub pack: String,
+    /// Scales both the effects and the music
+    pub master_volume: f32,
+    pub effects_volume: f32,
+    pub music_volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            enabled: true,
+            pack: "default".to_string(),
+            master_volume: 1.0,
+            effects_volume: 0.8,
+            music_volume: 0.5,
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pack.is_empty() || self.pack.contains(['/', '\\', '.']) {
+            return Err(format!(
+                "pack must be the name of a directory in resources/sounds, found '{}'",
+                self.pack
+            ));
+        }
+        for (name, volume) in &[
+            ("master_volume", self.master_volume),
+            ("effects_volume", self.effects_volume),
+            ("music_volume", self.music_volume),
+        ] {
+            if !(0.0..=1.0).contains(volume) {
+                return Err(format!("{} must be from 0 to 1, found {}", name, volume));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The stats shown next to every board during a game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudConfig {
+    pub pieces_per_second: bool,
+    pub attack_per_minute: bool,
+    pub lines: bool,
+    pub level: bool,
+    pub combo: bool,
+    pub back_to_back: bool,
+    pub finesse_faults: bool,
+    pub time: bool,
+    pub garbage_received: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> HudConfig {
+        HudConfig {
+            pieces_per_second: true,
+            attack_per_minute: true,
+            lines: true,
+            level: true,
+            combo: true,
+            back_to_back: true,
+            finesse_faults: true,
+            time: true,
+            garbage_received: true,
+        }
+    }
+}
+
+impl Config {
+    /// Loads and validates the config, a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("Couldn't read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks everything that parses but doesn't make sense
+    pub fn validate(&self) -> Result<(), String> {
+        self.rules.validate().map_err(|e| format!("rules: {}", e))?;
+        self.visuals
+            .validate()
+            .map_err(|e| format!("visuals: {}", e))?;
+        KeyMap::from_config(&self.controls).map_err(|e| format!("controls: {}", e))?;
+        self.online
+            .validate()
+            .map_err(|e| format!("online: {}", e))?;
+        self.audio.validate().map_err(|e| format!("audio: {}", e))?;
+        if self.game.init_level >= self.rules.levels() {
+            return Err(format!(
+                "game: init_level must be below the {} levels of rules.gravity, found {}",
+                self.rules.levels(),
+                self.game.init_level
+            ));
+        }
+        if !(PLAYER_AMOUNT..=MAX_PLAYERS).contains(&self.game.controllers.len()) {
+            return Err(format!(
+                "game: controllers must have {} to {} entries, found {}",
+                PLAYER_AMOUNT,
+                MAX_PLAYERS,
+                self.game.controllers.len()
+            ));
+        }
+        let controllers = self
+            .game
+            .ai_controllers()
+            .map_err(|e| format!("game: {}", e))?;
+        for controller in controllers.iter().flatten() {
+            if let Controller::AI(path) = controller {
+                if !is_ai_library(path) {
+                    let bots: Vec<&str> = Bot::ALL.iter().map(Bot::id).collect();
+                    return Err(format!(
+                        "game: controller '{}' must be \"{}\", a bot ({}) or an ai-script",
+                        path.display(),
+                        KEYBOARD,
+                        bots.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use super::Config;
+use crate::bots::Bot;
+use crate::game_state::{Controller, GameMode};
+use crate::netplay::Netcode;
+
+#[test]
+fn defaults() {
+    assert!(Config::default().validate().is_ok());
+    assert_eq!(Config::default(), Config::from_toml("").unwrap());
+    let text = toml::to_string(&Config::default()).unwrap();
+    assert_eq!(Config::default(), Config::from_toml(&text).unwrap());
+}
+
+#[test]
+fn partial_config() {
+    let config = Config::from_toml(
+        "[game]\nmode = \"Marathon\"\ninit_level = 1\n[rules]\ngravity = [0.5, 0.25]\n[controls]\ndas = 100\n[online]\nnetcode = \"Lockstep\"\n",
+    )
+    .unwrap();
+    assert_eq!(GameMode::Marathon, config.game.mode);
+    assert_eq!(vec![0.5, 0.25], config.rules.gravity);
+    assert_eq!(100, config.controls.das);
+    assert_eq!(Netcode::Lockstep, config.online.netcode);
+    assert_eq!(
+        Config::default().rules.attack_table,
+        config.rules.attack_table
+    );
+
+    let config = Config::from_toml("[game]\ncontrollers = [\"keyboard\", \"beam-bot\"]\n").unwrap();
+    assert!(
+        config.game.ai_controllers().unwrap() == vec![None, Some(Controller::Bot(Bot::ALL[1]))]
+    );
+}
+
+#[test]
+fn invalid_config() {
+    let errors = [
+        ("[rules]\ngravty = [1.0]\n", "unknown field"),
+        ("[rules]\ngravity = []\n", "rules: gravity"),
+        ("[game]\ninit_level = 20\n", "game: init_level"),
+        (
+            "[game]\ncontrollers = [\"keyboard\"]\n",
+            "game: controllers",
+        ),
+        (
+            "[game]\ncontrollers = [\"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\", \"keyboard\"]\n",
+            "game: controllers",
+        ),
+        (
+            "[game]\ncontrollers = [\"keyboard\", \"ai.rs\"]\n",
+            "ai-script",
+        ),
+        ("[visuals]\npalette = [[0, 0, 0]]\n", "visuals: palette"),
+        ("[visuals]\nbackground = [256, 0, 0]\n", "background"),
+        ("[[controls.players]]\nhold = [\"Nope\"]\n", "controls"),
+        ("[online]\naddress = \"localhost\"\n", "online: address"),
+        (
+            "[online]\nwatch_address = \":7778\"\n",
+            "online: watch_address",
+        ),
+        (
+            "[online]\nlobby_address = \"lobby\"\n",
+            "online: lobby_address",
+        ),
+        ("[audio]\nmusic_volume = 1.5\n", "audio: music_volume"),
+        ("[audio]\npack = \"../sounds\"\n", "audio: pack"),
+    ];
+    for (text, expected) in &errors {
+        let error = Config::from_toml(text).unwrap_err();
+        assert!(error.contains(expected), "{:?} gave {}", text, error);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::config::HudConfig;
+use crate::game_data::TICK_RATE;
+use crate::stats::PlayerStats;
+
+#[cfg(test)]
+mod tests;
+
+/// The lines of the stats panel of a player, leaving out the stats turned off
+/// in the config
+pub fn stat_lines(stats: &PlayerStats, config: &HudConfig) -> Vec<String> {
+    let lines = [
+        (
+            config.pieces_per_second,
+            format!("PPS {:.2}", stats.pieces_per_second()),
+        ),
+        (
+            config.attack_per_minute,
+            format!("APM {:.1}", stats.attack_per_minute()),
+        ),
+        (config.lines, format!("Lines {}", stats.lines)),
+        (config.level, format!("Level {}", stats.level)),
+        (config.combo, format!("Combo {}", stats.combo())),
+        (config.back_to_back, format!("B2B {}", stats.back_to_back)),
+        (
+            config.finesse_faults,
+            format!("Faults {}", stats.finesse_faults),
+        ),
+        (config.time, format!("Time {}", clock(stats.ticks))),
+        (
+            config.garbage_received,
+            format!("Garbage {}", stats.garbage_received),
+        ),
+    ];
+    lines
+        .iter()
+        .filter(|(shown, _)| *shown)
+        .map(|(_, line)| line.clone())
+        .collect()
+}
+
+/// Ticks as minutes and seconds, e.g. 1:05
+fn clock(ticks: u32) -> String {
+    let seconds = ticks / TICK_RATE;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The rows of the results table, a name followed by the stat of every player
+pub fn results_rows(players: &[PlayerStats]) -> Vec<(&'static str, Vec<String>)> {
+    let row =
+        |name, stat: &dyn Fn(&PlayerStats) -> String| (name, players.iter().map(stat).collect());
+    vec![
+        row("PPS", &|stats| format!("{:.2}", stats.pieces_per_second())),
+        row("APM", &|stats| format!("{:.1}", stats.attack_per_minute())),
+        row("Lines", &|stats| stats.lines.to_string()),
+        row("Singles", &|stats| stats.clears[0].to_string()),
+        row("Doubles", &|stats| stats.clears[1].to_string()),
+        row("Triples", &|stats| stats.clears[2].to_string()),
+        row("Tetrises", &|stats| stats.clears[3].to_string()),
+        row("Max combo", &|stats| stats.max_combo.to_string()),
+        row("Garbage sent", &|stats| stats.attack.to_string()),
+        row("Garbage received", &|stats| {
+            stats.garbage_received.to_string()
+        }),
+    ]
+}
+
+/// The tally of a series shown above the boards, e.g. "Series 2 - 1, first to 3"
+pub fn series_line(wins: &[u32], first_to: u32) -> String {
+    let tally: Vec<String> = wins.iter().map(u32::to_string).collect();
+    match wins.iter().position(|won| *won >= first_to) {
+        Some(winner) => format!("P{} wins the series {}", winner + 1, tally.join(" - ")),
+        None => format!("Series {}, first to {}", tally.join(" - "), first_to),
+    }
+}
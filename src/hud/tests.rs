@@ -0,0 +1,62 @@
+use super::{results_rows, series_line, stat_lines};
+use crate::config::HudConfig;
+use crate::game_data::TICK_RATE;
+use crate::stats::PlayerStats;
+
+#[test]
+fn stats_panel() {
+    let mut stats = PlayerStats::default();
+    stats.pieces = 90;
+    stats.lines = 12;
+    stats.attack = 15;
+    stats.level = 6;
+    stats.ticks = 65 * TICK_RATE;
+    let lines = stat_lines(&stats, &HudConfig::default());
+    assert_eq!(9, lines.len());
+    assert_eq!("PPS 1.38", lines[0]);
+    assert_eq!("APM 13.8", lines[1]);
+    assert!(lines.contains(&"Time 1:05".to_string()));
+
+    let config = HudConfig {
+        pieces_per_second: false,
+        combo: false,
+        time: false,
+        ..HudConfig::default()
+    };
+    let lines = stat_lines(&stats, &config);
+    assert_eq!(
+        vec![
+            "APM 13.8",
+            "Lines 12",
+            "Level 6",
+            "B2B 0",
+            "Faults 0",
+            "Garbage 0"
+        ],
+        lines
+    );
+}
+
+#[test]
+fn results_table() {
+    let mut first = PlayerStats::default();
+    first.clears = [3, 2, 1, 4];
+    first.max_combo = 2;
+    first.attack = 24;
+    let mut second = PlayerStats::default();
+    second.garbage_received = 24;
+    let rows = results_rows(&[first, second]);
+    assert_eq!(10, rows.len());
+    let row = |name| &rows.iter().find(|(row, _)| *row == name).unwrap().1;
+    assert_eq!(&vec!["4", "0"], row("Tetrises"));
+    assert_eq!(&vec!["2", "0"], row("Max combo"));
+    assert_eq!(&vec!["24", "0"], row("Garbage sent"));
+    assert_eq!(&vec!["0", "24"], row("Garbage received"));
+}
+
+#[test]
+fn series_tally() {
+    assert_eq!("Series 0 - 0, first to 3", series_line(&[0, 0], 3));
+    assert_eq!("Series 2 - 1 - 0, first to 3", series_line(&[2, 1, 0], 3));
+    assert_eq!("P2 wins the series 1 - 3", series_line(&[1, 3], 3));
+}
@@ -0,0 +1,113 @@
+use crate::game_data::Action;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests;
+
+/// Delayed auto shift, how long a direction is held before it starts repeating
+pub const DAS: Duration = Duration::from_millis(170);
+/// Auto repeat rate, the time between repeats once DAS has passed
+pub const ARR: Duration = Duration::from_millis(50);
+
+/// How far an analog stick has to be pushed to count as pressed
+pub const AXIS_THRESHOLD: f32 = 0.5;
+
+/// An action that is being held down
+struct Held<S> {
+    source: S,
+    player: usize,
+    action: Action,
+    next_repeat: Instant,
+}
+
+/// Repeats held movement actions with DAS and ARR, no matter if they come from
+/// a keyboard, a gamepad button or an analog stick. `S` identifies what is
+/// holding the action, e.g. a key.
+pub struct AutoRepeat<S> {
+    das: Duration,
+    arr: Duration,
+    held: Vec<Held<S>>,
+}
+
+impl<S: Copy + PartialEq> AutoRepeat<S> {
+    pub fn new(das: Duration, arr: Duration) -> AutoRepeat<S> {
+        AutoRepeat {
+            das,
+            arr,
+            held: Vec::new(),
+        }
+    }
+
+    /// Registers a press, returns false if the source is already held so the
+    /// action shouldn't be performed again
+    pub fn press(&mut self, source: S, player: usize, action: Action, now: Instant) -> bool {
+        if self.held.iter().any(|held| held.source == source) {
+            return false;
+        }
+        if is_repeatable(action) {
+            // the latest direction takes over, like on most tetris clients
+            if let Some(opposite) = opposite(action) {
+                self.held
+                    .retain(|held| held.player != player || held.action != opposite);
+            }
+            self.held.push(Held {
+                source,
+                player,
+                action,
+                next_repeat: now + self.das,
+            });
+        }
+        true
+    }
+
+    pub fn release(&mut self, source: S) {
+        self.held.retain(|held| held.source != source);
+    }
+
+    /// Forgets everything that is held, e.g. when a game ends
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+
+    /// Returns the actions that should be repeated since the last update
+    pub fn update(&mut self, now: Instant) -> Vec<(usize, Action)> {
+        let mut actions = Vec::new();
+        for held in &mut self.held {
+            while held.next_repeat <= now {
+                actions.push((held.player, held.action));
+                held.next_repeat += self.arr;
+                if self.arr == Duration::from_millis(0) {
+                    // instant repeat, one step per update is enough
+                    held.next_repeat = now + Duration::from_millis(1);
+                }
+            }
+        }
+        actions
+    }
+}
+
+fn is_repeatable(action: Action) -> bool {
+    matches!(
+        action,
+        Action::MoveLeft | Action::MoveRight | Action::SoftDrop
+    )
+}
+
+fn opposite(action: Action) -> Option<Action> {
+    match action {
+        Action::MoveLeft => Some(Action::MoveRight),
+        Action::MoveRight => Some(Action::MoveLeft),
+        _ => None,
+    }
+}
+
+/// Which direction an analog axis is pushed in, `None` inside the dead zone
+pub fn axis_direction(value: f32) -> Option<bool> {
+    if value > AXIS_THRESHOLD {
+        Some(true)
+    } else if value < -AXIS_THRESHOLD {
+        Some(false)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,50 @@
+use super::{axis_direction, AutoRepeat};
+use crate::game_data::Action;
+use std::time::{Duration, Instant};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn das_and_arr() {
+    let mut repeat = AutoRepeat::new(ms(100), ms(20));
+    let start = Instant::now();
+    assert!(repeat.press(1, 0, Action::MoveLeft, start));
+    assert!(!repeat.press(1, 0, Action::MoveLeft, start + ms(10)));
+    assert!(repeat.update(start + ms(99)).is_empty());
+    assert_eq!(vec![(0, Action::MoveLeft)], repeat.update(start + ms(100)));
+    assert!(repeat.update(start + ms(110)).is_empty());
+    assert_eq!(2, repeat.update(start + ms(140)).len());
+    repeat.release(1);
+    assert!(repeat.update(start + ms(500)).is_empty());
+}
+
+#[test]
+fn only_movement_repeats() {
+    let mut repeat = AutoRepeat::new(ms(100), ms(20));
+    let start = Instant::now();
+    assert!(repeat.press(1, 0, Action::HardDrop, start));
+    assert!(repeat.press(2, 1, Action::SoftDrop, start));
+    assert_eq!(vec![(1, Action::SoftDrop)], repeat.update(start + ms(100)));
+}
+
+#[test]
+fn latest_direction_wins() {
+    let mut repeat = AutoRepeat::new(ms(100), ms(20));
+    let start = Instant::now();
+    repeat.press(1, 0, Action::MoveLeft, start);
+    repeat.press(2, 1, Action::MoveLeft, start);
+    repeat.press(3, 0, Action::MoveRight, start + ms(50));
+    let actions = repeat.update(start + ms(150));
+    assert!(actions.contains(&(0, Action::MoveRight)));
+    assert!(actions.contains(&(1, Action::MoveLeft)));
+    assert!(!actions.contains(&(0, Action::MoveLeft)));
+}
+
+#[test]
+fn axis_dead_zone() {
+    assert_eq!(None, axis_direction(0.2));
+    assert_eq!(Some(true), axis_direction(0.9));
+    assert_eq!(Some(false), axis_direction(-0.6));
+}
@@ -0,0 +1,529 @@
+use crate::game_data::Action;
+use crate::game_state::PLAYER_AMOUNT;
+use crate::input::{ARR, DAS};
+use ggez::event::{Axis, Button, KeyCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// Keys used by the application itself, they can't be bound to actions
+pub const RESERVED_KEYS: [KeyCode; 3] = [KeyCode::R, KeyCode::Escape, KeyCode::F3];
+
+/// Keys that can be written in the keymap file, by their `Debug` name
+const KEYS: [KeyCode; 90] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Left,
+    KeyCode::Up,
+    KeyCode::Right,
+    KeyCode::Down,
+    KeyCode::Space,
+    KeyCode::Return,
+    KeyCode::Tab,
+    KeyCode::Back,
+    KeyCode::Escape,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Backslash,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::Grave,
+];
+
+/// Gamepad buttons that can be written in the keymap file
+const BUTTONS: [Button; 19] = [
+    Button::South,
+    Button::East,
+    Button::North,
+    Button::West,
+    Button::C,
+    Button::Z,
+    Button::LeftTrigger,
+    Button::LeftTrigger2,
+    Button::RightTrigger,
+    Button::RightTrigger2,
+    Button::Select,
+    Button::Start,
+    Button::Mode,
+    Button::LeftThumb,
+    Button::RightThumb,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+
+/// Gamepad axes that can be written in the keymap file
+const AXES: [Axis; 8] = [
+    Axis::LeftStickX,
+    Axis::LeftStickY,
+    Axis::LeftZ,
+    Axis::RightStickX,
+    Axis::RightStickY,
+    Axis::RightZ,
+    Axis::DPadX,
+    Axis::DPadY,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
+
+/// A gamepad button, or an analog axis pushed in the positive or negative direction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PadInput {
+    Button(Button),
+    Axis(Axis, bool),
+}
+
+/// Buttons are written by name, axes by name followed by + or -
+pub fn pad_input_name(input: PadInput) -> String {
+    match input {
+        PadInput::Button(button) => format!("{:?}", button),
+        PadInput::Axis(axis, positive) => {
+            format!("{:?}{}", axis, if positive { "+" } else { "-" })
+        }
+    }
+}
+
+pub fn pad_input_from_name(name: &str) -> Option<PadInput> {
+    if let Some(button) = BUTTONS.iter().find(|b| format!("{:?}", b) == name) {
+        return Some(PadInput::Button(*button));
+    }
+    AXES.iter().find_map(|axis| {
+        let axis_name = format!("{:?}", axis);
+        if name == format!("{}+", axis_name) {
+            Some(PadInput::Axis(*axis, true))
+        } else if name == format!("{}-", axis_name) {
+            Some(PadInput::Axis(*axis, false))
+        } else {
+            None
+        }
+    })
+}
+
+/// A gamepad input bound to an action, shared by all gamepads
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PadBinding {
+    pub input: PadInput,
+    pub action: Action,
+}
+
+/// A key bound to an action of a player
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub key: KeyCode,
+    pub player: usize,
+    pub action: Action,
+}
+
+/// Two bindings sharing the same key
+pub type Conflict = (Binding, Binding);
+
+/// Maps keyboard keys and gamepad inputs to player actions
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    bindings: Vec<Binding>,
+    pad_bindings: Vec<PadBinding>,
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        let players = [
+            [
+                KeyCode::A,
+                KeyCode::D,
+                KeyCode::E,
+                KeyCode::Q,
+                KeyCode::S,
+                KeyCode::W,
+                KeyCode::Space,
+            ],
+            [
+                KeyCode::J,
+                KeyCode::L,
+                KeyCode::O,
+                KeyCode::U,
+                KeyCode::K,
+                KeyCode::I,
+                KeyCode::RShift,
+            ],
+        ];
+        let mut bindings = Vec::new();
+        for (player, keys) in players.iter().enumerate() {
+            for (key, action) in keys.iter().zip(Action::ALL.iter()) {
+                bindings.push(Binding {
+                    key: *key,
+                    player,
+                    action: *action,
+                });
+            }
+        }
+        let pad_inputs = [
+            (Action::MoveLeft, PadInput::Button(Button::DPadLeft)),
+            (Action::MoveLeft, PadInput::Axis(Axis::LeftStickX, false)),
+            (Action::MoveRight, PadInput::Button(Button::DPadRight)),
+            (Action::MoveRight, PadInput::Axis(Axis::LeftStickX, true)),
+            (Action::RotateClockwise, PadInput::Button(Button::East)),
+            (
+                Action::RotateCounterClockwise,
+                PadInput::Button(Button::South),
+            ),
+            (Action::SoftDrop, PadInput::Button(Button::DPadDown)),
+            (Action::SoftDrop, PadInput::Axis(Axis::LeftStickY, false)),
+            (Action::HardDrop, PadInput::Button(Button::DPadUp)),
+            (Action::Hold, PadInput::Button(Button::LeftTrigger)),
+            (Action::Hold, PadInput::Button(Button::RightTrigger)),
+        ];
+        let pad_bindings = pad_inputs
+            .iter()
+            .map(|(action, input)| PadBinding {
+                input: *input,
+                action: *action,
+            })
+            .collect();
+        KeyMap {
+            bindings,
+            pad_bindings,
+        }
+    }
+}
+
+impl KeyMap {
+    /// Reads the bindings from the controls section of the config file
+    pub fn from_config(file: &ControlsConfig) -> Result<KeyMap, String> {
+        if file.players.len() != PLAYER_AMOUNT {
+            return Err(format!(
+                "expected bindings for {} players, found {}",
+                PLAYER_AMOUNT,
+                file.players.len()
+            ));
+        }
+        let mut bindings = Vec::new();
+        for (player, keys) in file.players.iter().enumerate() {
+            for action in &Action::ALL {
+                for name in keys.get(*action) {
+                    let key = key_from_name(name).ok_or_else(|| {
+                        format!(
+                            "unknown key '{}' for {} of player {}",
+                            name,
+                            action.name(),
+                            player + 1
+                        )
+                    })?;
+                    if RESERVED_KEYS.contains(&key) {
+                        return Err(format!("{} is reserved and can't be bound", name));
+                    }
+                    bindings.push(Binding {
+                        key,
+                        player,
+                        action: *action,
+                    });
+                }
+            }
+        }
+        let pad_bindings = match &file.gamepad {
+            Some(pad_keys) => {
+                let mut pad_bindings = Vec::new();
+                for action in &Action::ALL {
+                    for name in pad_keys.get(*action) {
+                        let input = pad_input_from_name(name).ok_or_else(|| {
+                            format!("unknown gamepad input '{}' for {}", name, action.name())
+                        })?;
+                        pad_bindings.push(PadBinding {
+                            input,
+                            action: *action,
+                        });
+                    }
+                }
+                pad_bindings
+            }
+            None => KeyMap::default().pad_bindings,
+        };
+        Ok(KeyMap {
+            bindings,
+            pad_bindings,
+        })
+    }
+
+    /// Writes the bindings to the controls section of the config file
+    pub fn update_config(&self, file: &mut ControlsConfig) {
+        let mut players = vec![PlayerKeys::default(); PLAYER_AMOUNT];
+        for binding in &self.bindings {
+            players[binding.player]
+                .get_mut(binding.action)
+                .push(key_name(binding.key));
+        }
+        let mut gamepad = PlayerKeys::default();
+        for binding in &self.pad_bindings {
+            gamepad
+                .get_mut(binding.action)
+                .push(pad_input_name(binding.input));
+        }
+        file.players = players;
+        file.gamepad = Some(gamepad);
+    }
+
+    /// All actions bound to a key, more than one only if there are conflicts
+    pub fn actions(&self, key: KeyCode) -> Vec<(usize, Action)> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.key == key)
+            .map(|binding| (binding.player, binding.action))
+            .collect()
+    }
+
+    /// The keys bound to an action of a player
+    pub fn keys(&self, player: usize, action: Action) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.player == player && binding.action == action)
+            .map(|binding| binding.key)
+            .collect()
+    }
+
+    /// Replaces the keys of an action with a single key
+    pub fn bind(&mut self, player: usize, action: Action, key: KeyCode) -> Result<(), String> {
+        if RESERVED_KEYS.contains(&key) {
+            return Err(format!("{} is reserved and can't be bound", key_name(key)));
+        }
+        self.bindings
+            .retain(|binding| binding.player != player || binding.action != action);
+        self.bindings.push(Binding {
+            key,
+            player,
+            action,
+        });
+        // same order as when loaded from a file
+        self.bindings
+            .sort_by_key(|binding| (binding.player, binding.action as usize));
+        Ok(())
+    }
+
+    /// All actions bound to a gamepad input
+    pub fn pad_actions(&self, input: PadInput) -> Vec<Action> {
+        self.pad_bindings
+            .iter()
+            .filter(|binding| binding.input == input)
+            .map(|binding| binding.action)
+            .collect()
+    }
+
+    /// The gamepad inputs bound to an action
+    pub fn pad_inputs(&self, action: Action) -> Vec<PadInput> {
+        self.pad_bindings
+            .iter()
+            .filter(|binding| binding.action == action)
+            .map(|binding| binding.input)
+            .collect()
+    }
+
+    /// Replaces the gamepad inputs of an action with a single input
+    pub fn bind_pad(&mut self, action: Action, input: PadInput) {
+        self.pad_bindings.retain(|binding| binding.action != action);
+        self.pad_bindings.push(PadBinding { input, action });
+        self.pad_bindings
+            .sort_by_key(|binding| binding.action as usize);
+    }
+
+    /// Every pair of bindings that share a key
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (i, first) in self.bindings.iter().enumerate() {
+            for second in &self.bindings[i + 1..] {
+                if first.key == second.key {
+                    conflicts.push((*first, *second));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// Describes a conflict for the player
+pub fn conflict_message((first, second): &Conflict) -> String {
+    format!(
+        "{} is bound to both P{} {} and P{} {}",
+        key_name(first.key),
+        first.player + 1,
+        first.action.name(),
+        second.player + 1,
+        second.action.name()
+    )
+}
+
+/// The controls section of the config file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ControlsConfig {
+    /// Delayed auto shift in milliseconds
+    #[serde(default = "default_das")]
+    pub das: u64,
+    /// Auto repeat rate in milliseconds
+    #[serde(default = "default_arr")]
+    pub arr: u64,
+    #[serde(default = "default_players")]
+    players: Vec<PlayerKeys>,
+    /// Missing means the default gamepad layout
+    #[serde(default)]
+    gamepad: Option<PlayerKeys>,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> ControlsConfig {
+        let mut config = ControlsConfig {
+            das: default_das(),
+            arr: default_arr(),
+            players: Vec::new(),
+            gamepad: None,
+        };
+        KeyMap::default().update_config(&mut config);
+        config
+    }
+}
+
+impl ControlsConfig {
+    pub fn das(&self) -> Duration {
+        Duration::from_millis(self.das)
+    }
+
+    pub fn arr(&self) -> Duration {
+        Duration::from_millis(self.arr)
+    }
+}
+
+fn default_das() -> u64 {
+    DAS.as_millis() as u64
+}
+
+fn default_arr() -> u64 {
+    ARR.as_millis() as u64
+}
+
+fn default_players() -> Vec<PlayerKeys> {
+    ControlsConfig::default().players
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlayerKeys {
+    #[serde(default)]
+    move_left: Vec<String>,
+    #[serde(default)]
+    move_right: Vec<String>,
+    #[serde(default)]
+    rotate_clockwise: Vec<String>,
+    #[serde(default)]
+    rotate_counter_clockwise: Vec<String>,
+    #[serde(default)]
+    soft_drop: Vec<String>,
+    #[serde(default)]
+    hard_drop: Vec<String>,
+    #[serde(default)]
+    hold: Vec<String>,
+}
+
+impl PlayerKeys {
+    fn get(&self, action: Action) -> &Vec<String> {
+        match action {
+            Action::MoveLeft => &self.move_left,
+            Action::MoveRight => &self.move_right,
+            Action::RotateClockwise => &self.rotate_clockwise,
+            Action::RotateCounterClockwise => &self.rotate_counter_clockwise,
+            Action::SoftDrop => &self.soft_drop,
+            Action::HardDrop => &self.hard_drop,
+            Action::Hold => &self.hold,
+        }
+    }
+
+    fn get_mut(&mut self, action: Action) -> &mut Vec<String> {
+        match action {
+            Action::MoveLeft => &mut self.move_left,
+            Action::MoveRight => &mut self.move_right,
+            Action::RotateClockwise => &mut self.rotate_clockwise,
+            Action::RotateCounterClockwise => &mut self.rotate_counter_clockwise,
+            Action::SoftDrop => &mut self.soft_drop,
+            Action::HardDrop => &mut self.hard_drop,
+            Action::Hold => &mut self.hold,
+        }
+    }
+}
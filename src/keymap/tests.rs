@@ -0,0 +1,85 @@
+use super::{
+    key_from_name, key_name, pad_input_from_name, pad_input_name, ControlsConfig, KeyMap, PadInput,
+};
+use crate::game_data::Action;
+use ggez::event::{Axis, Button, KeyCode};
+
+fn from_toml(text: &str) -> Result<KeyMap, String> {
+    let config: ControlsConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+    KeyMap::from_config(&config)
+}
+
+#[test]
+fn default_bindings() {
+    let keymap = KeyMap::default();
+    assert!(keymap.conflicts().is_empty());
+    assert_eq!(vec![(0, Action::MoveLeft)], keymap.actions(KeyCode::A));
+    assert_eq!(vec![(1, Action::Hold)], keymap.actions(KeyCode::RShift));
+    assert_eq!(vec![KeyCode::W], keymap.keys(0, Action::HardDrop));
+    assert!(keymap.actions(KeyCode::Z).is_empty());
+}
+
+#[test]
+fn key_names() {
+    assert_eq!("RShift", key_name(KeyCode::RShift));
+    assert_eq!(Some(KeyCode::Key1), key_from_name("Key1"));
+    assert_eq!(None, key_from_name("NotAKey"));
+}
+
+#[test]
+fn toml_round_trip() {
+    let mut keymap = KeyMap::default();
+    keymap.bind(1, Action::HardDrop, KeyCode::Up).unwrap();
+    keymap.bind_pad(Action::Hold, PadInput::Button(Button::North));
+    let mut config = ControlsConfig::default();
+    keymap.update_config(&mut config);
+    let text = toml::to_string(&config).unwrap();
+    assert_eq!(keymap, from_toml(&text).unwrap());
+}
+
+#[test]
+fn load_errors() {
+    let one_player = "[[players]]\nmove_left = [\"A\"]\n";
+    assert!(from_toml(one_player).is_err());
+    let unknown_key = "[[players]]\nmove_left = [\"Foo\"]\n[[players]]\n";
+    assert!(from_toml(unknown_key).is_err());
+    let reserved_key = "[[players]]\nhold = [\"Escape\"]\n[[players]]\n";
+    assert!(from_toml(reserved_key).is_err());
+}
+
+#[test]
+fn conflicts() {
+    let mut keymap = KeyMap::default();
+    keymap.bind(0, Action::Hold, KeyCode::J).unwrap();
+    let conflicts = keymap.conflicts();
+    assert_eq!(1, conflicts.len());
+    let (first, second) = conflicts[0];
+    assert_eq!((0, Action::Hold), (first.player, first.action));
+    assert_eq!((1, Action::MoveLeft), (second.player, second.action));
+    assert_eq!(2, keymap.actions(KeyCode::J).len());
+
+    assert!(keymap.bind(0, Action::Hold, KeyCode::R).is_err());
+}
+
+#[test]
+fn gamepad_bindings() {
+    let mut keymap = KeyMap::default();
+    let stick_left = PadInput::Axis(Axis::LeftStickX, false);
+    assert_eq!(vec![Action::MoveLeft], keymap.pad_actions(stick_left));
+    assert_eq!("LeftStickX-", pad_input_name(stick_left));
+    assert_eq!(Some(stick_left), pad_input_from_name("LeftStickX-"));
+    assert_eq!(None, pad_input_from_name("LeftStickX"));
+
+    keymap.bind_pad(Action::MoveLeft, PadInput::Button(Button::West));
+    assert!(keymap.pad_actions(stick_left).is_empty());
+    assert_eq!(
+        vec![PadInput::Button(Button::West)],
+        keymap.pad_inputs(Action::MoveLeft)
+    );
+
+    let no_gamepad = "[[players]]\n[[players]]\n";
+    assert_eq!(
+        KeyMap::default().pad_inputs(Action::Hold),
+        from_toml(no_gamepad).unwrap().pad_inputs(Action::Hold)
+    );
+}
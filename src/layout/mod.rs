@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod tests;
+
+/// The window size the ui is designed for, everything is scaled from it
+pub const REFERENCE_SIZE: (f32, f32) = (800.0, 600.0);
+
+/// Visible size of the tetris board
+pub const GRID_SIZE: (i32, i32) = (10, 20);
+
+/// Size of the area a board and its side panels take up, in blocks
+const CELL_SIZE: (f32, f32) = (20.0, 30.0);
+
+/// The top-left corner of the board inside its cell, in blocks
+const BOARD_OFFSET: (f32, f32) = (5.0, 5.0);
+
+/// Width and height of the next piece and saved piece boxes, in blocks
+const INFO_BOX: f32 = 3.0;
+
+/// Share of the screen width the focus board gets in games of three or more
+const FOCUS_WIDTH: f32 = 0.5;
+
+/// Where a board and its side panels are drawn
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoardLayout {
+    /// The top-left corner of the board
+    pub x: f32,
+    pub y: f32,
+    /// Size of each block
+    pub block: f32,
+}
+
+impl BoardLayout {
+    /// The x y w h of the board
+    pub fn board(&self) -> (f32, f32, f32, f32) {
+        (
+            self.x,
+            self.y,
+            GRID_SIZE.0 as f32 * self.block,
+            GRID_SIZE.1 as f32 * self.block,
+        )
+    }
+
+    /// Size of the scaled-down blocks in the info boxes
+    pub fn small_block(&self) -> f32 {
+        self.block / 2.0
+    }
+
+    /// The x y w h of the box left of the board showing the saved piece
+    pub fn hold_box(&self) -> (f32, f32, f32, f32) {
+        let size = INFO_BOX * self.block;
+        (self.x - size, self.y, size, size)
+    }
+
+    /// The x y w h of the box right of the board showing the next piece
+    pub fn next_box(&self) -> (f32, f32, f32, f32) {
+        let size = INFO_BOX * self.block;
+        (self.x + self.board().2, self.y, size, size)
+    }
+
+    /// The top-left corner of a block, rows are counted from the bottom of the board
+    pub fn cell(&self, x: usize, y: usize) -> (f32, f32) {
+        let (left, top, _, height) = self.board();
+        (
+            left + x as f32 * self.block,
+            top + height - (y as f32 + 1.0) * self.block,
+        )
+    }
+
+    /// The center of the score text
+    pub fn score_center(&self) -> (f32, f32) {
+        let (x, y, w, h) = self.board();
+        (x + w / 2.0, y + h + 1.5 * self.block)
+    }
+
+    /// The top-left corner of the stats panel below the next piece box
+    pub fn stats_corner(&self) -> (f32, f32) {
+        let (x, y, w, _) = self.board();
+        (x + w + self.block / 2.0, y + (INFO_BOX + 1.0) * self.block)
+    }
+
+    /// The center of the text above the board naming the player and its target
+    pub fn label_center(&self) -> (f32, f32) {
+        let (x, y, w, _) = self.board();
+        (x + w / 2.0, y - self.block)
+    }
+}
+
+/// Places `boards` boards in a grid that fills as much of the screen as possible
+pub fn layout_boards(screen: (f32, f32), boards: usize) -> Vec<BoardLayout> {
+    layout_area((0.0, 0.0, screen.0, screen.1), boards)
+}
+
+/// Places the boards of a match. With three or more players the focus board,
+/// usually the local player's, fills the left part of the screen and the
+/// opponents share the rest. Without a focus every board is the same size.
+pub fn layout_match(screen: (f32, f32), boards: usize, focus: Option<usize>) -> Vec<BoardLayout> {
+    let focus = match focus {
+        Some(focus) if boards > 2 && focus < boards => focus,
+        _ => return layout_boards(screen, boards),
+    };
+    let split = screen.0 * FOCUS_WIDTH;
+    let mut layouts = layout_area((split, 0.0, screen.0 - split, screen.1), boards - 1);
+    layouts.insert(focus, layout_area((0.0, 0.0, split, screen.1), 1)[0]);
+    layouts
+}
+
+/// Places boards in a grid filling as much of the x y w h area as possible
+fn layout_area(area: (f32, f32, f32, f32), boards: usize) -> Vec<BoardLayout> {
+    if boards == 0 {
+        return Vec::new();
+    }
+    let (area_x, area_y, area_w, area_h) = area;
+    // try every amount of rows and keep the one giving the biggest blocks
+    let (columns, rows, block) = (1..=boards)
+        .map(|rows| {
+            let columns = boards.div_ceil(rows);
+            let block =
+                (area_w / (columns as f32 * CELL_SIZE.0)).min(area_h / (rows as f32 * CELL_SIZE.1));
+            (columns, rows, block)
+        })
+        .fold((1, 1, f32::MIN), |best, option| {
+            if option.2 > best.2 {
+                option
+            } else {
+                best
+            }
+        });
+    let cell = (CELL_SIZE.0 * block, CELL_SIZE.1 * block);
+    let left = area_x + (area_w - columns as f32 * cell.0) / 2.0;
+    let top = area_y + (area_h - rows as f32 * cell.1) / 2.0;
+    (0..boards)
+        .map(|i| {
+            let row = i / columns;
+            // the last row is centered if it isn't full
+            let in_row = columns.min(boards - row * columns);
+            let row_left = left + (columns - in_row) as f32 * cell.0 / 2.0;
+            BoardLayout {
+                x: row_left + (i % columns) as f32 * cell.0 + BOARD_OFFSET.0 * block,
+                y: top + row as f32 * cell.1 + BOARD_OFFSET.1 * block,
+                block,
+            }
+        })
+        .collect()
+}
+
+/// How much menus and text are scaled compared to the reference size
+pub fn ui_scale(screen: (f32, f32)) -> f32 {
+    (screen.0 / REFERENCE_SIZE.0).min(screen.1 / REFERENCE_SIZE.1)
+}
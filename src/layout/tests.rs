@@ -0,0 +1,111 @@
+use super::*;
+
+#[test]
+fn reference_layout() {
+    // two boards at the reference size are placed like the original fixed layout
+    let boards = layout_boards(REFERENCE_SIZE, 2);
+    assert_eq!(
+        boards,
+        vec![
+            BoardLayout {
+                x: 100.0,
+                y: 100.0,
+                block: 20.0
+            },
+            BoardLayout {
+                x: 500.0,
+                y: 100.0,
+                block: 20.0
+            },
+        ]
+    );
+    assert_eq!(boards[0].board(), (100.0, 100.0, 200.0, 400.0));
+    assert_eq!(boards[0].hold_box(), (40.0, 100.0, 60.0, 60.0));
+    assert_eq!(boards[0].next_box(), (300.0, 100.0, 60.0, 60.0));
+    assert_eq!(boards[0].cell(0, 0), (100.0, 480.0));
+    assert_eq!(boards[0].score_center(), (200.0, 530.0));
+    assert_eq!(boards[0].stats_corner(), (310.0, 180.0));
+    assert_eq!(ui_scale(REFERENCE_SIZE), 1.0);
+}
+
+#[test]
+fn scales_with_window() {
+    let boards = layout_boards((1600.0, 1200.0), 2);
+    assert_eq!(boards[1].block, 40.0);
+    assert_eq!(boards[1].board(), (1000.0, 200.0, 400.0, 800.0));
+    assert_eq!(ui_scale((1600.0, 1200.0)), 2.0);
+
+    // a wide window is limited by its height and the boards are centered
+    let boards = layout_boards((1600.0, 600.0), 2);
+    assert_eq!(boards[0].block, 20.0);
+    assert_eq!(boards[0].x, 500.0);
+    assert_eq!(ui_scale((1600.0, 600.0)), 1.0);
+}
+
+#[test]
+fn many_boards() {
+    assert!(layout_boards(REFERENCE_SIZE, 0).is_empty());
+
+    // a tall window stacks the boards instead of shrinking them
+    let boards = layout_boards((400.0, 1200.0), 2);
+    assert_eq!(boards[0].block, 20.0);
+    assert_eq!(boards[0].x, boards[1].x);
+    assert!(boards[0].y < boards[1].y);
+
+    for &screen in &[REFERENCE_SIZE, (1920.0, 1080.0), (500.0, 900.0)] {
+        for amount in 1..=8 {
+            let boards = layout_boards(screen, amount);
+            assert_eq!(boards.len(), amount);
+            for (i, layout) in boards.iter().enumerate() {
+                let (hold_x, _, _, _) = layout.hold_box();
+                let (next_x, _, next_w, _) = layout.next_box();
+                let (_, score_y) = layout.score_center();
+                assert!(hold_x >= 0.0 && next_x + next_w <= screen.0);
+                assert!(layout.y >= 0.0 && score_y <= screen.1);
+                for other in &boards[i + 1..] {
+                    assert!(
+                        (layout.x - other.x).abs() >= 16.0 * layout.block
+                            || (layout.y - other.y).abs() >= 22.0 * layout.block
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn focus_board() {
+    // two players keep the equal layout even with a focus
+    assert_eq!(
+        layout_match(REFERENCE_SIZE, 2, Some(1)),
+        layout_boards(REFERENCE_SIZE, 2)
+    );
+    assert_eq!(
+        layout_match(REFERENCE_SIZE, 4, None),
+        layout_boards(REFERENCE_SIZE, 4)
+    );
+
+    for &screen in &[REFERENCE_SIZE, (1920.0, 1080.0), (500.0, 900.0)] {
+        for amount in 3..=8 {
+            let boards = layout_match(screen, amount, Some(1));
+            assert_eq!(boards.len(), amount);
+            let focus = boards[1];
+            assert_eq!(
+                focus.label_center().0,
+                focus.board().0 + focus.board().2 / 2.0
+            );
+            for (i, layout) in boards.iter().enumerate() {
+                let (hold_x, _, _, _) = layout.hold_box();
+                let (next_x, _, next_w, _) = layout.next_box();
+                let (_, score_y) = layout.score_center();
+                assert!(hold_x >= 0.0 && next_x + next_w <= screen.0);
+                assert!(layout.label_center().1 >= 0.0 && score_y <= screen.1);
+                if i != 1 {
+                    // the opponents are never bigger and are right of the focus board
+                    assert!(layout.block <= focus.block);
+                    assert!(hold_x >= focus.next_box().0 + focus.next_box().2);
+                }
+            }
+        }
+    }
+}
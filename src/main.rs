@@ -1,25 +1,61 @@
+mod animation;
 mod app;
-mod game_data;
-mod game_state;
+mod config;
+mod hud;
+mod input;
+mod keymap;
+mod layout;
+mod sound;
+mod theme;
 
-use app::{AppState, SCREEN_SIZE};
+use tetris_engine::{bots, game_data, game_state, lobby, netplay, replay, series, spectate, stats};
+
+use app::AppState;
+use config::{Config, CONFIG_FILE};
+use ggez::conf::ModuleConf;
 use ggez::event;
+use ggez::GameError;
 use std::path;
+use std::process;
 
 fn main() {
+    let config = match Config::load(path::Path::new(CONFIG_FILE)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid config: {}", e);
+            process::exit(1);
+        }
+    };
+    let [width, height] = config.visuals.screen_size;
     let resource_dir = path::PathBuf::from("./resources");
     let context_builder = ggez::ContextBuilder::new("tetris", "malte och isak")
         .add_resource_path(resource_dir)
         .window_setup(ggez::conf::WindowSetup::default().title("Tetris goes brrr"))
         .window_mode(
             ggez::conf::WindowMode::default()
-                .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1) // Set window dimenstions
+                .dimensions(width, height) // Set window dimenstions
                 .resizable(true), // Fixate window size
         );
 
-    let (contex, event_loop) = &mut context_builder.build().expect("context builder error");
+    // the game is played without sound if there is no audio device
+    let mut audio = config.audio.enabled;
+    let built = context_builder
+        .clone()
+        .modules(ModuleConf::default().audio(audio))
+        .build();
+    let (contex, event_loop) = &mut match built {
+        Err(GameError::AudioError(e)) => {
+            eprintln!("Playing without sound: {}", e);
+            audio = false;
+            context_builder
+                .modules(ModuleConf::default().audio(false))
+                .build()
+        }
+        built => built,
+    }
+    .expect("context builder error");
 
-    let state = &mut AppState::new(contex);
+    let state = &mut AppState::new(contex, config, audio);
 
     event::run(contex, event_loop, state);
 }
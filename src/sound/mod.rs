@@ -0,0 +1,186 @@
+use crate::config::AudioConfig;
+use crate::game_data::{Action, ClearKind, GameEvent};
+
+use ggez::audio::{SoundSource, Source};
+use ggez::{filesystem, Context};
+
+#[cfg(test)]
+mod tests;
+
+/// The formats a sound pack can use, tried in this order
+const EXTENSIONS: [&str; 3] = ["ogg", "wav", "flac"];
+/// The name of the looping music in a sound pack
+const MUSIC: &str = "music";
+
+/// The sound effects a pack can have
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sound {
+    Move,
+    Rotate,
+    Lock,
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    Garbage,
+    Hold,
+    TopOut,
+    Countdown,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 11] = [
+        Sound::Move,
+        Sound::Rotate,
+        Sound::Lock,
+        Sound::Single,
+        Sound::Double,
+        Sound::Triple,
+        Sound::Tetris,
+        Sound::Garbage,
+        Sound::Hold,
+        Sound::TopOut,
+        Sound::Countdown,
+    ];
+
+    /// The name of the sound's file in a pack, without the extension
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Sound::Move => "move",
+            Sound::Rotate => "rotate",
+            Sound::Lock => "lock",
+            Sound::Single => "single",
+            Sound::Double => "double",
+            Sound::Triple => "triple",
+            Sound::Tetris => "tetris",
+            Sound::Garbage => "garbage",
+            Sound::Hold => "hold",
+            Sound::TopOut => "topout",
+            Sound::Countdown => "countdown",
+        }
+    }
+
+    /// The sound played when a game event happens
+    pub fn of_event(event: GameEvent) -> Option<Sound> {
+        match event {
+            GameEvent::PieceLocked { .. } => Some(Sound::Lock),
+            GameEvent::LinesCleared { kind, .. } => Some(match kind {
+                ClearKind::Single => Sound::Single,
+                ClearKind::Double => Sound::Double,
+                ClearKind::Triple => Sound::Triple,
+                ClearKind::Tetris => Sound::Tetris,
+            }),
+            GameEvent::GarbageReceived { .. } => Some(Sound::Garbage),
+            GameEvent::Hold => Some(Sound::Hold),
+            GameEvent::TopOut => Some(Sound::TopOut),
+            GameEvent::GarbageSent { .. } | GameEvent::LevelUp { .. } => None,
+        }
+    }
+
+    /// The sound played when a player performs an action, the rest are heard
+    /// through their events
+    pub fn of_action(action: Action) -> Option<Sound> {
+        match action {
+            Action::MoveLeft | Action::MoveRight => Some(Sound::Move),
+            Action::RotateClockwise | Action::RotateCounterClockwise => Some(Sound::Rotate),
+            _ => None,
+        }
+    }
+}
+
+/// Where a sound of a pack is looked for, one path per supported format
+pub fn pack_paths(pack: &str, name: &str) -> Vec<String> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| format!("/sounds/{}/{}.{}", pack, name, extension))
+        .collect()
+}
+
+/// The sound effects and music of a pack. Everything stays silent without an
+/// audio device, and sounds missing from the pack are skipped.
+#[derive(Default)]
+pub struct Sounds {
+    effects: Vec<(Sound, Source)>,
+    music: Option<Source>,
+    effects_volume: f32,
+}
+
+impl Sounds {
+    /// Loads the configured pack from the resources, `audio` is false if the
+    /// context was created without an audio device
+    pub fn load(ctx: &mut Context, config: &AudioConfig, audio: bool) -> Sounds {
+        if !audio || !config.enabled {
+            return Sounds::default();
+        }
+        let mut effects = Vec::new();
+        for &sound in &Sound::ALL {
+            if let Some(source) = load_source(ctx, &config.pack, sound.file_name()) {
+                effects.push((sound, source));
+            }
+        }
+        let mut music = load_source(ctx, &config.pack, MUSIC);
+        if let Some(music) = &mut music {
+            music.set_repeat(true);
+            music.set_volume(config.master_volume * config.music_volume);
+        }
+        Sounds {
+            effects,
+            music,
+            effects_volume: config.master_volume * config.effects_volume,
+        }
+    }
+
+    /// Plays a sound effect, it may overlap with the ones already playing
+    pub fn play(&mut self, sound: Sound) {
+        let volume = self.effects_volume;
+        if let Some((_, source)) = self.effects.iter_mut().find(|(s, _)| *s == sound) {
+            // every detached playback gets a new sink with the default volume
+            source.set_volume(volume);
+            if let Err(e) = source.play_detached() {
+                eprintln!("Couldn't play {}: {}", sound.file_name(), e);
+            }
+        }
+    }
+
+    /// Plays the sounds of game events
+    pub fn play_events(&mut self, events: &[(u32, usize, GameEvent)]) {
+        for &(_, _, event) in events {
+            if let Some(sound) = Sound::of_event(event) {
+                self.play(sound);
+            }
+        }
+    }
+
+    /// Starts looping the music unless it's already playing
+    pub fn start_music(&mut self) {
+        if let Some(music) = &mut self.music {
+            if !music.playing() {
+                if let Err(e) = music.play() {
+                    eprintln!("Couldn't play the music: {}", e);
+                }
+            }
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(music) = &mut self.music {
+            if music.playing() {
+                music.stop();
+            }
+        }
+    }
+}
+
+/// Loads the first format of a sound found in the pack
+fn load_source(ctx: &mut Context, pack: &str, name: &str) -> Option<Source> {
+    let path = pack_paths(pack, name)
+        .into_iter()
+        .find(|path| filesystem::exists(ctx, path))?;
+    match Source::new(ctx, &path) {
+        Ok(source) => Some(source),
+        Err(e) => {
+            eprintln!("Couldn't load {}: {}", path, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use super::{pack_paths, Sound};
+use crate::game_data::{Action, ClearKind, GameEvent};
+
+#[test]
+fn event_sounds() {
+    let clear = |kind| GameEvent::LinesCleared { kind, rows: 1 };
+    assert_eq!(
+        Some(Sound::Single),
+        Sound::of_event(clear(ClearKind::Single))
+    );
+    assert_eq!(
+        Some(Sound::Tetris),
+        Sound::of_event(clear(ClearKind::Tetris))
+    );
+    assert_eq!(
+        Some(Sound::Garbage),
+        Sound::of_event(GameEvent::GarbageReceived { lines: 2 })
+    );
+    // the attacker hears its clear, the garbage is heard by the one receiving it
+    assert_eq!(None, Sound::of_event(GameEvent::GarbageSent { lines: 2 }));
+    assert_eq!(Some(Sound::TopOut), Sound::of_event(GameEvent::TopOut));
+
+    assert_eq!(Some(Sound::Move), Sound::of_action(Action::MoveRight));
+    assert_eq!(
+        Some(Sound::Rotate),
+        Sound::of_action(Action::RotateCounterClockwise)
+    );
+    // placing and holding are heard through their events
+    assert_eq!(None, Sound::of_action(Action::HardDrop));
+    assert_eq!(None, Sound::of_action(Action::Hold));
+}
+
+#[test]
+fn default_pack() {
+    for sound in &Sound::ALL {
+        let found = pack_paths("default", sound.file_name())
+            .iter()
+            .any(|path| std::path::Path::new(&format!("resources{}", path)).exists());
+        assert!(found, "the default pack has no {}", sound.file_name());
+    }
+    assert!(std::path::Path::new("resources/sounds/default/music.wav").exists());
+}
@@ -0,0 +1,396 @@
+use crate::app::BLOCK_SIZE;
+use crate::config::{Visuals, GHOST_PALETTE_SIZE, PALETTE_SIZE};
+use crate::layout::GRID_SIZE;
+
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{
+    self, Color, DrawMode, DrawParam, FilterMode, Image, Mesh, MeshBuilder, Rect,
+};
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+#[cfg(test)]
+mod tests;
+
+/// Where the theme files are loaded from
+pub const THEME_DIR: &str = "./resources/themes";
+/// The theme made of the colors in the `[visuals]` section of the config
+pub const DEFAULT_THEME: &str = "Default";
+
+const GRID_LINE_WIDTH: f32 = 1.0;
+/// Color of the patterns drawn on top of the blocks
+const PATTERN_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.4);
+
+/// The colors and block graphics of the game. Colors are written as [r, g, b]
+/// or [r, g, b, a] with values from 0 to 255.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    pub name: String,
+    pub background: [u8; 3],
+    pub board_background: [u8; 4],
+    pub grid: [u8; 3],
+    /// The seven tetrominoes followed by the garbage blocks
+    pub palette: Vec<[u8; 3]>,
+    /// The ghost pieces showing where the tetrominoes will land
+    pub ghost_palette: Vec<[u8; 4]>,
+    /// Draws a different pattern on every kind of block, so they can be told
+    /// apart without the colors
+    #[serde(default)]
+    pub patterns: bool,
+    /// An image in the themes directory with one square block per piece side
+    /// by side, in the order of the palette. The ghosts use the alpha of the
+    /// ghost palette.
+    #[serde(default)]
+    pub sprites: Option<String>,
+}
+
+impl Theme {
+    /// The theme of the colors in the config
+    pub fn from_visuals(visuals: &Visuals) -> Theme {
+        Theme {
+            name: DEFAULT_THEME.to_string(),
+            background: visuals.background,
+            board_background: visuals.board_background,
+            grid: visuals.grid,
+            palette: visuals.palette.clone(),
+            ghost_palette: visuals.ghost_palette.clone(),
+            patterns: false,
+            sprites: None,
+        }
+    }
+
+    /// Colors that can be told apart with any kind of color blindness, with a
+    /// pattern on every kind of block
+    pub fn colorblind() -> Theme {
+        Theme {
+            name: "Colorblind".to_string(),
+            background: [40, 40, 40],
+            board_background: [0, 0, 0, 230],
+            grid: [90, 90, 90],
+            palette: vec![
+                [86, 180, 233],  // Sky blue
+                [240, 228, 66],  // Yellow
+                [204, 121, 167], // Reddish purple
+                [0, 158, 115],   // Bluish green
+                [213, 94, 0],    // Vermillion
+                [0, 114, 178],   // Blue
+                [230, 159, 0],   // Orange
+                [150, 150, 150], // Grey
+            ],
+            ghost_palette: vec![
+                [86, 180, 233, 90],
+                [240, 228, 66, 90],
+                [204, 121, 167, 90],
+                [0, 158, 115, 90],
+                [213, 94, 0, 90],
+                [0, 114, 178, 90],
+                [230, 159, 0, 90],
+            ],
+            patterns: true,
+            sprites: None,
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Theme, String> {
+        let theme: Theme = toml::from_str(text).map_err(|e| e.to_string())?;
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.palette.len() != PALETTE_SIZE {
+            return Err(format!(
+                "palette must have {} colors, found {}",
+                PALETTE_SIZE,
+                self.palette.len()
+            ));
+        }
+        if self.ghost_palette.len() != GHOST_PALETTE_SIZE {
+            return Err(format!(
+                "ghost_palette must have {} colors, found {}",
+                GHOST_PALETTE_SIZE,
+                self.ghost_palette.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The built-in themes followed by the ones in `dir`, sorted by file name.
+/// Theme files that can't be loaded are described in the returned errors.
+pub fn load_themes(visuals: &Visuals, dir: &Path) -> (Vec<Theme>, Vec<String>) {
+    let mut themes = vec![Theme::from_visuals(visuals), Theme::colorblind()];
+    let mut errors = Vec::new();
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    for path in paths {
+        let theme = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Theme::from_toml(&text));
+        match theme {
+            Ok(theme) if themes.iter().any(|t| t.name == theme.name) => errors.push(format!(
+                "{}: there is already a theme called {}",
+                path.display(),
+                theme.name
+            )),
+            Ok(theme) => themes.push(theme),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (themes, errors)
+}
+
+/// A theme turned into what is drawn
+pub struct Skin {
+    pub background: Color,
+    pub board_background: Color,
+    /// The seven tetrominoes followed by the garbage blocks
+    pub palette: [Color; PALETTE_SIZE],
+    ghost_palette: [Color; GHOST_PALETTE_SIZE],
+    patterns: bool,
+    /// Drawn instead of the plain blocks if the theme has a sprite sheet
+    sprites: Option<Image>,
+    pub grid: Mesh,
+}
+
+/// Shapes and blocks collected to be drawn with as few draw calls as possible
+pub struct Batch {
+    shapes: MeshBuilder,
+    sprites: Option<SpriteBatch>,
+}
+
+impl Batch {
+    pub fn rectangle(&mut self, bounds: Rect, color: Color) {
+        self.shapes.rectangle(DrawMode::fill(), bounds, color);
+    }
+
+    /// Uploads the batch, it can then be drawn any number of times. The batch
+    /// must have at least one shape.
+    pub fn build(self, ctx: &mut Context) -> GameResult<BuiltBatch> {
+        Ok(BuiltBatch {
+            shapes: self.shapes.build(ctx)?,
+            sprites: self.sprites,
+        })
+    }
+}
+
+/// A batch ready to be drawn, the shapes go below the sprites
+pub struct BuiltBatch {
+    pub shapes: Mesh,
+    pub sprites: Option<SpriteBatch>,
+}
+
+impl Skin {
+    /// Builds the meshes and loads the sprites of a validated theme. A sprite
+    /// sheet that can't be loaded is described in the error next to the skin,
+    /// which uses the plain blocks instead.
+    pub fn new(ctx: &mut Context, theme: &Theme) -> (Skin, Option<String>) {
+        let mut palette = [graphics::BLACK; PALETTE_SIZE];
+        for (color, config) in palette.iter_mut().zip(theme.palette.iter()) {
+            *color = rgb(*config);
+        }
+        let mut ghost_palette = [graphics::BLACK; GHOST_PALETTE_SIZE];
+        for (color, config) in ghost_palette.iter_mut().zip(theme.ghost_palette.iter()) {
+            *color = rgba(*config);
+        }
+        let (sprites, error) = match &theme.sprites {
+            Some(file) => match load_sprites(ctx, file) {
+                Ok(image) => (Some(image), None),
+                Err(e) => (None, Some(format!("Couldn't load {}: {}", file, e))),
+            },
+            None => (None, None),
+        };
+        let skin = Skin {
+            background: rgb(theme.background),
+            board_background: rgba(theme.board_background),
+            palette,
+            ghost_palette,
+            patterns: theme.patterns,
+            sprites,
+            grid: grid_mesh(ctx, rgb(theme.grid)).expect("grid mesh err"),
+        };
+        (skin, error)
+    }
+
+    /// An empty batch
+    pub fn batch(&self) -> Batch {
+        Batch {
+            shapes: MeshBuilder::new(),
+            sprites: self.sprites.clone().map(|image| {
+                let mut sprites = SpriteBatch::new(image);
+                sprites.set_filter(FilterMode::Nearest);
+                sprites
+            }),
+        }
+    }
+
+    /// Adds a block of a board to a batch, 1 to 8 are the pieces and the
+    /// garbage and 9 to 15 the ghosts. `size` is the side of the block on screen.
+    pub fn add_block(&self, batch: &mut Batch, block: u32, dest: [f32; 2], size: f32) {
+        let index = block as usize - 1;
+        let (kind, color) = if index < PALETTE_SIZE {
+            (index, self.palette[index])
+        } else {
+            (
+                index - PALETTE_SIZE,
+                self.ghost_palette[index - PALETTE_SIZE],
+            )
+        };
+        match (&mut batch.sprites, &self.sprites) {
+            (Some(sprites), Some(image)) => {
+                let tile = image.height() as f32;
+                let tiles = PALETTE_SIZE as f32;
+                sprites.add(
+                    DrawParam::default()
+                        .src(Rect::new(kind as f32 / tiles, 0.0, 1.0 / tiles, 1.0))
+                        .dest(dest)
+                        .scale([size / tile, size / tile])
+                        // the ghosts only take the alpha of their color
+                        .color(Color::new(1.0, 1.0, 1.0, color.a)),
+                );
+            }
+            _ => add_block_shape(&mut batch.shapes, kind, color, self.patterns, dest, size),
+        }
+    }
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgb(r, g, b)
+}
+
+fn rgba([r, g, b, a]: [u8; 4]) -> Color {
+    Color::from_rgba(r, g, b, a)
+}
+
+/// Loads a sprite sheet from the themes directory, checking that it has a
+/// square block for every kind of block
+fn load_sprites(ctx: &mut Context, file: &str) -> GameResult<Image> {
+    let mut image = Image::new(ctx, format!("/themes/{}", file))?;
+    if image.width() as usize != image.height() as usize * PALETTE_SIZE {
+        return Err(ggez::GameError::ResourceLoadError(format!(
+            "the sprite sheet must be {} square blocks wide",
+            PALETTE_SIZE
+        )));
+    }
+    image.set_filter(FilterMode::Nearest);
+    Ok(image)
+}
+
+/// Adds a block at `dest` with the side `size`, with the pattern of its kind
+/// of piece if `patterns` is set
+fn add_block_shape(
+    mesh: &mut MeshBuilder,
+    kind: usize,
+    color: Color,
+    patterns: bool,
+    dest: [f32; 2],
+    size: f32,
+) {
+    mesh.rectangle(
+        DrawMode::fill(),
+        Rect::new(dest[0], dest[1], size, size),
+        color,
+    );
+    if !patterns {
+        return;
+    }
+    let point = |x: f32, y: f32| ggez::mint::Point2 {
+        x: dest[0] + x * size,
+        y: dest[1] + y * size,
+    };
+    let line = |mesh: &mut MeshBuilder, from: (f32, f32), to: (f32, f32)| {
+        mesh.line(
+            &[point(from.0, from.1), point(to.0, to.1)],
+            size * 0.15,
+            PATTERN_COLOR,
+        )
+        .map(|_| ())
+    };
+    let pattern = match kind {
+        // I, O, T, S, Z, J, L and the garbage
+        0 => line(mesh, (0.1, 0.5), (0.9, 0.5)),
+        1 => {
+            mesh.rectangle(
+                DrawMode::fill(),
+                Rect::new(
+                    dest[0] + size * 0.3,
+                    dest[1] + size * 0.3,
+                    size * 0.4,
+                    size * 0.4,
+                ),
+                PATTERN_COLOR,
+            );
+            Ok(())
+        }
+        2 => mesh
+            .triangles(
+                &[point(0.5, 0.2), point(0.8, 0.75), point(0.2, 0.75)],
+                PATTERN_COLOR,
+            )
+            .map(|_| ()),
+        3 => line(mesh, (0.15, 0.85), (0.85, 0.15)),
+        4 => line(mesh, (0.15, 0.15), (0.85, 0.85)),
+        5 => line(mesh, (0.5, 0.1), (0.5, 0.9)),
+        6 => {
+            mesh.circle(
+                DrawMode::stroke(size * 0.12),
+                point(0.5, 0.5),
+                size * 0.25,
+                0.5,
+                PATTERN_COLOR,
+            );
+            Ok(())
+        }
+        _ => line(mesh, (0.15, 0.15), (0.85, 0.85))
+            .and_then(|_| line(mesh, (0.15, 0.85), (0.85, 0.15))),
+    };
+    pattern.expect("Failed creating block patterns");
+}
+
+/// The mesh of the grid-lines
+fn grid_mesh(ctx: &mut Context, grid_color: Color) -> GameResult<Mesh> {
+    let mut mesh = MeshBuilder::new();
+    for x in 0..(GRID_SIZE.0 + 1) {
+        mesh.line(
+            &[
+                ggez::mint::Point2 {
+                    x: (x as f32) * BLOCK_SIZE.0,
+                    y: 0.0,
+                },
+                ggez::mint::Point2 {
+                    x: (x as f32) * BLOCK_SIZE.0,
+                    y: GRID_SIZE.1 as f32 * BLOCK_SIZE.1,
+                },
+            ],
+            GRID_LINE_WIDTH,
+            grid_color,
+        )?;
+    }
+    for y in 0..(GRID_SIZE.1 + 1) {
+        mesh.line(
+            &[
+                ggez::mint::Point2 {
+                    x: 0.0,
+                    y: (y as f32) * BLOCK_SIZE.1,
+                },
+                ggez::mint::Point2 {
+                    x: GRID_SIZE.0 as f32 * BLOCK_SIZE.0,
+                    y: (y as f32) * BLOCK_SIZE.1,
+                },
+            ],
+            GRID_LINE_WIDTH,
+            grid_color,
+        )?;
+    }
+    mesh.build(ctx)
+}
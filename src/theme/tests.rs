@@ -0,0 +1,32 @@
+use super::{load_themes, Theme, DEFAULT_THEME, THEME_DIR};
+use crate::config::Visuals;
+use std::path::Path;
+
+#[test]
+fn builtin_themes() {
+    let visuals = Visuals::default();
+    let (themes, errors) = load_themes(&visuals, Path::new("./no-themes-here"));
+    assert!(errors.is_empty());
+    let names: Vec<&str> = themes.iter().map(|theme| theme.name.as_str()).collect();
+    assert_eq!(vec![DEFAULT_THEME, "Colorblind"], names);
+    assert_eq!(visuals.palette, themes[0].palette);
+    for theme in &themes {
+        assert!(theme.validate().is_ok(), "{} is invalid", theme.name);
+    }
+}
+
+#[test]
+fn theme_files() {
+    let (themes, errors) = load_themes(&Visuals::default(), Path::new(THEME_DIR));
+    assert!(errors.is_empty(), "{:?}", errors);
+    let bevel = themes.iter().find(|theme| theme.name == "Bevel").unwrap();
+    assert_eq!(Some("bevel.png"), bevel.sprites.as_deref());
+    assert!(Path::new(THEME_DIR).join("bevel.png").exists());
+
+    let error = Theme::from_toml(
+        "name = \"Short\"\nbackground = [0, 0, 0]\nboard_background = [0, 0, 0, 0]\ngrid = [0, 0, 0]\npalette = [[0, 0, 0]]\nghost_palette = []\n",
+    )
+    .unwrap_err();
+    assert!(error.contains("palette"), "{}", error);
+    assert!(Theme::from_toml("name = \"Missing colors\"\n").is_err());
+}
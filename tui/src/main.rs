@@ -0,0 +1,109 @@
+//! Plays the game in a terminal, for machines without a window system.
+//! Usage: `tui [--marathon] [--level N] [bot or ai-script ...]`, player 2 gets the first one
+
+mod terminal;
+
+use std::env;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use terminal::{key_action, render, Key, Terminal};
+use tetris_engine::game_data::{Rules, TICK_RATE};
+use tetris_engine::game_state::{Controller, Game, GameMode, PLAYER_AMOUNT};
+
+struct Options {
+    mode: GameMode,
+    init_level: usize,
+    controllers: [Controller; PLAYER_AMOUNT],
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        mode: GameMode::Versus,
+        init_level: 5,
+        controllers: [Controller::Keyboard, Controller::Keyboard],
+    };
+    let rules = Rules::default();
+    let mut scripts = 0;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--marathon" => options.mode = GameMode::Marathon,
+            "--level" => {
+                let level = args.next().ok_or("--level needs a value")?;
+                options.init_level = match level.parse() {
+                    Ok(level) if level < rules.levels() => level,
+                    _ => return Err(format!("Invalid level '{}'", level)),
+                };
+            }
+            _ if scripts < PLAYER_AMOUNT => {
+                options.controllers[PLAYER_AMOUNT - 1 - scripts] = Controller::from_arg(&arg)?;
+                scripts += 1;
+            }
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(options)
+}
+
+fn play(terminal: &mut Terminal, options: &Options) -> Result<(), String> {
+    let mut game = Game::new(
+        &Rules::default(),
+        options.init_level,
+        options.mode,
+        options.controllers.to_vec(),
+        rand::random(),
+    )?;
+    let tick = Duration::from_secs(1) / TICK_RATE;
+    let mut next_tick = Instant::now();
+    loop {
+        for key in terminal.keys()? {
+            match key {
+                Key::Escape => return Ok(()),
+                Key::Char('r') | Key::Char('R') => game.restart(options.init_level, rand::random()),
+                key => {
+                    if let Some((player, action)) = key_action(key) {
+                        if options.controllers[player] == Controller::Keyboard && !game.is_over() {
+                            game.perform(player, action);
+                        }
+                    }
+                }
+            }
+        }
+        if !game.is_over() {
+            game.update();
+        }
+
+        let mut frame = render(&game);
+        if game.is_over() {
+            match game.get_winner() {
+                Some(player) => frame.push_str(&format!("  P{} wins!", player + 1)),
+                None => frame.push_str("  Draw!"),
+            }
+        }
+        frame.push_str("  R restarts, Escape quits\x1b[K");
+        terminal.draw(&frame)?;
+
+        // the game runs at a fixed tick rate, a slow terminal skips ticks instead of catching up
+        next_tick += tick;
+        let now = Instant::now();
+        if next_tick > now {
+            thread::sleep(next_tick - now);
+        } else {
+            next_tick = now;
+        }
+    }
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let result = Terminal::new().and_then(|mut terminal| play(&mut terminal, &options));
+    // the terminal is restored before the error is printed
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Drawing the game as colored text and reading keys in a terminal.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{cursor, ExecutableCommand};
+use std::fmt::Write as _;
+use std::io::{stdout, Stdout, Write};
+use std::time::Duration;
+use tetris_engine::game_data::{Action, ROWS};
+use tetris_engine::game_state::{Game, PLAYER_AMOUNT};
+
+#[cfg(test)]
+mod tests;
+
+/// Rows shown of every board, the top four are hidden like in the window
+pub const VISIBLE_ROWS: usize = 20;
+
+/// ANSI 256-color codes of the tetrominoes and the garbage, like the default palette
+const PALETTE: [u8; 8] = [51, 226, 90, 46, 196, 21, 208, 244];
+
+/// Color of the attack meter
+const METER_COLOR: u8 = 244;
+
+/// A key read from the terminal
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// A printable character, as typed
+    Char(char),
+    /// Escape or Ctrl-C
+    Escape,
+    /// The up arrow
+    Up,
+    /// The down arrow
+    Down,
+    /// The left arrow
+    Left,
+    /// The right arrow
+    Right,
+}
+
+/// The keys of both players, the default bindings of the window where a terminal
+/// can read them. Player 2 can use the arrow keys too.
+pub const BINDINGS: [[(char, Action); 7]; PLAYER_AMOUNT] = [
+    [
+        ('a', Action::MoveLeft),
+        ('d', Action::MoveRight),
+        ('e', Action::RotateClockwise),
+        ('q', Action::RotateCounterClockwise),
+        ('s', Action::SoftDrop),
+        ('w', Action::HardDrop),
+        (' ', Action::Hold),
+    ],
+    [
+        ('j', Action::MoveLeft),
+        ('l', Action::MoveRight),
+        ('o', Action::RotateClockwise),
+        ('u', Action::RotateCounterClockwise),
+        ('k', Action::SoftDrop),
+        ('i', Action::HardDrop),
+        ('h', Action::Hold),
+    ],
+];
+
+/// The player and action a key is bound to
+pub fn key_action(key: Key) -> Option<(usize, Action)> {
+    match key {
+        Key::Char(c) => {
+            let c = c.to_ascii_lowercase();
+            BINDINGS.iter().enumerate().find_map(|(player, bindings)| {
+                bindings
+                    .iter()
+                    .find(|(key, _)| *key == c)
+                    .map(|(_, action)| (player, *action))
+            })
+        }
+        Key::Left => Some((1, Action::MoveLeft)),
+        Key::Right => Some((1, Action::MoveRight)),
+        Key::Down => Some((1, Action::SoftDrop)),
+        Key::Up => Some((1, Action::HardDrop)),
+        Key::Escape => None,
+    }
+}
+
+/// The key of a key press read with crossterm, other events and releases are
+/// `None`. Ctrl-C is read as escape since raw mode doesn't turn it into a signal.
+pub fn read_key(event: &Event) -> Option<Key> {
+    match event {
+        Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        }) => match code {
+            KeyCode::Char('c') | KeyCode::Char('C')
+                if modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                Some(Key::Escape)
+            }
+            KeyCode::Char(c) => Some(Key::Char(*c)),
+            KeyCode::Esc => Some(Key::Escape),
+            KeyCode::Up => Some(Key::Up),
+            KeyCode::Down => Some(Key::Down),
+            KeyCode::Left => Some(Key::Left),
+            KeyCode::Right => Some(Key::Right),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Draws both boards with their hold and next pieces, attack meters and scores.
+/// The frame starts by moving the cursor to the top left, so it overwrites the last one.
+pub fn render(game: &Game) -> String {
+    let boards = game.get_boards();
+    let next_pieces = game.get_next_pieces();
+    let saved_pieces = game.get_saved_pieces();
+    let meters = game.get_attackbars();
+    let scores = game.get_scores();
+    let losts = game.get_losts();
+
+    let mut frame = String::from("\x1b[H");
+    for player in 0..PLAYER_AMOUNT {
+        let lost = if losts[player] { "  Topped out" } else { "" };
+        let header = format!("Player {}  Score {}{}", player + 1, scores[player], lost);
+        let _ = write!(frame, "  {:<41}", header);
+    }
+    frame.push_str("\x1b[K\r\n");
+    for y in 0..VISIBLE_ROWS {
+        for player in 0..PLAYER_AMOUNT {
+            frame.push_str("  ");
+            info_row(&mut frame, "Hold", &saved_pieces[player], y);
+            // the meter grows from the bottom of the board
+            if VISIBLE_ROWS - y <= meters[player] as usize {
+                let _ = write!(frame, "\x1b[48;5;{}m \x1b[0m", METER_COLOR);
+            } else {
+                frame.push(' ');
+            }
+            frame.push('|');
+            // the rows of the board start at the bottom
+            for &block in &boards[player][VISIBLE_ROWS.min(ROWS) - 1 - y] {
+                block_cell(&mut frame, block);
+            }
+            frame.push('|');
+            info_row(&mut frame, "Next", &next_pieces[player], y);
+        }
+        frame.push_str("\x1b[K\r\n");
+    }
+    for _ in 0..PLAYER_AMOUNT {
+        let _ = write!(frame, "{:12}+{}+{:9}", "", "-".repeat(20), "");
+    }
+    frame.push_str("\x1b[K\r\n");
+    frame
+}
+
+/// One row of the hold or next box, the label on top and the piece below it
+fn info_row(frame: &mut String, label: &str, piece: &[[u32; 4]; 4], y: usize) {
+    match y {
+        0 => {
+            let _ = write!(frame, " {:<8}", label);
+        }
+        1..=4 => {
+            frame.push(' ');
+            for &block in &piece[y - 1] {
+                block_cell(frame, block);
+            }
+        }
+        _ => frame.push_str(&" ".repeat(9)),
+    }
+}
+
+/// A block is two characters wide so it looks square
+fn block_cell(frame: &mut String, block: u32) {
+    match block as usize {
+        0 => frame.push_str("  "),
+        // ghost pieces are outlined in the color of their tetromino
+        block if block > PALETTE.len() => {
+            let color = PALETTE[(block - PALETTE.len() - 1) % PALETTE.len()];
+            let _ = write!(frame, "\x1b[38;5;{}m[]\x1b[0m", color);
+        }
+        block => {
+            let _ = write!(frame, "\x1b[48;5;{}m  \x1b[0m", PALETTE[block - 1]);
+        }
+    }
+}
+
+/// Puts the terminal in raw mode until dropped, so keys are read as soon as they are pressed
+pub struct Terminal {
+    stdout: Stdout,
+}
+
+impl Terminal {
+    /// Sets up the terminal of the standard input and output
+    pub fn new() -> Result<Terminal, String> {
+        #[cfg(windows)]
+        {
+            if !crossterm::ansi_support::supports_ansi() {
+                return Err("The terminal doesn't support colors".to_string());
+            }
+        }
+        terminal::enable_raw_mode().map_err(|e| format!("Couldn't set up the terminal: {}", e))?;
+        let mut terminal = Terminal { stdout: stdout() };
+        terminal
+            .stdout
+            .execute(cursor::Hide)
+            .and_then(|stdout| stdout.execute(Clear(ClearType::All)))
+            .map_err(|e| format!("Couldn't set up the terminal: {}", e))?;
+        Ok(terminal)
+    }
+
+    /// The keys pressed since the last call, doesn't block
+    pub fn keys(&mut self) -> Result<Vec<Key>, String> {
+        let mut keys = Vec::new();
+        while event::poll(Duration::ZERO).map_err(|e| format!("Couldn't read keys: {}", e))? {
+            let event = event::read().map_err(|e| format!("Couldn't read keys: {}", e))?;
+            keys.extend(read_key(&event));
+        }
+        Ok(keys)
+    }
+
+    /// Writes a frame to the terminal
+    pub fn draw(&mut self, frame: &str) -> Result<(), String> {
+        self.stdout
+            .write_all(frame.as_bytes())
+            .and_then(|_| self.stdout.flush())
+            .map_err(|e| format!("Couldn't draw: {}", e))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.draw("\x1b[0m\r\n");
+        let _ = self.stdout.execute(cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,56 @@
+use super::{key_action, read_key, render, Key, VISIBLE_ROWS};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use tetris_engine::game_data::{Action, Rules};
+use tetris_engine::game_state::{Controller, Game, GameMode};
+
+#[test]
+fn keys() {
+    let press = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+    assert_eq!(
+        Some(Key::Char('D')),
+        read_key(&press(KeyCode::Char('D'), KeyModifiers::SHIFT))
+    );
+    assert_eq!(
+        Some(Key::Up),
+        read_key(&press(KeyCode::Up, KeyModifiers::NONE))
+    );
+    assert_eq!(
+        Some(Key::Escape),
+        read_key(&press(KeyCode::Char('c'), KeyModifiers::CONTROL))
+    );
+    assert_eq!(
+        Some(Key::Escape),
+        read_key(&press(KeyCode::Esc, KeyModifiers::NONE))
+    );
+    assert_eq!(None, read_key(&press(KeyCode::F(1), KeyModifiers::NONE)));
+    // windows reports releases too, they aren't presses
+    let release = KeyEvent::new_with_kind(KeyCode::Left, KeyModifiers::NONE, KeyEventKind::Release);
+    assert_eq!(None, read_key(&Event::Key(release)));
+    assert_eq!(None, read_key(&Event::FocusGained));
+    assert_eq!(key_action(Key::Char('D')), Some((0, Action::MoveRight)));
+    assert_eq!(key_action(Key::Char('h')), Some((1, Action::Hold)));
+    assert_eq!(key_action(Key::Left), Some((1, Action::MoveLeft)));
+    assert_eq!(key_action(Key::Char('x')), None);
+}
+
+#[test]
+fn frame() {
+    let mut game = Game::new(
+        &Rules::default(),
+        0,
+        GameMode::Versus,
+        vec![Controller::Keyboard, Controller::Keyboard],
+        1,
+    )
+    .unwrap();
+    game.perform(0, Action::HardDrop);
+    game.update();
+    let frame = render(&game);
+    assert!(frame.starts_with("\x1b[H"));
+    // the header, the rows of the board and the bottom border
+    assert_eq!(frame.matches("\r\n").count(), VISIBLE_ROWS + 2);
+    assert!(frame.contains("Player 1  Score"));
+    assert!(frame.contains("Player 2  Score"));
+    // the dropped piece and both falling pieces are drawn
+    assert!(frame.matches("\x1b[48;5;").count() >= 12);
+}